@@ -0,0 +1,591 @@
+//! Telegram bot that converts the media sent by the users into emoji and
+//! stickers, so that they don't need to run the CLI themselves
+
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::telegram::{
+    BotApi, CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MediaFile,
+    Message, Update,
+};
+use crate::video::{MultiVideoGenContext, PackKind};
+use buildstructor::buildstructor;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_QUEUE: usize = 3;
+
+/// How long the inline buttons of the prompt stay usable
+const MEDIA_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Max number of the prompts waiting for the users to press the buttons.
+/// The oldest ones become outdated when the limit is reached.
+const MAX_PENDING_MEDIA: usize = 10_000;
+
+/// Delay before receiving the updates again after a failure
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const HELP: &str = "Send me a video, a GIF or an image, and I'll turn it into \
+    a Telegram emoji or sticker";
+
+/// Pack kinds that the users can choose from
+const PACK_KINDS: [(PackKind, &str); 2] = [
+    (PackKind::Emoji, "😀 Emoji"),
+    (PackKind::Sticker, "🖼 Sticker"),
+];
+
+pub(crate) struct BotContext {
+    api: BotApi,
+    ffmpeg: Arc<dyn Ffmpeg>,
+
+    /// Max number of media converted at the same time for all users
+    concurrency: NonZeroUsize,
+
+    /// Max number of jobs a single user can have in the queue
+    max_queue: usize,
+
+    /// How long a single `getUpdates` request waits for the updates
+    poll_timeout: Duration,
+}
+
+/// State shared between the handlers of the updates
+struct Bot {
+    api: BotApi,
+    ffmpeg: Arc<dyn Ffmpeg>,
+    max_queue: usize,
+
+    /// Limits the number of media converted at the same time
+    jobs: Semaphore,
+
+    /// Media that the prompts with the inline buttons were sent for by the
+    /// chat ID and the prompt message ID. The entry is removed when the job
+    /// is queued, so every prompt makes at most one job.
+    media: Mutex<HashMap<(i64, i64), Media>>,
+
+    /// Queues of the jobs by the user ID
+    queues: Mutex<HashMap<i64, UserQueue>>,
+}
+
+#[derive(Clone)]
+struct Media {
+    file_id: String,
+
+    /// The message with the media, that the result is sent in reply to
+    message_id: i64,
+
+    /// When the prompt was sent
+    created: Instant,
+}
+
+#[derive(Default)]
+struct UserQueue {
+    /// Makes the jobs of the user run one by one in the order they were queued
+    lock: Arc<tokio::sync::Mutex<()>>,
+
+    /// Number of the jobs that are queued or running
+    len: usize,
+}
+
+struct Job {
+    chat_id: i64,
+    media: Media,
+    pack_kind: PackKind,
+
+    /// Frees the place in the queue of the user when the job is done
+    slot: QueueSlot,
+}
+
+/// Place of the job in the queue of the user
+struct QueueSlot {
+    bot: Arc<Bot>,
+    user_id: i64,
+
+    /// Lock of the queue of the user
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+#[buildstructor]
+impl BotContext {
+    #[builder]
+    pub(crate) fn new(
+        api: BotApi,
+        ffmpeg: Option<Arc<dyn Ffmpeg>>,
+        concurrency: Option<NonZeroUsize>,
+        max_queue: Option<usize>,
+        poll_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            api,
+            ffmpeg: ffmpeg.unwrap_or_else(|| Arc::new(crate::ffmpeg::FfmpegProcess)),
+            concurrency: concurrency
+                .unwrap_or_else(|| MultiVideoGenContext::default_concurrency("")),
+            max_queue: max_queue.unwrap_or(DEFAULT_MAX_QUEUE),
+            poll_timeout: poll_timeout.unwrap_or(DEFAULT_POLL_TIMEOUT),
+        }
+    }
+}
+
+impl BotContext {
+    /// Receives the updates with long polling until the process is stopped
+    pub(crate) async fn run(self) -> Result {
+        let me = self.api.get_me().await?;
+
+        info!(
+            "🤖 Started the bot {}",
+            display::bold(&me.username.unwrap_or_default())
+        );
+
+        let bot = Arc::new(Bot {
+            api: self.api,
+            ffmpeg: self.ffmpeg,
+            max_queue: self.max_queue,
+            jobs: Semaphore::new(self.concurrency.get()),
+            media: Default::default(),
+            queues: Default::default(),
+        });
+
+        let mut offset = 0;
+
+        loop {
+            let updates = match bot.api.get_updates(offset, self.poll_timeout).await {
+                Ok(updates) => updates,
+                Err(err) => {
+                    warn!("Failed to receive the updates, retrying in {RETRY_DELAY:?}: {err:#}");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = offset.max(update.update_id + 1);
+
+                let bot = bot.clone();
+                let span = info_span!("update", id = update.update_id);
+
+                tokio::spawn(
+                    async move {
+                        if let Err(err) = bot.handle(update).await {
+                            warn!("Failed to handle the update: {err:#}");
+                        }
+                    }
+                    .instrument(span),
+                );
+            }
+        }
+    }
+}
+
+impl Bot {
+    async fn handle(self: Arc<Self>, update: Update) -> Result {
+        if let Some(message) = update.message {
+            return self.on_message(message).await;
+        }
+
+        if let Some(query) = update.callback_query {
+            return self.on_callback_query(query).await;
+        }
+
+        Ok(())
+    }
+
+    /// Asks the user what to generate from the media
+    async fn on_message(&self, message: Message) -> Result {
+        let chat_id = message.chat.id;
+
+        let Some(file) = media_file(&message) else {
+            self.api.send_message(chat_id, HELP, None, None).await?;
+            return Ok(());
+        };
+
+        let buttons = PACK_KINDS
+            .iter()
+            .map(|(kind, text)| InlineKeyboardButton {
+                text: (*text).to_owned(),
+                callback_data: kind.to_string(),
+            })
+            .collect();
+
+        let keyboard = InlineKeyboardMarkup {
+            inline_keyboard: vec![buttons],
+        };
+
+        let prompt = self
+            .api
+            .send_message(
+                chat_id,
+                "What should I make of it?",
+                Some(message.message_id),
+                Some(&keyboard),
+            )
+            .await?;
+
+        let media = Media {
+            file_id: file.file_id.clone(),
+            message_id: message.message_id,
+            created: Instant::now(),
+        };
+
+        let mut pending = self.media.lock().unwrap();
+
+        pending.retain(|_, media| media.created.elapsed() < MEDIA_TTL);
+
+        if pending.len() >= MAX_PENDING_MEDIA {
+            let oldest = pending
+                .iter()
+                .min_by_key(|(_, media)| media.created)
+                .map(|(key, _)| *key);
+
+            if let Some(oldest) = oldest {
+                pending.remove(&oldest);
+            }
+        }
+
+        pending.insert((chat_id, prompt.message_id), media);
+
+        Ok(())
+    }
+
+    /// Queues the job when the user presses the inline button
+    async fn on_callback_query(self: Arc<Self>, query: CallbackQuery) -> Result {
+        let pack_kind = PACK_KINDS
+            .iter()
+            .map(|(kind, _)| *kind)
+            .find(|kind| query.data.as_deref() == Some(&kind.to_string()));
+
+        let user_id = query.from.id;
+
+        let job = match self.queue_job(pack_kind, query.message.as_ref(), user_id) {
+            Ok(job) => job,
+            Err(text) => return self.api.answer_callback_query(&query.id, Some(&text)).await,
+        };
+
+        self.api
+            .answer_callback_query(&query.id, Some("⏳ Working on it"))
+            .await?;
+
+        let span = info_span!("job", user = user_id, pack = %job.pack_kind);
+
+        tokio::spawn(
+            async move {
+                let result = async {
+                    let _queue = job.slot.lock.lock().await;
+                    let _permit = self.jobs.acquire().await?;
+                    self.convert(&job).await
+                }
+                .await;
+
+                if let Err(err) = result {
+                    warn!("Failed to convert the media: {err:#}");
+
+                    let text = format!("❌ Failed to make the {}: {err:#}", job.pack_kind);
+
+                    self.api
+                        .send_message(job.chat_id, &text, Some(job.media.message_id), None)
+                        .await?;
+                }
+
+                anyhow::Ok(())
+            }
+            .instrument(span),
+        );
+
+        Ok(())
+    }
+
+    /// Takes the media of the prompt and reserves the place for its job in
+    /// the queue of the user. Returns the answer to the user if it's impossible.
+    fn queue_job(
+        self: &Arc<Self>,
+        pack_kind: Option<PackKind>,
+        prompt: Option<&Message>,
+        user_id: i64,
+    ) -> Result<Job, String> {
+        let outdated = || "This button is outdated, send the media again".to_owned();
+
+        let (Some(pack_kind), Some(prompt)) = (pack_kind, prompt) else {
+            return Err(outdated());
+        };
+
+        let key = (prompt.chat.id, prompt.message_id);
+        let mut pending = self.media.lock().unwrap();
+
+        let media = pending.remove(&key).ok_or_else(outdated)?;
+
+        if media.created.elapsed() >= MEDIA_TTL {
+            return Err(outdated());
+        }
+
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(user_id).or_default();
+
+        if queue.len >= self.max_queue {
+            // The media is kept, so the user can press the button later
+            pending.insert(key, media);
+
+            return Err(format!(
+                "You already have {} jobs in the queue, wait for them to finish",
+                self.max_queue
+            ));
+        }
+
+        queue.len += 1;
+
+        let slot = QueueSlot {
+            bot: self.clone(),
+            user_id,
+            lock: queue.lock.clone(),
+        };
+
+        Ok(Job {
+            chat_id: prompt.chat.id,
+            media,
+            pack_kind,
+            slot,
+        })
+    }
+
+    async fn convert(&self, job: &Job) -> Result {
+        let file = self.api.get_file(&job.media.file_id).await?;
+
+        let file_path = file
+            .file_path
+            .context("The file is too big. Bots can download files up to 20 MB.")?;
+
+        let bytes = self.api.download_file(&file_path).await?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path().unwrap_utf8();
+
+        // The extension helps ffmpeg and the pipeline to detect the format
+        let extension = Utf8Path::new(&file_path).extension().unwrap_or("mp4");
+        let input = dir.join(format!("input.{extension}"));
+
+        fs::write(&input, bytes).await?;
+
+        let context = MultiVideoGenContext::builder()
+            .pack_kind(job.pack_kind)
+            .input(input)
+            .output(dir.to_owned())
+            .overwrite(true)
+            .ffmpeg(self.ffmpeg.clone())
+            .build()?
+            .contexts()
+            .await?
+            .into_iter()
+            .next()
+            .context("BUG: no outputs were generated for the input")?;
+
+        let (crf, output) = context.search_crf().await?;
+
+        let document = InputFile {
+            file_name: format!("{}.webm", job.pack_kind),
+            bytes: output.to_vec(),
+        };
+
+        let caption = format!("CRF {crf}, {}", display::human_size(output.len()));
+
+        self.api
+            .send_document(job.chat_id, document, &caption, Some(job.media.message_id))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        let mut queues = self.bot.queues.lock().unwrap();
+
+        if let Some(queue) = queues.get_mut(&self.user_id) {
+            queue.len -= 1;
+
+            // Nobody waits for the lock of the empty queue
+            if queue.len == 0 {
+                queues.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+/// Returns the media file of the message that can be converted
+fn media_file(message: &Message) -> Option<&MediaFile> {
+    message
+        .video
+        .as_ref()
+        .or(message.animation.as_ref())
+        .or(message.document.as_ref())
+        // The last photo size is the largest one
+        .or_else(|| message.photo.as_ref()?.last())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::testing::{FakeBotApi, SentMessage};
+    use crate::video::testing::SharedMockFfmpeg;
+    use expect_test::expect;
+    use serde_json::json;
+
+    /// Waits until the bot sends the given number of messages
+    async fn wait_for_messages(fake: &FakeBotApi, count: usize) -> Vec<SentMessage> {
+        for _ in 0..500 {
+            let messages = fake.sent_messages();
+            if messages.len() >= count {
+                return messages;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!(
+            "The bot didn't send {count} messages: {:?}",
+            fake.sent_messages()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_bot() {
+        let fake = FakeBotApi::start().await;
+
+        let file_id = fake.add_file("clip.mp4", b"clip");
+
+        let bot = BotContext::builder()
+            .api(fake.client())
+            .ffmpeg(SharedMockFfmpeg::with_best_crf(25, PackKind::Emoji))
+            .poll_timeout(Duration::ZERO)
+            .build();
+
+        let bot = tokio::spawn(bot.run());
+
+        fake.push_update(json!({
+            "message": {
+                "message_id": 10,
+                "chat": { "id": 5 },
+                "video": { "file_id": file_id },
+            }
+        }));
+
+        let prompt = wait_for_messages(&fake, 1).await.remove(0);
+
+        fake.push_update(json!({
+            "callback_query": {
+                "id": "query",
+                "from": { "id": 7 },
+                "message": { "message_id": prompt.message_id, "chat": { "id": 5 } },
+                "data": "emoji",
+            }
+        }));
+
+        let messages = wait_for_messages(&fake, 2).await;
+
+        bot.abort();
+
+        let messages = messages
+            .iter()
+            .map(|message| {
+                let buttons = message
+                    .buttons
+                    .iter()
+                    .map(|(text, data)| format!("[{text} → {data}]"));
+                let document = message
+                    .document
+                    .iter()
+                    .map(|(name, len)| format!("<{name}, {len} bytes>"));
+
+                format!(
+                    "chat {} in reply to {:?}: {} {}",
+                    message.chat_id,
+                    message.reply_to,
+                    message.text,
+                    buttons.chain(document).join(" "),
+                )
+            })
+            .join("\n");
+
+        expect![[r#"
+            chat 5 in reply to Some(10): What should I make of it? [😀 Emoji → emoji] [🖼 Sticker → sticker]
+            chat 5 in reply to Some(10): CRF 25, 64 KiB <emoji.webm, 65536 bytes>"#]]
+        .assert_eq(&messages);
+
+        let calls = fake
+            .calls()
+            .into_iter()
+            .filter(|call| call != "getUpdates")
+            .collect_vec();
+
+        expect![[r#"
+            [
+                "getMe",
+                "sendMessage",
+                "answerCallbackQuery",
+                "getFile",
+                "sendDocument",
+            ]
+        "#]]
+        .assert_debug_eq(&calls);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn button_pressed_twice() {
+        let fake = FakeBotApi::start().await;
+
+        let file_id = fake.add_file("clip.mp4", b"clip");
+
+        let bot = BotContext::builder()
+            .api(fake.client())
+            .ffmpeg(SharedMockFfmpeg::with_best_crf(25, PackKind::Sticker))
+            .poll_timeout(Duration::ZERO)
+            .build();
+
+        let bot = tokio::spawn(bot.run());
+
+        fake.push_update(json!({
+            "message": {
+                "message_id": 10,
+                "chat": { "id": 5 },
+                "video": { "file_id": file_id },
+            }
+        }));
+
+        let prompt = wait_for_messages(&fake, 1).await.remove(0);
+
+        for id in ["first", "second"] {
+            fake.push_update(json!({
+                "callback_query": {
+                    "id": id,
+                    "from": { "id": 7 },
+                    "message": { "message_id": prompt.message_id, "chat": { "id": 5 } },
+                    "data": "sticker",
+                }
+            }));
+        }
+
+        wait_for_messages(&fake, 2).await;
+
+        // Let the second job finish if it was queued by mistake
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        bot.abort();
+
+        let mut answers = fake.callback_answers();
+        answers.sort();
+
+        expect![[r#"
+            [
+                "This button is outdated, send the media again",
+                "⏳ Working on it",
+            ]
+        "#]]
+        .assert_debug_eq(&answers);
+
+        let documents = fake
+            .sent_messages()
+            .iter()
+            .filter(|message| message.document.is_some())
+            .count();
+
+        assert_eq!(documents, 1);
+    }
+}
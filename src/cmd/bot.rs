@@ -0,0 +1,41 @@
+use crate::bot::BotContext;
+use crate::cmd::BotApiArgs;
+use crate::prelude::*;
+use async_trait::async_trait;
+use clap::Parser;
+use std::num::NonZeroUsize;
+
+/// Run a Telegram bot that converts the media sent to it into emoji and stickers
+///
+/// Users send a video, a GIF or an image to the bot and pick whether they
+/// want an emoji or a sticker with the inline buttons. The bot replies with
+/// the generated WEBM file and its CRF and size.
+///
+/// The updates are received with long polling, so the bot doesn't need
+/// a public address. Make sure no webhook is set for the bot.
+#[derive(Parser, Debug)]
+pub struct Bot {
+    /// Max number of media converted at the same time for all users
+    #[clap(long)]
+    concurrency: Option<NonZeroUsize>,
+
+    /// Max number of jobs a single user can have in the queue
+    #[clap(long, default_value_t = 3)]
+    max_queue: usize,
+
+    #[clap(flatten)]
+    bot: BotApiArgs,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Bot {
+    async fn run(self) -> Result {
+        BotContext::builder()
+            .api(self.bot.client())
+            .and_concurrency(self.concurrency)
+            .max_queue(self.max_queue)
+            .build()
+            .run()
+            .await
+    }
+}
@@ -0,0 +1,107 @@
+use crate::cmd::PackKindArgs;
+use crate::prelude::*;
+use crate::video::{ConcatGenContext, ConcatPart, MultiVideoGenContext, Transition};
+use async_trait::async_trait;
+use clap::Parser;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+/// Concatenate several videos or images into a single emoji or sticker
+///
+/// The parts are scaled and padded to the same resolution and frame rate
+/// before they are joined, so they may have different sizes. The result goes
+/// through the same scaling, padding and CRF search as `tstick video`.
+///
+/// The output files are named `{name}-{emoji|sticker}.webm`.
+#[derive(Parser, Debug)]
+pub struct Concat {
+    #[clap(flatten)]
+    pack_kinds: PackKindArgs,
+
+    /// Part of the output in format `path[@duration]`. The parts are joined
+    /// in the order they are specified.
+    ///
+    /// The duration limits how long the part is shown. By default, videos are
+    /// shown entirely, and still images are shown during `--still-duration`.
+    /// The suffix after the last `@` is taken as the duration only if it's
+    /// a valid one, so paths like `me@2x.png` are kept intact.
+    #[clap(long, short, value_parser = ConcatPart::parse, required_unless_present = "spec_file")]
+    input: Vec<ConcatPart>,
+
+    /// Path to the file with the parts, one `path[@duration]` per line.
+    /// Empty lines and lines starting with `#` are ignored. Relative paths
+    /// are resolved against the directory of the spec file.
+    #[clap(long, conflicts_with = "input")]
+    spec_file: Option<Utf8PathBuf>,
+
+    /// Transition between the parts in format `name[:duration]`, where the
+    /// name is one of the `xfade` ffmpeg filter transitions, e.g. `fade`,
+    /// `wipeleft`, `slideup`, `circleopen`. The default duration is 0.25 seconds.
+    ///
+    /// The full list of transitions: <https://trac.ffmpeg.org/wiki/Xfade>
+    #[clap(long, value_parser = Transition::parse)]
+    transition: Option<Transition>,
+
+    /// Path to the output directory. If not specified, the directory of the
+    /// first part is used.
+    #[clap(long, short)]
+    output: Option<Utf8PathBuf>,
+
+    /// Name of the output files without the `-{emoji|sticker}.webm` suffix
+    #[clap(long, default_value = "concat")]
+    name: String,
+
+    /// Overwrite the output files if they already exist, without asking for confirmation
+    #[clap(long)]
+    overwrite: bool,
+
+    /// Set the `publisher` metadata of the generated emoji/sticker WEBM files
+    #[clap(long)]
+    publisher: Option<String>,
+
+    /// How long the still image parts without an explicit duration are shown
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    still_duration: Option<Duration>,
+
+    /// The value of the video filter flag that will be passed to ffmpeg
+    /// after the parts are concatenated
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Maximum number of outputs to be proceesed in parallel.
+    #[clap(long, default_value_t = default_concurrency())]
+    concurrency: NonZeroUsize,
+
+    /// Additional arguments that will be passed to ffmpeg between the input and output args.
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    ffmpeg_args: Vec<String>,
+}
+
+fn default_concurrency() -> NonZeroUsize {
+    MultiVideoGenContext::default_concurrency(
+        " HINT: The value of concurrency may be overriden with the \
+        `--concurrency` flag.",
+    )
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Concat {
+    async fn run(self) -> Result {
+        ConcatGenContext::builder()
+            .pack_kinds(self.pack_kinds.pack_kinds())
+            .parts(self.input)
+            .and_spec_file(self.spec_file)
+            .and_transition(self.transition)
+            .and_output(self.output)
+            .name(self.name)
+            .and_still_duration(self.still_duration)
+            .and_filter(self.filter)
+            .ffmpeg_args(self.ffmpeg_args)
+            .concurrency(self.concurrency)
+            .overwrite(self.overwrite)
+            .and_publisher(self.publisher)
+            .build()?
+            .run()
+            .await
+    }
+}
@@ -0,0 +1,102 @@
+use crate::prelude::*;
+use crate::video::{GridGenContext, LoopMode, MultiVideoGenContext};
+use async_trait::async_trait;
+use clap::Parser;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::time::Duration;
+
+/// Split a video into a grid of custom emoji tiles
+///
+/// The tiles form a single big animation when they are typed in rows.
+/// All tiles are encoded with the same CRF, so that the quality doesn't
+/// visibly jump between them.
+///
+/// The output files are named `{input_file_name}-{tile_number}-emoji.webm`,
+/// where the tiles are numbered row by row. The order in which the tiles
+/// need to be uploaded to the pack is also written to the file
+/// `{input_file_name}-grid.txt`.
+#[derive(Parser, Debug)]
+pub struct Grid {
+    /// Path to the input media file
+    #[clap(long, short)]
+    input: Utf8PathBuf,
+
+    /// Path to the output directory where the tiles will be put.
+    /// If not specified, the directory of the input file is used.
+    #[clap(long, short)]
+    output: Option<Utf8PathBuf>,
+
+    /// Number of columns in the grid
+    #[clap(long)]
+    cols: NonZeroU32,
+
+    /// Number of rows in the grid
+    #[clap(long)]
+    rows: NonZeroU32,
+
+    /// Overwrite the output files if they already exist, without asking for confirmation
+    #[clap(long)]
+    overwrite: bool,
+
+    /// Set the `publisher` metadata of the generated emoji WEBM files
+    #[clap(long)]
+    publisher: Option<String>,
+
+    /// The time from which the video will be cut.
+    ///
+    /// The total video duration must not exceed 3 seconds.
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    begin: Option<Duration>,
+
+    /// The time to which the video will be cut.
+    ///
+    /// The total video duration must not exceed 3 seconds.
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    end: Option<Duration>,
+
+    /// Make the video loop seamlessly. See `tstick video --help` for details.
+    #[clap(long = "loop", value_parser = LoopMode::parse)]
+    loop_mode: Option<LoopMode>,
+
+    /// The value of the video filter flag that will be passed to ffmpeg
+    /// before splitting the video into tiles
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Maximum number of tiles to be proceesed in parallel.
+    #[clap(long, default_value_t = default_concurrency())]
+    concurrency: NonZeroUsize,
+
+    /// Additional arguments that will be passed to ffmpeg between the input and output args.
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    ffmpeg_args: Vec<String>,
+}
+
+fn default_concurrency() -> NonZeroUsize {
+    MultiVideoGenContext::default_concurrency(
+        " HINT: The value of concurrency may be overriden with the \
+        `--concurrency` flag.",
+    )
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Grid {
+    async fn run(self) -> Result {
+        GridGenContext::builder()
+            .input(self.input)
+            .and_output(self.output)
+            .cols(self.cols)
+            .rows(self.rows)
+            .ffmpeg_args(self.ffmpeg_args)
+            .concurrency(self.concurrency)
+            .overwrite(self.overwrite)
+            .and_begin(self.begin)
+            .and_end(self.end)
+            .and_loop_mode(self.loop_mode)
+            .and_filter(self.filter)
+            .and_publisher(self.publisher)
+            .build()
+            .run()
+            .await
+    }
+}
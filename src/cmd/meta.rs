@@ -0,0 +1,46 @@
+use crate::meta::MetaContext;
+use crate::prelude::*;
+use async_trait::async_trait;
+use clap::Parser;
+
+/// Suggest the emoji and keywords of the stickers by their file names
+///
+/// The emoji and keywords of a sticker are kept in the `{stem}.meta.toml`
+/// file next to it:
+///
+/// ```toml
+/// emoji    = ["🔥", "💃"]
+/// keywords = ["fire", "dance"]
+/// ```
+///
+/// They are read by the `pack` commands, and the `video` command copies them
+/// next to the generated files.
+///
+/// This command writes such files for the inputs that don't have them yet.
+/// The emoji are looked up by the words of the file name in the bundled
+/// database of emoji names and shortcodes, for example `fire_dance.mp4` gets
+/// `🔥💃`. The files without any emoji found are reported to be filled
+/// manually.
+#[derive(Parser, Debug)]
+pub struct Meta {
+    /// Path to the media file(s) or directory(ies) containing them
+    #[clap(long, short, required = true)]
+    input: Vec<Utf8PathBuf>,
+
+    /// Don't write anything, but fail if some of the files don't have
+    /// the emoji in their `{stem}.meta.toml` files yet
+    #[clap(long)]
+    check: bool,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Meta {
+    async fn run(self) -> Result {
+        MetaContext::builder()
+            .inputs(self.input)
+            .check(self.check)
+            .build()
+            .run()
+            .await
+    }
+}
@@ -1,8 +1,24 @@
+mod bot;
+mod concat;
+mod grid;
+mod meta;
+mod pack;
+mod serve;
+mod tgs;
+mod thumbnail;
 mod video;
 
 use crate::prelude::*;
 use async_trait::async_trait;
 
+pub use bot::*;
+pub use concat::*;
+pub use grid::*;
+pub use meta::*;
+pub use pack::*;
+pub use serve::*;
+pub use tgs::*;
+pub use thumbnail::*;
 pub use video::*;
 
 #[async_trait]
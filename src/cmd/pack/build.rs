@@ -0,0 +1,42 @@
+use crate::pack::{BuildContext, MANIFEST_FILE_NAME};
+use crate::prelude::*;
+use async_trait::async_trait;
+use clap::Parser;
+use std::num::NonZeroUsize;
+
+/// Generate the files of the pack described in a TOML manifest
+///
+/// The manifest lists the source of every sticker with its own trim, filter,
+/// pack kinds, emoji and keywords, plus the pack-wide defaults. Only the
+/// stickers whose sources or settings changed since the last build are
+/// regenerated.
+#[derive(Parser, Debug)]
+pub(crate) struct Build {
+    /// Path to the pack manifest
+    #[clap(long, short, default_value = MANIFEST_FILE_NAME)]
+    manifest: Utf8PathBuf,
+
+    /// Rebuild all stickers even if they are up to date
+    #[clap(long)]
+    force: bool,
+
+    /// Maximum number of outputs to be generated in parallel. Overrides the
+    /// `concurrency` from the manifest.
+    #[clap(long)]
+    concurrency: Option<NonZeroUsize>,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Build {
+    async fn run(self) -> Result {
+        BuildContext::builder()
+            .manifest(self.manifest)
+            .force(self.force)
+            .and_concurrency(self.concurrency)
+            .build()
+            .run()
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use super::BotApiArgs;
+use crate::pack::ImportContext;
+use crate::prelude::*;
+use async_trait::async_trait;
+use clap::Parser;
+
+/// Download an existing sticker or custom emoji set and write a pack manifest for it
+///
+/// Every WEBM, TGS and WEBP file of the set is downloaded into the `stickers`
+/// directory of the output. The manifest keeps the order, the emoji and the
+/// type of the set, so the pack can be regenerated with `pack build` or
+/// re-published with `pack sync`. The lockfile written next to the manifest
+/// lets `pack sync` update the stickers of the set in place instead of
+/// uploading them again.
+///
+/// The Bot API returns only the first emoji of every sticker and no search
+/// keywords, so they need to be restored in the manifest manually.
+#[derive(Parser, Debug)]
+pub(crate) struct Import {
+    /// Short name of the set, i.e. the last segment of its
+    /// `t.me/addstickers/{name}` or `t.me/addemoji/{name}` link
+    name: String,
+
+    /// Directory where the manifest and the files are put. It's named after
+    /// the set in the current directory by default.
+    #[clap(long, short)]
+    output: Option<Utf8PathBuf>,
+
+    /// Overwrite the existing files without asking for confirmation
+    #[clap(long)]
+    overwrite: bool,
+
+    #[clap(flatten)]
+    bot: BotApiArgs,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Import {
+    async fn run(self) -> Result {
+        let output = self.output.unwrap_or_else(|| self.name.clone().into());
+
+        ImportContext::builder()
+            .api(self.bot.client())
+            .name(self.name)
+            .output(output)
+            .overwrite(self.overwrite)
+            .build()
+            .run()
+            .await
+    }
+}
@@ -0,0 +1,75 @@
+mod build;
+mod import;
+mod sync;
+mod upload;
+
+use crate::cmd::Cmd;
+use crate::prelude::*;
+use crate::telegram::{self, BotApi, RetryPolicy};
+use async_trait::async_trait;
+use clap::{Args, Parser, Subcommand};
+
+/// Manage Telegram sticker and custom emoji sets through the Bot API
+#[derive(Parser, Debug)]
+pub struct Pack {
+    #[clap(subcommand)]
+    cmd: PackCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum PackCmd {
+    Build(build::Build),
+    Upload(upload::Upload),
+    Sync(sync::Sync),
+    Import(import::Import),
+}
+
+#[async_trait]
+impl Cmd for Pack {
+    async fn run(self) -> Result {
+        match self.cmd {
+            PackCmd::Build(cmd) => cmd.run().await,
+            PackCmd::Upload(cmd) => cmd.run().await,
+            PackCmd::Sync(cmd) => cmd.run().await,
+            PackCmd::Import(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// Options of the connection to the Bot API
+#[derive(Args, Debug)]
+#[clap(next_help_heading = "Bot API")]
+pub(crate) struct BotApiArgs {
+    /// Token of the bot that owns the sets. Get it from @BotFather.
+    #[clap(long, env = "TSTICK_BOT_TOKEN", hide_env_values = true)]
+    token: String,
+
+    /// URL of the Bot API server. Override it to use a local Bot API server.
+    #[clap(
+        long,
+        env = "TSTICK_BOT_API_URL",
+        hide_env_values = true,
+        default_value = telegram::DEFAULT_BASE_URL
+    )]
+    api_url: String,
+
+    /// Max number of retries of a request that hit the flood limits, failed
+    /// with a server error or didn't reach the server. The delay between
+    /// the retries grows exponentially, and the `retry_after` from Telegram
+    /// is respected.
+    #[clap(long, default_value_t = RetryPolicy::default().max_retries)]
+    max_retries: u32,
+}
+
+impl BotApiArgs {
+    pub(crate) fn client(&self) -> BotApi {
+        BotApi::builder()
+            .token(self.token.clone())
+            .base_url(self.api_url.clone())
+            .retry(RetryPolicy {
+                max_retries: self.max_retries,
+                ..Default::default()
+            })
+            .build()
+    }
+}
@@ -0,0 +1,69 @@
+use super::BotApiArgs;
+use crate::pack::{QrOutput, SyncContext, MANIFEST_FILE_NAME};
+use crate::prelude::*;
+use async_trait::async_trait;
+use clap::Parser;
+
+/// Make the published set match the pack manifest
+///
+/// The pack is built first, then the set is fetched through the Bot API and
+/// compared with the manifest and the lockfile next to it. The lockfile
+/// remembers which published sticker every manifest item corresponds to.
+/// The plan of the changes (add, replace, move, delete, update emoji or
+/// keywords) is printed and applied after the confirmation. The link to
+/// the set is printed at the end.
+///
+/// The sync refuses to delete the stickers of the set that aren't recorded
+/// in the lockfile, e.g. if the set was published with `tstick pack upload`
+/// or the lockfile was lost. Use `tstick pack import` to get the manifest
+/// with the lockfile for such a set, or pass `--force` to replace them.
+#[derive(Parser, Debug)]
+pub(crate) struct Sync {
+    /// Path to the pack manifest
+    #[clap(long, short, default_value = MANIFEST_FILE_NAME)]
+    manifest: Utf8PathBuf,
+
+    /// ID of the Telegram user that owns the set
+    #[clap(long)]
+    user_id: i64,
+
+    /// Apply the plan without asking for confirmation
+    #[clap(long, short)]
+    yes: bool,
+
+    /// Delete the stickers of the set that aren't recorded in the lockfile
+    /// and upload the items of the manifest instead of them
+    #[clap(long)]
+    force: bool,
+
+    /// Output the QR code with the link to the set after publishing it.
+    /// `terminal` (default) renders it in the terminal, `png` writes it to
+    /// the `{name}.qr.png` file next to the outputs of the pack.
+    #[clap(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "terminal"
+    )]
+    qr: Option<QrOutput>,
+
+    #[clap(flatten)]
+    bot: BotApiArgs,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Sync {
+    async fn run(self) -> Result {
+        SyncContext::builder()
+            .api(self.bot.client())
+            .user_id(self.user_id)
+            .manifest(self.manifest)
+            .yes(self.yes)
+            .force(self.force)
+            .and_qr(self.qr)
+            .build()
+            .run()
+            .await
+    }
+}
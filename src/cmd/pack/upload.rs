@@ -0,0 +1,102 @@
+use super::BotApiArgs;
+use crate::pack::{QrOutput, UploadContext};
+use crate::prelude::*;
+use crate::telegram::StickerType;
+use async_trait::async_trait;
+use clap::Parser;
+
+/// Upload the generated files as a sticker set or a custom emoji set
+///
+/// If the set doesn't exist, it's created, otherwise the files are added
+/// to the end of it. The files are added in the order of their names.
+///
+/// Every sticker must have at least one emoji. Nothing is uploaded if some
+/// of them don't have any.
+///
+/// If the upload fails, its progress is kept in the `{name}.upload.toml`
+/// journal next to the files. Running the same command again continues it
+/// without adding the same stickers twice.
+///
+/// The link to the set is printed after publishing it.
+#[derive(Parser, Debug)]
+pub(crate) struct Upload {
+    /// Path to the sticker file(s) or directory(ies) containing them.
+    /// WEBM videos, TGS animations and WEBP/PNG images are supported.
+    #[clap(long, short, required = true)]
+    input: Vec<Utf8PathBuf>,
+
+    /// ID of the Telegram user that will own the set
+    #[clap(long)]
+    user_id: i64,
+
+    /// Short name of the set used in the `t.me/addstickers/{name}` links.
+    /// It must end with `_by_{bot_username}`.
+    #[clap(long)]
+    name: String,
+
+    /// Title of the set. Required if the set doesn't exist yet.
+    #[clap(long)]
+    title: Option<String>,
+
+    /// Create a custom emoji set instead of a sticker set
+    #[clap(long)]
+    custom_emoji: bool,
+
+    /// Create the custom emoji set with `needs_repainting`, so that Telegram
+    /// recolors the emoji into the color of the text. The emoji must be
+    /// white silhouettes, see `tstick video --repaintable`. It has no effect
+    /// on the existing sets.
+    #[clap(long, requires = "custom_emoji")]
+    repaintable: bool,
+
+    /// Emoji associated with the uploaded stickers that don't have them
+    /// in the `{stem}.meta.toml` files next to them. See `tstick meta`.
+    #[clap(long)]
+    emoji: Vec<String>,
+
+    /// Search keywords of the uploaded stickers that don't have them
+    /// in the `{stem}.meta.toml` files next to them
+    #[clap(long)]
+    keyword: Vec<String>,
+
+    /// Output the QR code with the link to the set after publishing it.
+    /// `terminal` (default) renders it in the terminal, `png` writes it to
+    /// the `{name}.qr.png` file next to the uploaded files.
+    #[clap(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "terminal"
+    )]
+    qr: Option<QrOutput>,
+
+    #[clap(flatten)]
+    bot: BotApiArgs,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Upload {
+    async fn run(self) -> Result {
+        let sticker_type = if self.custom_emoji {
+            StickerType::CustomEmoji
+        } else {
+            StickerType::Regular
+        };
+
+        UploadContext::builder()
+            .api(self.bot.client())
+            .user_id(self.user_id)
+            .name(self.name)
+            .and_title(self.title)
+            .sticker_type(sticker_type)
+            .needs_repainting(self.repaintable)
+            .inputs(self.input)
+            .emoji(self.emoji)
+            .keywords(self.keyword)
+            .and_qr(self.qr)
+            .build()?
+            .run()
+            .await
+    }
+}
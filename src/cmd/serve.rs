@@ -0,0 +1,56 @@
+use crate::prelude::*;
+use crate::serve::ServeContext;
+use async_trait::async_trait;
+use clap::Parser;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+
+/// Run a local REST API server that converts the uploaded media into emoji
+/// and stickers
+///
+/// `POST /jobs` accepts a multipart form with the media in the `file` field
+/// and the JSON options in the `options` field. The options have the same
+/// names and meaning as the flags of the `video` command, for example
+/// `{"kinds": ["emoji"], "begin": "1.5", "loop": "pingpong"}`. The job is
+/// queued and its description is returned.
+///
+/// `GET /jobs` and `GET /jobs/{id}` return the status of the jobs.
+///
+/// `GET /jobs/{id}/events` streams the status of the job as server-sent
+/// events until the job finishes.
+///
+/// `DELETE /jobs/{id}` cancels the job.
+///
+/// `GET /jobs/{id}/outputs/{file}` downloads the generated file of a
+/// finished job.
+#[derive(Parser, Debug)]
+pub struct Serve {
+    /// Address to listen on. Beware that the API has no authentication.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// Directory where the jobs and their files are saved. The unfinished
+    /// jobs are restarted when the server starts again with the same store.
+    ///
+    /// If not specified, the jobs are kept in a temporary directory that is
+    /// removed when the server stops.
+    #[clap(long)]
+    store: Option<Utf8PathBuf>,
+
+    /// Max number of jobs that run at the same time
+    #[clap(long)]
+    concurrency: Option<NonZeroUsize>,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Serve {
+    async fn run(self) -> Result {
+        ServeContext::builder()
+            .listen(self.listen)
+            .and_store(self.store)
+            .and_concurrency(self.concurrency)
+            .build()
+            .run()
+            .await
+    }
+}
@@ -0,0 +1,52 @@
+use crate::prelude::*;
+use crate::tgs::TgsContext;
+use async_trait::async_trait;
+use clap::Parser;
+
+/// Convert Lottie animations into TGS animated stickers
+///
+/// The animation is optimized to reduce its size: the numbers are rounded,
+/// the unused assets, the editor metadata and the hidden layers are removed.
+/// Then it's validated against the Telegram requirements: 512x512 size,
+/// 60 fps, at most 3 seconds, no images and no expressions. The violations are
+/// reported with the JSON pointers to the offending values.
+///
+/// The output files are named `{input_file_name}.tgs`.
+#[derive(Parser, Debug)]
+pub struct Tgs {
+    /// Path to the Lottie JSON or TGS file(s) or directory(ies) with them.
+    /// Only the `.json` files are taken from the directories.
+    #[clap(long, short, required = true)]
+    input: Vec<Utf8PathBuf>,
+
+    /// Path to the output directory. If not specified, the output files are
+    /// put into the same directories where the input files are located.
+    #[clap(long, short)]
+    output: Option<Utf8PathBuf>,
+
+    /// Number of decimal places to keep in the floating point numbers.
+    /// The lower it is, the smaller the output, but the animation may
+    /// become less smooth.
+    ///
+    /// [default: 3]
+    #[clap(long)]
+    precision: Option<u32>,
+
+    /// Overwrite the output files if they already exist, without asking for confirmation
+    #[clap(long)]
+    overwrite: bool,
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Tgs {
+    async fn run(self) -> Result {
+        TgsContext::builder()
+            .inputs(self.input)
+            .and_output(self.output)
+            .and_precision(self.precision)
+            .overwrite(self.overwrite)
+            .build()
+            .run()
+            .await
+    }
+}
@@ -0,0 +1,151 @@
+use crate::prelude::*;
+use crate::video::{LoopMode, MultiVideoGenContext, PackKind};
+use async_trait::async_trait;
+use clap::Parser;
+use std::time::Duration;
+
+/// Generate a thumbnail of a sticker/emoji pack
+///
+/// The thumbnail is a 100x100 WEBM video that fits into 32 KB. It's shown
+/// in the list of packs in Telegram.
+///
+/// The output file is named `{input_file_name}-thumbnail.webm`.
+#[derive(Parser, Debug)]
+pub struct Thumbnail {
+    /// Path to the input media file or the directory with the pack files.
+    /// If the input is a directory, the first media file in it sorted by name
+    /// is used. The previously generated thumbnails are skipped.
+    #[clap(long, short)]
+    input: Utf8PathBuf,
+
+    /// Path to the output directory where the thumbnail will be put.
+    /// If not specified, the directory of the input file is used.
+    #[clap(long, short)]
+    output: Option<Utf8PathBuf>,
+
+    /// Overwrite the output file if it already exists, without asking for confirmation
+    #[clap(long)]
+    overwrite: bool,
+
+    /// Set the `publisher` metadata of the generated WEBM file
+    #[clap(long)]
+    publisher: Option<String>,
+
+    /// The time from which the video will be cut.
+    ///
+    /// The total video duration must not exceed 3 seconds.
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    begin: Option<Duration>,
+
+    /// The time to which the video will be cut.
+    ///
+    /// The total video duration must not exceed 3 seconds.
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    end: Option<Duration>,
+
+    /// Make the video loop seamlessly. See `tstick video --help` for details.
+    #[clap(long = "loop", value_parser = LoopMode::parse)]
+    loop_mode: Option<LoopMode>,
+
+    /// The value of the video filter flag that will be passed to ffmpeg
+    /// before rescaling it to the needed size
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Additional arguments that will be passed to ffmpeg between the input and output args.
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    ffmpeg_args: Vec<String>,
+}
+
+impl Thumbnail {
+    /// Returns the input file itself or the first media file of the pack
+    /// directory
+    async fn input_file(&self) -> Result<Utf8PathBuf> {
+        if !self.input.is_dir() {
+            return Ok(self.input.clone());
+        }
+
+        let thumbnail_suffix = format!("-{}", PackKind::Thumbnail);
+
+        let mut files = crate::fs::files(&self.input).await?;
+
+        files.retain(|file| {
+            let is_thumbnail =
+                matches!(file.file_stem(), Some(stem) if stem.ends_with(&thumbnail_suffix));
+            crate::video::is_media(file) && !is_thumbnail
+        });
+
+        files.sort();
+
+        let file = files.into_iter().next().with_context(|| {
+            format!(
+                "The pack directory `{}` doesn't contain any media files",
+                self.input
+            )
+        })?;
+
+        if file != self.input {
+            info!(
+                "🖼️ Using the first file of the pack: {}",
+                crate::display::bold(&file)
+            );
+        }
+
+        Ok(file)
+    }
+}
+
+#[async_trait]
+impl crate::cmd::Cmd for Thumbnail {
+    async fn run(self) -> Result {
+        let input = self.input_file().await?;
+
+        let output = self.output.or_else(|| {
+            // The thumbnail of the pack directory is put next to the pack files
+            self.input.is_dir().then(|| self.input.clone())
+        });
+
+        MultiVideoGenContext::builder()
+            .pack_kind(PackKind::Thumbnail)
+            .input(input)
+            .ffmpeg_args(self.ffmpeg_args)
+            .overwrite(self.overwrite)
+            .and_output(output)
+            .and_begin(self.begin)
+            .and_end(self.end)
+            .and_loop_mode(self.loop_mode)
+            .and_filter(self.filter)
+            .and_publisher(self.publisher)
+            .build()?
+            .run()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn input_file_skips_non_media() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let cmd = Thumbnail::parse_from(["thumbnail", "--input", dir.as_str()]);
+
+        for name in ["a-grid.txt", "a-thumbnail.webm", "a.meta.toml", "notes.md"] {
+            fs::write(dir.join(name), "").await.unwrap();
+        }
+
+        let err = cmd.input_file().await.unwrap_err();
+        assert!(
+            err.to_string().contains("doesn't contain any media files"),
+            "{err}"
+        );
+
+        fs::write(dir.join("b-emoji.webm"), "").await.unwrap();
+        fs::write(dir.join("c.png"), "").await.unwrap();
+
+        assert_eq!(cmd.input_file().await.unwrap(), dir.join("b-emoji.webm"));
+    }
+}
@@ -1,8 +1,12 @@
 use crate::prelude::*;
-use crate::video::{MultiVideoGenContext, PackKind};
+use crate::video::{
+    AnimationPreset, AutoWindowMetric, CaptionPosition, CaptionStyle, Fade, Flip, LoopMode,
+    MatteSource, MultiVideoGenContext, PackKind, Playback, Repaint, Rotation, SplitMode,
+    StaticFormat, Watermark, WatermarkContent, WatermarkCorner,
+};
 use async_trait::async_trait;
 use clap::{Args, Parser};
-use std::num::NonZeroUsize;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::time::Duration;
 
 /// Generate telegram emoji or sticker from a video using ffmpeg
@@ -12,6 +16,16 @@ use std::time::Duration;
 ///
 /// This command implements the two-pass method described in the following docs:
 /// <https://trac.ffmpeg.org/wiki/Encode/VP9>
+///
+/// Static emoji and stickers are generated as WEBP or PNG images instead.
+///
+/// Lottie animations (`.tgs` and `.json`) are accepted as inputs too. They are
+/// rendered frame by frame at up to 30 FPS into a lossless intermediate video
+/// first. Only shape, solid and precomposition layers with solid fills and
+/// strokes are supported; the animations with the other features are rejected.
+///
+/// SVG images are rasterized directly at the size of the output and then
+/// processed like the other still images.
 #[derive(Parser, Debug)]
 pub struct Video {
     #[clap(flatten)]
@@ -64,11 +78,102 @@ pub struct Video {
     #[clap(long, value_parser = crate::util::duration::parse)]
     end: Option<Duration>,
 
+    /// Split every input into several emoji/stickers. The outputs are named
+    /// `{input_file_name}-{segment_number}-{emoji|sticker}.webm`.
+    ///
+    /// `windows:{duration}` splits the input into consecutive windows of
+    /// the given duration.
+    ///
+    /// `scene[:threshold]` cuts the input at the scene changes detected by
+    /// ffmpeg. The threshold is in range `[0; 1]` (0.4 by default); the lower
+    /// it is, the more cuts there are.
+    ///
+    /// `ranges:{begin}-{end},...` uses the explicit list of time ranges.
+    ///
+    /// The segments are limited by `--begin` and `--end` and the ones that
+    /// are longer than 3 seconds are cut.
+    #[clap(long, value_parser = SplitMode::parse, conflicts_with = "caption_file")]
+    split: Option<SplitMode>,
+
+    /// Trim the still (frozen) frames at the head and the tail of every input,
+    /// that often appear in screen captures and exported GIFs. The adjusted
+    /// `begin` and `end` times are logged.
+    #[clap(long)]
+    trim_still: bool,
+
+    /// Analyse every input that is longer than 3 seconds and pick the most
+    /// interesting window of it automatically. The reason why the window was
+    /// chosen is logged.
+    ///
+    /// The value defines the metric of how interesting the frames are.
+    /// `motion` (default) is the difference between the consecutive frames,
+    /// `scene` is the scene change score, and `alpha` is the difference between
+    /// the alpha channels of the consecutive frames.
+    #[clap(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "motion",
+        conflicts_with_all = ["begin", "split"],
+    )]
+    auto_window: Option<AutoWindowMetric>,
+
+    /// Make the video loop seamlessly. Telegram plays emoji and stickers
+    /// in an infinite loop, so this hides the jump at the loop point.
+    ///
+    /// `crossfade[:duration]` blends the tail of the segment into its head
+    /// (the default crossfade duration is 0.5 seconds).
+    ///
+    /// `pingpong` plays the segment forward and then backward.
+    ///
+    /// The source segment is shortened if needed to make the looped output
+    /// fit into 3 seconds.
+    #[clap(long = "loop", value_parser = LoopMode::parse)]
+    loop_mode: Option<LoopMode>,
+
+    /// Animation preset to apply to the still image inputs (PNG, JPEG, SVG, etc.).
+    ///
+    /// Still images are converted into a video of `--still-duration` length
+    /// even if this option isn't specified.
+    #[clap(long, value_enum)]
+    animate: Option<AnimationPreset>,
+
+    /// Duration of the video generated from a still image input.
+    /// It's also the duration of a single animation cycle.
+    ///
+    /// [default: 2]
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    still_duration: Option<Duration>,
+
+    /// Frame rate of the video generated from a still image input.
+    ///
+    /// [default: 30]
+    #[clap(long)]
+    still_fps: Option<NonZeroU32>,
+
+    /// Image format of the static emoji and stickers. The quality is
+    /// reduced until the image fits into the size limit.
+    #[clap(long, value_enum, default_value_t)]
+    static_format: StaticFormat,
+
     /// The value of the video filter flag that will be passed to ffmpeg
     /// before rescaling it to the needed size
     #[clap(long)]
     filter: Option<String>,
 
+    #[clap(flatten)]
+    caption: CaptionArgs,
+
+    #[clap(flatten)]
+    watermark: WatermarkArgs,
+
+    #[clap(flatten)]
+    repaint: RepaintArgs,
+
+    #[clap(flatten)]
+    playback: PlaybackArgs,
+
     /// Maximum number of inputs to be proceesed in parallel.
     #[clap(long, default_value_t = default_concurrency())]
     concurrency: NonZeroUsize,
@@ -82,7 +187,7 @@ pub struct Video {
 
 #[derive(Debug, Args)]
 #[group(required = true, multiple = true)]
-struct PackKindArgs {
+pub(crate) struct PackKindArgs {
     /// Generate an emoji WEBM file
     #[clap(long)]
     emoji: bool,
@@ -90,6 +195,238 @@ struct PackKindArgs {
     /// Generate a sticker WEBM file
     #[clap(long)]
     sticker: bool,
+
+    /// Generate a static emoji image file. See `--static-format`.
+    ///
+    /// A single frame is taken from a video input at `--begin`.
+    #[clap(long)]
+    static_emoji: bool,
+
+    /// Generate a static sticker image file. See `--static-format`.
+    ///
+    /// A single frame is taken from a video input at `--begin`.
+    #[clap(long)]
+    static_sticker: bool,
+
+    /// Generate a 100x100 pack thumbnail WEBM file
+    #[clap(long)]
+    thumbnail: bool,
+}
+
+impl PackKindArgs {
+    pub(crate) fn pack_kinds(&self) -> Vec<PackKind> {
+        [
+            self.emoji.then_some(PackKind::Emoji),
+            self.sticker.then_some(PackKind::Sticker),
+            self.static_emoji.then_some(PackKind::StaticEmoji),
+            self.static_sticker.then_some(PackKind::StaticSticker),
+            self.thumbnail.then_some(PackKind::Thumbnail),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[derive(Debug, Args)]
+#[clap(next_help_heading = "Caption")]
+struct CaptionArgs {
+    /// Text to render on top of the video. The text is rendered after the video
+    /// is scaled to the final size, so that it stays readable.
+    #[clap(long)]
+    caption: Option<String>,
+
+    /// Path to a text file where every line is a separate caption.
+    /// A separate output is generated for every line with the name
+    /// `{input_file_name}-{line_number}-{emoji|sticker}.webm`.
+    #[clap(long, conflicts_with = "caption")]
+    caption_file: Option<Utf8PathBuf>,
+
+    /// Vertical position of the caption
+    #[clap(long, value_enum, default_value_t)]
+    caption_position: CaptionPosition,
+
+    /// Path to the font file to render the caption with. If not specified,
+    /// the default font that ffmpeg resolves via fontconfig is used.
+    #[clap(long)]
+    caption_font: Option<Utf8PathBuf>,
+
+    /// Font size of the caption in pixels of the output video. By default,
+    /// it's proportional to the size of the emoji/sticker.
+    #[clap(long)]
+    caption_size: Option<u32>,
+
+    /// Color of the caption text in the ffmpeg color syntax
+    #[clap(long, default_value = "white")]
+    caption_color: String,
+
+    /// Width of the stroke around the caption text in pixels
+    #[clap(long, default_value_t = 2)]
+    caption_stroke_width: u32,
+
+    /// Color of the stroke around the caption text in the ffmpeg color syntax
+    #[clap(long, default_value = "black")]
+    caption_stroke_color: String,
+
+    /// Reveal the words of the caption one by one with this interval
+    #[clap(long, value_parser = crate::util::duration::parse)]
+    caption_word_interval: Option<Duration>,
+}
+
+impl CaptionArgs {
+    fn style(self) -> CaptionStyle {
+        CaptionStyle {
+            position: self.caption_position,
+            font_file: self.caption_font,
+            size: self.caption_size,
+            color: self.caption_color,
+            stroke_width: self.caption_stroke_width,
+            stroke_color: self.caption_stroke_color,
+            word_interval: self.caption_word_interval,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+#[clap(next_help_heading = "Watermark")]
+struct WatermarkArgs {
+    /// Path to the logo image to overlay on top of the video as a watermark.
+    /// Unlike the `--publisher` metadata, it isn't lost when the file is
+    /// re-encoded.
+    #[clap(long)]
+    watermark_image: Option<Utf8PathBuf>,
+
+    /// Short text to overlay on top of the video as a watermark
+    #[clap(long, conflicts_with = "watermark_image")]
+    watermark_text: Option<String>,
+
+    /// The corner of the video where the watermark is placed
+    #[clap(long, value_enum, default_value_t)]
+    watermark_corner: WatermarkCorner,
+
+    /// Opacity of the watermark in range `[0; 1]`
+    #[clap(long, default_value_t = 0.5)]
+    watermark_opacity: f64,
+
+    /// Height of the watermark relative to the size of the emoji/sticker
+    #[clap(long, default_value_t = 0.15)]
+    watermark_scale: f64,
+
+    /// Don't put the watermark on the given kind of output, for example on
+    /// tiny emoji
+    #[clap(long, value_enum)]
+    watermark_skip: Vec<PackKind>,
+}
+
+impl WatermarkArgs {
+    fn watermark(self) -> Option<Watermark> {
+        let content = self
+            .watermark_image
+            .map(WatermarkContent::Image)
+            .or_else(|| self.watermark_text.map(WatermarkContent::Text))?;
+
+        Some(Watermark {
+            content,
+            corner: self.watermark_corner,
+            opacity: self.watermark_opacity,
+            scale: self.watermark_scale,
+            skip: self.watermark_skip,
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+#[clap(next_help_heading = "Repaint")]
+struct RepaintArgs {
+    /// Make the emoji repaintable. Telegram recolors the emoji of the sets
+    /// created with `needs_repainting` into the color of the text, so they
+    /// must be white silhouettes on a transparent background.
+    ///
+    /// The value defines where the opacity of the silhouette is taken from.
+    /// `threshold` (default) makes the pixels brighter than
+    /// `--repaint-threshold` opaque, `luminance` uses the brightness of
+    /// the pixels as their opacity, and `alpha` keeps the existing alpha
+    /// channel.
+    ///
+    /// It applies only to the emoji outputs. Create the set with
+    /// `tstick pack upload --repaintable` to make Telegram repaint them.
+    #[clap(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "threshold",
+    )]
+    repaintable: Option<MatteSource>,
+
+    /// Brightness in range `[0; 1]` that separates the opaque pixels from
+    /// the transparent ones for the `threshold` source
+    #[clap(long, default_value_t = Repaint::default().threshold)]
+    repaint_threshold: f64,
+
+    /// Swap the opaque and the transparent parts of the silhouette. Useful
+    /// for the dark art on a light background. Not allowed for the `alpha`
+    /// source.
+    #[clap(long)]
+    repaint_invert: bool,
+
+    /// Blur the edges of the silhouette with this standard deviation in
+    /// pixels to smooth them. Zero keeps the edges sharp.
+    #[clap(long, default_value_t = Repaint::default().smoothing)]
+    repaint_smoothing: f64,
+}
+
+impl RepaintArgs {
+    fn repaint(self) -> Option<Repaint> {
+        Some(Repaint {
+            source: self.repaintable?,
+            threshold: self.repaint_threshold,
+            invert: self.repaint_invert,
+            smoothing: self.repaint_smoothing,
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+#[clap(next_help_heading = "Playback")]
+struct PlaybackArgs {
+    /// Play the video backwards
+    #[clap(long)]
+    reverse: bool,
+
+    /// Speed up the video by the given factor. Values less than 1 slow it down.
+    ///
+    /// The `--begin` and `--end` bounds refer to the source video, while the
+    /// 3 seconds limit applies to the output after the speed change. For
+    /// example, with `--speed 2` up to 6 seconds of the source fit into the output.
+    #[clap(long, value_parser = crate::video::parse_speed)]
+    speed: Option<f64>,
+
+    /// Rotate the video clockwise by the given number of degrees
+    #[clap(long, value_enum)]
+    rotate: Option<Rotation>,
+
+    /// Mirror the video horizontally (`h`) or vertically (`v`)
+    #[clap(long, value_enum)]
+    flip: Option<Flip>,
+
+    /// Fade the output from and to the full transparency in format `in:out`,
+    /// where `in` and `out` are the durations of the fades. Any of them may
+    /// be zero, e.g. `0.5:0` fades only at the beginning.
+    #[clap(long, value_parser = Fade::parse)]
+    fade: Option<Fade>,
+}
+
+impl PlaybackArgs {
+    fn playback(self) -> Playback {
+        Playback {
+            reverse: self.reverse,
+            speed: self.speed,
+            rotate: self.rotate,
+            flip: self.flip,
+            fade: self.fade,
+        }
+    }
 }
 
 fn default_concurrency() -> NonZeroUsize {
@@ -102,16 +439,8 @@ fn default_concurrency() -> NonZeroUsize {
 #[async_trait]
 impl crate::cmd::Cmd for Video {
     async fn run(self) -> Result {
-        let pack_kinds = [
-            self.pack_kinds.emoji.then_some(PackKind::Emoji),
-            self.pack_kinds.sticker.then_some(PackKind::Sticker),
-        ]
-        .into_iter()
-        .flatten()
-        .collect();
-
         let context = MultiVideoGenContext::builder()
-            .pack_kinds(pack_kinds)
+            .pack_kinds(self.pack_kinds.pack_kinds())
             .inputs(self.input)
             .ffmpeg_args(self.ffmpeg_args)
             .concurrency(self.concurrency)
@@ -119,7 +448,21 @@ impl crate::cmd::Cmd for Video {
             .and_output(self.output)
             .and_begin(self.begin)
             .and_end(self.end)
+            .and_split(self.split)
+            .and_auto_window(self.auto_window)
+            .trim_still(self.trim_still)
+            .and_loop_mode(self.loop_mode)
+            .and_animation(self.animate)
+            .and_still_duration(self.still_duration)
+            .and_still_fps(self.still_fps)
             .and_filter(self.filter)
+            .and_caption(self.caption.caption.clone())
+            .and_caption_file(self.caption.caption_file.clone())
+            .caption_style(self.caption.style())
+            .and_watermark(self.watermark.watermark())
+            .and_repaint(self.repaint.repaint())
+            .playback(self.playback.playback())
+            .static_format(self.static_format)
             .and_publisher(self.publisher)
             .build()?;
 
@@ -2,6 +2,7 @@ use crate::prelude::*;
 use anyhow::Result;
 use async_trait::async_trait;
 use std::fmt;
+use std::time::Duration;
 
 #[async_trait]
 pub(crate) trait Ffmpeg: fmt::Debug + Send + Sync {
@@ -26,6 +27,9 @@ pub(crate) trait Ffmpeg: fmt::Debug + Send + Sync {
 
         fs::read(output_file).await.err_into()
     }
+
+    /// Query the duration of the media file using ffprobe.
+    async fn probe_duration(&self, input: &Utf8Path) -> Result<Duration>;
 }
 
 #[derive(Debug)]
@@ -36,4 +40,48 @@ impl Ffmpeg for FfmpegProcess {
     async fn run(&self, args: Vec<String>) -> Result<Vec<u8>> {
         crate::util::cmd::ffmpeg(args).await
     }
+
+    async fn probe_duration(&self, input: &Utf8Path) -> Result<Duration> {
+        crate::util::cmd::get_media_duration(input).await
+    }
+}
+
+/// Escapes the value of a filter option such that it can be embedded into a
+/// filtergraph description. There are two levels of escaping: one for the
+/// filter option value and one for the filtergraph itself.
+///
+/// See <https://ffmpeg.org/ffmpeg-filters.html#Notes-on-filtergraph-escaping>
+pub(crate) fn escape_filter_value(value: &str) -> String {
+    let escape = |value: &str, special: &[char]| {
+        value
+            .chars()
+            .flat_map(|char| {
+                special
+                    .contains(&char)
+                    .then_some('\\')
+                    .into_iter()
+                    .chain([char])
+            })
+            .collect::<String>()
+    };
+
+    let value = escape(value, &['\\', '\'', ':']);
+    escape(&value, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_escape_filter_value() {
+        let actual = escape_filter_value(
+            "this is a 'string': may contain one, or more, [special]; characters",
+        );
+        expect![[
+            r#"this is a \\\'string\\\'\\: may contain one\, or more\, \[special\]\; characters"#
+        ]]
+        .assert_eq(&actual);
+    }
 }
@@ -1,4 +1,5 @@
 use crate::display;
+use crate::meta::StickerMeta;
 use crate::prelude::*;
 use crate::util::input;
 use crate::util::path::Utf8StemmedPathBuf;
@@ -6,6 +7,9 @@ use futures::prelude::*;
 
 /// Returns either a stream of files in the directory or a stream of a single
 /// file depending on whether the path is a directory or a file.
+///
+/// The sidecar metadata files and the files generated by the pack commands
+/// are skipped when listing the directory.
 pub(crate) async fn files(path: impl AsRef<Utf8Path>) -> Result<Vec<Utf8PathBuf>> {
     let path = path.as_ref();
 
@@ -17,6 +21,9 @@ pub(crate) async fn files(path: impl AsRef<Utf8Path>) -> Result<Vec<Utf8PathBuf>
 
     read_dir_stream(dir)
         .map(|entry| entry?.path().try_into().err_into())
+        .try_filter(|path: &Utf8PathBuf| {
+            future::ready(!StickerMeta::is_sidecar(path) && !crate::pack::is_generated_file(path))
+        })
         .try_collect()
         .await
 }
@@ -85,6 +92,16 @@ pub(crate) fn validate_duplicate_input_names<'a>(
     bail!("The following input files have the same name, but they must be unique.\n{inputs}");
 }
 
+pub(crate) async fn write_output(output: &Utf8Path, bytes: &[u8]) -> Result {
+    fs::write(output, bytes).await?;
+
+    let out_file = nu_ansi_term::Color::Magenta.bold().paint(output.as_str());
+
+    info!("🔥 Saved output at {out_file}");
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,7 +1,17 @@
+// `buildstructor` generates `cfg(feature = "cargo-clippy")` attributes that
+// newer toolchains report as unexpected
+#![allow(unexpected_cfgs)]
+
+mod bot;
 mod cmd;
 mod display;
 mod ffmpeg;
 mod fs;
+mod meta;
+mod pack;
+mod serve;
+mod telegram;
+mod tgs;
 mod util;
 mod video;
 
@@ -32,12 +42,30 @@ use cmd::Cmd;
 /// A tool that automates the management of telegram stickers and emojis
 #[derive(Parser, Debug)]
 #[command(version)]
+// The args are parsed only once, so the size of the enum doesn't matter
+#[allow(clippy::large_enum_variant)]
 enum Args {
     Video(cmd::Video),
+    Grid(cmd::Grid),
+    Concat(cmd::Concat),
+    Thumbnail(cmd::Thumbnail),
+    Tgs(cmd::Tgs),
+    Pack(cmd::Pack),
+    Meta(cmd::Meta),
+    Bot(cmd::Bot),
+    Serve(cmd::Serve),
 }
 
 pub async fn run() -> anyhow::Result<()> {
     match Args::parse() {
         Args::Video(cmd) => cmd.run().await,
+        Args::Grid(cmd) => cmd.run().await,
+        Args::Concat(cmd) => cmd.run().await,
+        Args::Thumbnail(cmd) => cmd.run().await,
+        Args::Tgs(cmd) => cmd.run().await,
+        Args::Pack(cmd) => cmd.run().await,
+        Args::Meta(cmd) => cmd.run().await,
+        Args::Bot(cmd) => cmd.run().await,
+        Args::Serve(cmd) => cmd.run().await,
     }
 }
@@ -0,0 +1,142 @@
+use super::StickerMeta;
+use crate::display;
+use crate::prelude::*;
+use buildstructor::buildstructor;
+
+const COMMENT: &str = "Suggested by `tstick meta` from the file name. Review it before publishing.";
+
+pub(crate) struct MetaContext {
+    inputs: Vec<Utf8PathBuf>,
+
+    /// Only check that all files have the emoji without writing anything
+    check: bool,
+}
+
+#[buildstructor]
+impl MetaContext {
+    #[builder]
+    pub(crate) fn new(inputs: Vec<Utf8PathBuf>, check: bool) -> Self {
+        Self { inputs, check }
+    }
+}
+
+impl MetaContext {
+    /// Writes the suggested metadata for the files that don't have it
+    pub(crate) async fn run(self) -> Result {
+        let mut files = vec![];
+        for input in &self.inputs {
+            files.extend(crate::fs::files(input).await?);
+        }
+
+        files.retain(|file| !StickerMeta::is_sidecar(file));
+        files.sort();
+
+        let mut unresolved = vec![];
+        let mut suggested = 0;
+
+        for file in &files {
+            if let Some(meta) = StickerMeta::load(file).await? {
+                if meta.emoji.is_empty() {
+                    unresolved.push(file.as_path());
+                }
+                continue;
+            }
+
+            let meta = StickerMeta::suggest(file);
+
+            if self.check || meta.emoji.is_empty() {
+                unresolved.push(file.as_path());
+                continue;
+            }
+
+            meta.save(file, COMMENT).await?;
+            suggested += 1;
+
+            info!(
+                "💡 Suggested {} for {}",
+                meta.emoji.join(""),
+                display::bold(&file)
+            );
+        }
+
+        if self.check {
+            let no_emoji: &[String] = &[];
+            return super::ensure_resolved(unresolved.into_iter().map(|file| (file, no_emoji)));
+        }
+
+        for file in &unresolved {
+            warn!(
+                "⚠️ No emoji found for {}. Write them to {} manually.",
+                display::bold(file),
+                display::bold(&StickerMeta::path(file)),
+            );
+        }
+
+        info!(
+            "Wrote {} suggestions, {} of {} files have no emoji",
+            display::bold(&suggested),
+            display::bold(&unresolved.len()),
+            display::bold(&files.len()),
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_meta() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        for name in ["fire_dance.mp4", "IMG_2041.mp4", "cat.mp4"] {
+            fs::write(dir.join(name), "").await.unwrap();
+        }
+
+        fs::write(dir.join("cat.meta.toml"), "emoji = ['😺']")
+            .await
+            .unwrap();
+
+        let run = |check| {
+            MetaContext::builder()
+                .input(dir.to_owned())
+                .check(check)
+                .build()
+                .run()
+        };
+
+        let err = run(true).await.unwrap_err();
+        let err = format!("{err:#}").replace(dir.as_str(), "{dir}");
+
+        expect![[r#"
+            The following stickers have no emoji:
+            - `{dir}/IMG_2041.mp4`
+            - `{dir}/fire_dance.mp4` (suggested: 🔥💃)
+            Add the emoji to the `{stem}.meta.toml` files next to them. Run `tstick meta` to generate the files with the suggestions."#]]
+        .assert_eq(&err);
+
+        run(false).await.unwrap();
+
+        expect![[r##"
+            # Suggested by `tstick meta` from the file name. Review it before publishing.
+
+            emoji = ["🔥", "💃"]
+            keywords = ["fire", "dance"]
+        "##]]
+        .assert_eq(
+            &fs::read_to_string(dir.join("fire_dance.meta.toml"))
+                .await
+                .unwrap(),
+        );
+
+        assert!(!dir.join("IMG_2041.meta.toml").exists());
+
+        // The existing files are kept intact
+        expect!["emoji = ['😺']"]
+            .assert_eq(&fs::read_to_string(dir.join("cat.meta.toml")).await.unwrap());
+    }
+}
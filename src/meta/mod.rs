@@ -0,0 +1,147 @@
+//! Emoji and search keywords of the stickers. They are kept in the sidecar
+//! files named `{stem}.meta.toml` next to the media files.
+//!
+//! ```toml
+//! emoji    = ["🔥", "💃"]
+//! keywords = ["fire", "dance"]
+//! ```
+
+mod generate;
+mod suggest;
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub(crate) use generate::MetaContext;
+pub(crate) use suggest::{suggest_emoji, suggest_keywords};
+
+/// Telegram doesn't accept more emoji or keywords per sticker than this
+pub(crate) const MAX_EMOJI_PER_STICKER: usize = 20;
+pub(crate) const MAX_KEYWORDS_PER_STICKER: usize = 20;
+
+const SIDECAR_SUFFIX: &str = ".meta.toml";
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct StickerMeta {
+    #[serde(default)]
+    pub(crate) emoji: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) keywords: Vec<String>,
+}
+
+impl StickerMeta {
+    /// Path to the sidecar file of the media file
+    pub(crate) fn path(media: &Utf8Path) -> Utf8PathBuf {
+        let stem = media.file_stem().unwrap_or_default();
+        media.with_file_name(format!("{stem}{SIDECAR_SUFFIX}"))
+    }
+
+    pub(crate) fn is_sidecar(path: &Utf8Path) -> bool {
+        path.as_str().ends_with(SIDECAR_SUFFIX)
+    }
+
+    /// Reads the sidecar file of the media file if it exists
+    pub(crate) async fn load(media: &Utf8Path) -> Result<Option<Self>> {
+        let path = Self::path(media);
+
+        if !path.try_exists()? {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path).await?;
+
+        let meta: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse the sticker metadata `{path}`"))?;
+
+        validate(&meta.emoji, &meta.keywords)
+            .with_context(|| format!("Invalid sticker metadata `{path}`"))?;
+
+        Ok(Some(meta))
+    }
+
+    /// Writes the sidecar file of the media file with the given comment
+    /// at the top
+    pub(crate) async fn save(&self, media: &Utf8Path, comment: &str) -> Result {
+        let content = format!("# {comment}\n\n{}", toml::to_string(self)?);
+        fs::write(Self::path(media), content).await?;
+        Ok(())
+    }
+
+    /// Suggests the metadata by the name of the media file
+    pub(crate) fn suggest(media: &Utf8Path) -> Self {
+        let stem = media.file_stem().unwrap_or_default();
+
+        Self {
+            emoji: suggest_emoji(stem),
+            keywords: suggest_keywords(stem),
+        }
+    }
+}
+
+pub(crate) fn validate(emoji: &[String], keywords: &[String]) -> Result {
+    let emoji = emoji.len();
+    if emoji > MAX_EMOJI_PER_STICKER {
+        bail!("The sticker has {emoji} emoji, but at most {MAX_EMOJI_PER_STICKER} are allowed");
+    }
+
+    let keywords = keywords.len();
+    if keywords > MAX_KEYWORDS_PER_STICKER {
+        bail!(
+            "The sticker has {keywords} keywords, but at most \
+            {MAX_KEYWORDS_PER_STICKER} are allowed"
+        );
+    }
+
+    Ok(())
+}
+
+/// Copies the sidecar file of the input next to the output generated from it,
+/// so that the metadata follows the output to the upload
+pub(crate) async fn copy_sidecar(input: &Utf8Path, output: &Utf8Path) -> Result {
+    let source = StickerMeta::path(input);
+
+    if !source.try_exists()? {
+        return Ok(());
+    }
+
+    fs::copy(&source, StickerMeta::path(output)).await?;
+
+    Ok(())
+}
+
+/// Fails if any of the stickers has no emoji. Telegram requires at least one,
+/// so this is checked before publishing anything.
+pub(crate) fn ensure_resolved<'a>(
+    stickers: impl IntoIterator<Item = (&'a Utf8Path, &'a [String])>,
+) -> Result {
+    let unresolved = stickers
+        .into_iter()
+        .filter(|(_, emoji)| emoji.is_empty())
+        .map(|(path, _)| path)
+        .collect_vec();
+
+    if unresolved.is_empty() {
+        return Ok(());
+    }
+
+    let list = unresolved.iter().format_with("\n", |path, f| {
+        let suggested = suggest_emoji(path.file_stem().unwrap_or_default());
+
+        if suggested.is_empty() {
+            f(&format_args!("- `{path}`"))
+        } else {
+            f(&format_args!(
+                "- `{path}` (suggested: {})",
+                suggested.join("")
+            ))
+        }
+    });
+
+    bail!(
+        "The following stickers have no emoji:\n{list}\n\
+        Add the emoji to the `{{stem}}{SIDECAR_SUFFIX}` files next to them. \
+        Run `tstick meta` to generate the files with the suggestions."
+    )
+}
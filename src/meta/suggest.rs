@@ -0,0 +1,150 @@
+//! Suggestions of the emoji and keywords by the names of the media files
+
+use super::MAX_KEYWORDS_PER_STICKER;
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Bundled database of the emoji names and shortcodes
+const DATABASE: &str = include_str!("emoji.txt");
+
+/// Max number of words in the names from the database
+const MAX_NAME_WORDS: usize = 5;
+
+/// Returns the emoji whose names are mentioned in the file stem, for example
+/// `fire_dance` gives `🔥💃`. The emoji are in the order of the words.
+pub(crate) fn suggest_emoji(stem: &str) -> Vec<String> {
+    let names = names();
+    let words = words(stem);
+
+    let mut emoji = vec![];
+    let mut i = 0;
+
+    // Greedily match the longest names first, so that `thumbs_up` gives `👍`
+    // instead of `👆` for `up`
+    while i < words.len() {
+        let found = (1..=MAX_NAME_WORDS.min(words.len() - i))
+            .rev()
+            .find_map(|len| {
+                let name = words[i..i + len].join("_");
+                let emoji = names.get(name.as_str()).or_else(|| {
+                    // Plurals are looked up by their singular form
+                    let singular = name
+                        .strip_suffix('s')
+                        .filter(|_| len == 1 && name.len() > 3);
+                    names.get(singular?)
+                })?;
+                Some((len, *emoji))
+            });
+
+        let Some((len, found)) = found else {
+            i += 1;
+            continue;
+        };
+
+        if !emoji.iter().any(|emoji| emoji == found) {
+            emoji.push(found.to_owned());
+        }
+
+        i += len;
+    }
+
+    emoji
+}
+
+/// Returns the words of the file stem as the search keywords
+pub(crate) fn suggest_keywords(stem: &str) -> Vec<String> {
+    words(stem)
+        .into_iter()
+        .filter(|word| word.chars().count() > 1 && !word.chars().all(|c| c.is_ascii_digit()))
+        .unique()
+        .take(MAX_KEYWORDS_PER_STICKER)
+        .collect()
+}
+
+/// Splits the file stem into the lowercase words by the separators and
+/// the `camelCase` boundaries
+fn words(stem: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut prev_lowercase = false;
+
+    for char in stem.chars() {
+        if !char.is_alphanumeric() {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            prev_lowercase = false;
+            continue;
+        }
+
+        if char.is_uppercase() && prev_lowercase {
+            words.push(std::mem::take(&mut word));
+        }
+
+        prev_lowercase = char.is_lowercase();
+        word.extend(char.to_lowercase());
+    }
+
+    words.extend((!word.is_empty()).then_some(word));
+    words
+}
+
+/// Emoji by their names
+fn names() -> &'static HashMap<&'static str, &'static str> {
+    static NAMES: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+
+    NAMES.get_or_init(|| {
+        let mut names = HashMap::new();
+
+        let lines = DATABASE
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        for line in lines {
+            let mut columns = line.split_whitespace();
+            let Some(emoji) = columns.next() else {
+                continue;
+            };
+
+            for name in columns {
+                names.entry(name).or_insert(emoji);
+            }
+        }
+
+        names
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn suggestions() {
+        let suggestions = [
+            "fire_dance",
+            "thumbs-up",
+            "HappyCat",
+            "cats and dogs",
+            "rolling_on_the_floor_laughing",
+            "IMG_2041",
+        ]
+        .map(|stem| {
+            format!(
+                "{stem}: {} {:?}",
+                suggest_emoji(stem).join(""),
+                suggest_keywords(stem),
+            )
+        });
+
+        expect![[r#"
+            fire_dance: 🔥💃 ["fire", "dance"]
+            thumbs-up: 👍 ["thumbs", "up"]
+            HappyCat: 😄🐱 ["happy", "cat"]
+            cats and dogs: 🐱🐶 ["cats", "and", "dogs"]
+            rolling_on_the_floor_laughing: 🤣 ["rolling", "on", "the", "floor", "laughing"]
+            IMG_2041:  ["img"]"#]]
+        .assert_eq(&suggestions.join("\n"));
+    }
+}
@@ -0,0 +1,414 @@
+use super::manifest::{Manifest, ManifestItem};
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::util::duration;
+use crate::util::path::Utf8StemmedPathBuf;
+use crate::video::{LoopMode, MultiVideoGenContext, PackKind, Repaint};
+use buildstructor::buildstructor;
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+/// Name of the file in the output directory that remembers what every
+/// sticker was built from
+const STATE_FILE_NAME: &str = ".tstick-build.json";
+
+pub(crate) struct BuildContext {
+    manifest: Utf8PathBuf,
+
+    /// Rebuild all stickers even if they are up to date
+    force: bool,
+
+    /// Overrides the concurrency from the manifest
+    concurrency: Option<NonZeroUsize>,
+
+    ffmpeg: Option<Arc<dyn Ffmpeg>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct BuildState {
+    /// Records of the built stickers by their input paths from the manifest
+    items: BTreeMap<Utf8PathBuf, BuildRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BuildRecord {
+    fingerprint: String,
+
+    /// Names of the files generated in the output directory
+    outputs: BTreeMap<PackKind, String>,
+}
+
+/// Everything that influences the outputs of the sticker except for
+/// the contents of its source file
+#[derive(Serialize)]
+struct Fingerprint<'a> {
+    version: &'static str,
+    publisher: Option<&'a str>,
+    repaint: Option<&'a Repaint>,
+    kinds: &'a [PackKind],
+    item: ManifestItem,
+}
+
+#[buildstructor]
+impl BuildContext {
+    #[builder]
+    pub(crate) fn new(
+        manifest: Utf8PathBuf,
+        force: bool,
+        concurrency: Option<NonZeroUsize>,
+        ffmpeg: Option<Arc<dyn Ffmpeg>>,
+    ) -> Self {
+        Self {
+            manifest,
+            force,
+            concurrency,
+            ffmpeg,
+        }
+    }
+}
+
+/// Paths to the generated files of a sticker
+pub(crate) type ItemOutputs = BTreeMap<PackKind, Utf8PathBuf>;
+
+impl BuildContext {
+    /// Returns the outputs of every sticker in the order of the manifest
+    pub(crate) async fn run(self) -> Result<Vec<ItemOutputs>> {
+        let manifest = Manifest::load(&self.manifest).await?;
+
+        let inputs: Vec<_> = manifest
+            .stickers
+            .iter()
+            .map(|item| Utf8StemmedPathBuf::try_from(manifest.input_path(item)))
+            .try_collect()?;
+
+        // The outputs of all stickers are put into the same directory
+        crate::fs::validate_duplicate_input_names(&inputs)?;
+
+        let output = manifest.output_dir();
+        fs::create_dir_all(&output).await?;
+
+        let state_path = output.join(STATE_FILE_NAME);
+        let mut state = BuildState::load(&state_path).await?;
+
+        // Forget the stickers that were removed from the manifest
+        state
+            .items
+            .retain(|input, _| manifest.stickers.iter().any(|item| &item.input == input));
+
+        let mut jobs = vec![];
+
+        for item in &manifest.stickers {
+            let fingerprint = fingerprint(&manifest, item).await?;
+
+            if !self.force && state.is_up_to_date(&output, &item.input, &fingerprint) {
+                debug!(input = %item.input, "The sticker is up to date");
+                continue;
+            }
+
+            let contexts = self
+                .video_context(&manifest, item, &output)
+                .with_context(|| format!("Invalid sticker `{}`", item.input))?
+                .contexts()
+                .instrument(info_span!("prepare", input = %item.input))
+                .await?;
+
+            jobs.push((item, fingerprint, contexts));
+        }
+
+        if jobs.is_empty() {
+            info!(
+                "✅ All {} stickers are up to date",
+                display::bold(&manifest.stickers.len())
+            );
+            return state.outputs(&manifest, &output);
+        }
+
+        info!(
+            "🔨 Building {} of {} stickers",
+            display::bold(&jobs.len()),
+            display::bold(&manifest.stickers.len()),
+        );
+
+        let concurrency = self
+            .concurrency
+            .or(manifest.pack.concurrency)
+            .unwrap_or_else(|| MultiVideoGenContext::default_concurrency(""));
+
+        let start = std::time::Instant::now();
+
+        // Collecting the tasks into a `Vec` instead of borrowing the jobs in
+        // the stream is needed due to a compiler bug (rust/issues/102211)
+        let tasks = jobs
+            .iter()
+            .enumerate()
+            .flat_map(|(job, (_, _, contexts))| {
+                contexts.iter().cloned().map(move |context| (job, context))
+            })
+            .collect_vec();
+
+        // Don't stop at the first error to keep the progress of the stickers
+        // that were built successfully
+        let results: Vec<_> = stream::iter(tasks)
+            .enumerate()
+            .map(|(id, (job, context))| {
+                context
+                    .generate_file()
+                    .map(move |result| (job, result))
+                    .instrument(info_span!("task", id = id + 1))
+            })
+            .buffer_unordered(concurrency.get())
+            .collect()
+            .await;
+
+        let mut errors = vec![];
+        let mut failed = BTreeSet::new();
+
+        for (job, result) in results {
+            if let Err(err) = result {
+                errors.push(err);
+                failed.insert(job);
+            }
+        }
+
+        for (job, (item, fingerprint, contexts)) in jobs.into_iter().enumerate() {
+            if failed.contains(&job) {
+                state.items.remove(&item.input);
+                continue;
+            }
+
+            let outputs = contexts
+                .iter()
+                .filter_map(|context| {
+                    let file_name = context.output.file_name()?;
+                    Some((context.pack_kind, file_name.to_owned()))
+                })
+                .collect();
+
+            let record = BuildRecord {
+                fingerprint,
+                outputs,
+            };
+
+            state.items.insert(item.input.clone(), record);
+        }
+
+        state.save(&state_path).await?;
+
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err.context(format!("Failed to build {} sticker(s)", failed.len())));
+        }
+
+        info!("Finished in {}", display::elpased(start));
+
+        state.outputs(&manifest, &output)
+    }
+
+    fn video_context(
+        &self,
+        manifest: &Manifest,
+        item: &ManifestItem,
+        output: &Utf8Path,
+    ) -> Result<MultiVideoGenContext> {
+        let parse_duration = |name, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(duration::parse)
+                .transpose()
+                .with_context(|| format!("Invalid `{name}`"))
+        };
+
+        let loop_mode = item
+            .loop_mode
+            .as_deref()
+            .map(LoopMode::parse)
+            .transpose()
+            .context("Invalid `loop`")?;
+
+        MultiVideoGenContext::builder()
+            .pack_kinds(item.kinds(&manifest.pack).to_vec())
+            .input(manifest.input_path(item))
+            .output(output.to_owned())
+            .and_begin(parse_duration("begin", &item.begin)?)
+            .and_end(parse_duration("end", &item.end)?)
+            .trim_still(item.trim_still)
+            .and_loop_mode(loop_mode)
+            .and_animation(item.animate)
+            .and_filter(item.filter.clone())
+            .and_caption(item.caption.clone())
+            .and_repaint(manifest.pack.repaint.clone())
+            .and_publisher(manifest.pack.publisher.clone())
+            .and_ffmpeg(self.ffmpeg.clone())
+            // The outputs are owned by the build
+            .overwrite(true)
+            .build()
+    }
+}
+
+impl BuildState {
+    async fn load(path: &Utf8Path) -> Result<Self> {
+        if !path.try_exists()? {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read(path).await?;
+
+        serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse the build state `{path}`"))
+    }
+
+    async fn save(&self, path: &Utf8Path) -> Result {
+        fs::write(path, serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+
+    fn is_up_to_date(&self, output: &Utf8Path, input: &Utf8Path, fingerprint: &str) -> bool {
+        let Some(record) = self.items.get(input) else {
+            return false;
+        };
+
+        record.fingerprint == fingerprint
+            && record
+                .outputs
+                .values()
+                .all(|file| output.join(file).exists())
+    }
+
+    fn outputs(&self, manifest: &Manifest, output: &Utf8Path) -> Result<Vec<ItemOutputs>> {
+        manifest
+            .stickers
+            .iter()
+            .map(|item| {
+                let record = self
+                    .items
+                    .get(&item.input)
+                    .with_context(|| format!("BUG: the sticker `{}` wasn't built", item.input))?;
+
+                Ok(record
+                    .outputs
+                    .iter()
+                    .map(|(&kind, file)| (kind, output.join(file)))
+                    .collect())
+            })
+            .collect()
+    }
+}
+
+/// Hash of the sticker's settings and the contents of its source file
+async fn fingerprint(manifest: &Manifest, item: &ManifestItem) -> Result<String> {
+    let input = manifest.input_path(item);
+
+    if fs::metadata(&input).await?.is_dir() {
+        bail!("The input of the sticker must be a file, but `{input}` is a directory");
+    }
+
+    let settings = Fingerprint {
+        version: env!("CARGO_PKG_VERSION"),
+        publisher: manifest.pack.publisher.as_deref(),
+        repaint: manifest.pack.repaint.as_ref(),
+        kinds: item.kinds(&manifest.pack),
+        item: item.build_settings(),
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&settings)?);
+    hasher.update(fs::read(&input).await?);
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::manifest::MANIFEST_FILE_NAME;
+    use crate::video::testing::SharedMockFfmpeg;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_incremental_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let manifest = dir.join(MANIFEST_FILE_NAME);
+
+        let write_manifest = |b_end: &'static str| {
+            let manifest = manifest.clone();
+            async move {
+                let content = format!(
+                    "
+                    [pack]
+                    kinds = ['emoji']
+
+                    [[sticker]]
+                    input = 'clips/a.mp4'
+                    loop = 'pingpong'
+
+                    [[sticker]]
+                    input = 'b.mp4'
+                    end = '{b_end}'
+                    "
+                );
+                fs::write(&manifest, content).await.unwrap();
+            }
+        };
+
+        fs::create_dir(dir.join("clips")).await.unwrap();
+        fs::write(dir.join("clips/a.mp4"), "a").await.unwrap();
+        fs::write(dir.join("b.mp4"), "b").await.unwrap();
+
+        write_manifest("2").await;
+
+        // Returns the names of the inputs that were encoded
+        let build = || async {
+            let mock_ffmpeg = SharedMockFfmpeg::with_best_crf(25, PackKind::Emoji);
+
+            BuildContext::builder()
+                .manifest(manifest.clone())
+                .force(false)
+                .ffmpeg(mock_ffmpeg.clone())
+                .build()
+                .run()
+                .await
+                .unwrap();
+
+            mock_ffmpeg
+                .unwrap()
+                .args_log
+                .iter()
+                .flat_map(|args| args.iter().skip_while(|arg| *arg != "-i").nth(1))
+                .map(|input| Utf8Path::new(input).file_name().unwrap().to_owned())
+                .unique()
+                .sorted()
+                .collect_vec()
+        };
+
+        expect![[r#"
+            [
+                "a.mp4",
+                "b.mp4",
+            ]
+        "#]]
+        .assert_debug_eq(&build().await);
+
+        assert!(dir.join("out/a-emoji.webm").exists());
+        assert!(dir.join("out/b-emoji.webm").exists());
+
+        expect!["[]"].assert_eq(&format!("{:?}", build().await));
+
+        // Changing the source rebuilds only the sticker made of it
+        fs::write(dir.join("clips/a.mp4"), "a2").await.unwrap();
+        expect![[r#"["a.mp4"]"#]].assert_eq(&format!("{:?}", build().await));
+
+        // Changing the settings rebuilds only the sticker they belong to
+        write_manifest("2.5").await;
+        expect![[r#"["b.mp4"]"#]].assert_eq(&format!("{:?}", build().await));
+
+        // Missing outputs are regenerated
+        fs::remove_file(dir.join("out/a-emoji.webm")).await.unwrap();
+        expect![[r#"["a.mp4"]"#]].assert_eq(&format!("{:?}", build().await));
+    }
+}
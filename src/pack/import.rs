@@ -0,0 +1,319 @@
+use super::journal::hash_file;
+use super::lock::{LockedSticker, Lockfile};
+use super::manifest::{Manifest, ManifestItem, PackSettings, MANIFEST_FILE_NAME};
+use crate::display;
+use crate::prelude::*;
+use crate::telegram::{BotApi, Sticker, StickerType};
+use crate::video::{MatteSource, PackKind, Repaint};
+use buildstructor::buildstructor;
+
+/// Directory inside of the output where the downloaded files are put
+const STICKERS_DIR: &str = "stickers";
+
+pub(crate) struct ImportContext {
+    api: BotApi,
+
+    /// Short name of the set to import
+    name: String,
+
+    /// Directory where the manifest and the downloaded files are put
+    output: Utf8PathBuf,
+
+    overwrite: bool,
+}
+
+#[buildstructor]
+impl ImportContext {
+    #[builder]
+    pub(crate) fn new(api: BotApi, name: String, output: Utf8PathBuf, overwrite: bool) -> Self {
+        Self {
+            api,
+            name,
+            output,
+            overwrite,
+        }
+    }
+}
+
+impl ImportContext {
+    pub(crate) async fn run(self) -> Result {
+        super::share::validate_name(&self.name)?;
+
+        let set = self.api.get_sticker_set(&self.name).await?;
+
+        let manifest_path = self.output.join(MANIFEST_FILE_NAME);
+        let lock_path = Lockfile::path(&manifest_path);
+
+        // The files are numbered to keep the order of the set visible
+        let files = set
+            .stickers
+            .iter()
+            .enumerate()
+            .map(|(i, sticker)| {
+                let file_name = format!("{:03}.{}", i + 1, extension(sticker));
+                Utf8Path::new(STICKERS_DIR).join(file_name)
+            })
+            .collect_vec();
+
+        crate::fs::validate_output_files_overwriting(
+            self.overwrite,
+            [manifest_path.clone(), lock_path.clone()]
+                .into_iter()
+                .chain(files.iter().map(|file| self.output.join(file))),
+        )
+        .await?;
+
+        fs::create_dir_all(self.output.join(STICKERS_DIR)).await?;
+
+        for (sticker, file) in set.stickers.iter().zip(&files) {
+            self.download(sticker, &self.output.join(file))
+                .instrument(info_span!("download", file = %file))
+                .await?;
+        }
+
+        let default_kind = animated_kind(set.sticker_type);
+
+        let stickers = set
+            .stickers
+            .iter()
+            .zip(files)
+            .map(|(sticker, input)| {
+                let kind = if sticker.is_animated || sticker.is_video {
+                    default_kind
+                } else {
+                    static_kind(set.sticker_type)
+                };
+
+                ManifestItem {
+                    input,
+                    // Keep the manifest short by omitting the default kind
+                    kinds: (kind != default_kind).then_some(kind).into_iter().collect(),
+                    // The Bot API returns only the first emoji and no keywords
+                    emoji: sticker.emoji.iter().cloned().collect(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let manifest = Manifest {
+            pack: PackSettings {
+                name: Some(set.name.clone()),
+                title: Some(set.title.clone()),
+                sticker_type: set.sticker_type,
+                // The stickers are already silhouettes, so only their alpha
+                // is kept as is
+                repaint: set
+                    .stickers
+                    .iter()
+                    .any(|sticker| sticker.needs_repainting)
+                    .then(|| Repaint {
+                        source: MatteSource::Alpha,
+                        smoothing: 0.0,
+                        ..Default::default()
+                    }),
+                kinds: vec![default_kind],
+                ..Default::default()
+            },
+            stickers,
+            dir: Default::default(),
+        };
+
+        let content = format!(
+            "# Imported from the set `{}` with `tstick pack import`\n\n{}",
+            set.name,
+            toml::to_string(&manifest)?,
+        );
+
+        fs::write(&manifest_path, content).await?;
+
+        // Let `tstick pack sync` recognize the stickers of the set. Their
+        // hashes are of the downloaded files, so the first sync replaces them
+        // in place with the files built from the manifest.
+        let mut locked = Vec::with_capacity(set.stickers.len());
+
+        for (sticker, item) in set.stickers.iter().zip(&manifest.stickers) {
+            locked.push(LockedSticker {
+                input: item.input.clone(),
+                file_unique_id: sticker.file_unique_id.clone(),
+                hash: hash_file(&self.output.join(&item.input)).await?,
+                emoji: item.emoji.clone(),
+                keywords: vec![],
+            });
+        }
+
+        let lock = Lockfile {
+            name: Some(set.name.clone()),
+            stickers: locked,
+        };
+
+        lock.save(&lock_path).await?;
+
+        info!(
+            "📦 Imported {} stickers of the set {} into {}",
+            display::bold(&set.stickers.len()),
+            display::bold(&set.name),
+            display::bold(&manifest_path),
+        );
+
+        Ok(())
+    }
+
+    async fn download(&self, sticker: &Sticker, path: &Utf8Path) -> Result {
+        let file = self.api.get_file(&sticker.file_id).await?;
+
+        let file_path = file.file_path.with_context(|| {
+            format!(
+                "The Bot API returned no path to download the file `{}`. \
+                Files larger than 20 MB can't be downloaded by bots.",
+                sticker.file_id
+            )
+        })?;
+
+        let bytes = self.api.download_file(&file_path).await?;
+
+        crate::fs::write_output(path, &bytes).await
+    }
+}
+
+fn extension(sticker: &Sticker) -> &'static str {
+    if sticker.is_video {
+        "webm"
+    } else if sticker.is_animated {
+        "tgs"
+    } else {
+        "webp"
+    }
+}
+
+fn animated_kind(sticker_type: StickerType) -> PackKind {
+    match sticker_type {
+        StickerType::CustomEmoji => PackKind::Emoji,
+        StickerType::Regular | StickerType::Mask => PackKind::Sticker,
+    }
+}
+
+fn static_kind(sticker_type: StickerType) -> PackKind {
+    match sticker_type {
+        StickerType::CustomEmoji => PackKind::StaticEmoji,
+        StickerType::Regular | StickerType::Mask => PackKind::StaticSticker,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::testing::FakeBotApi;
+    use crate::telegram::{InputFile, InputSticker, StickerFormat};
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_import() {
+        let fake = FakeBotApi::start().await;
+        let api = fake.client();
+
+        let mut stickers = vec![];
+
+        for (name, format, emoji) in [
+            ("dance.webm", StickerFormat::Video, "💃"),
+            ("still.webp", StickerFormat::Static, "🗿"),
+        ] {
+            let file = InputFile {
+                file_name: name.to_owned(),
+                bytes: name.as_bytes().to_vec(),
+            };
+
+            let file = api.upload_sticker_file(42, file, format).await.unwrap();
+
+            stickers.push(InputSticker {
+                sticker: file.file_id,
+                format,
+                emoji_list: vec![emoji.to_owned()],
+                keywords: vec![],
+            });
+        }
+
+        api.create_new_sticker_set(
+            42,
+            "mix_by_bot",
+            "Mix",
+            &stickers,
+            StickerType::CustomEmoji,
+            true,
+        )
+        .await
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        ImportContext::builder()
+            .api(api)
+            .name("mix_by_bot")
+            .output(dir.to_owned())
+            .overwrite(false)
+            .build()
+            .run()
+            .await
+            .unwrap();
+
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        let manifest = fs::read_to_string(&manifest_path).await.unwrap();
+
+        expect![[r##"
+            # Imported from the set `mix_by_bot` with `tstick pack import`
+
+            [pack]
+            name = "mix_by_bot"
+            title = "Mix"
+            type = "custom_emoji"
+            kinds = ["emoji"]
+
+            [pack.repaint]
+            source = "alpha"
+            threshold = 0.5
+            invert = false
+            smoothing = 0.0
+
+            [[sticker]]
+            input = "stickers/001.webm"
+            emoji = ["💃"]
+
+            [[sticker]]
+            input = "stickers/002.webp"
+            kinds = ["static-emoji"]
+            emoji = ["🗿"]
+        "##]]
+        .assert_eq(&manifest);
+
+        let lock = fs::read_to_string(Lockfile::path(&manifest_path))
+            .await
+            .unwrap();
+
+        expect![[r##"
+            # This file is generated by `tstick pack sync` and `tstick pack import`. Don't edit it manually.
+
+            name = "mix_by_bot"
+
+            [[sticker]]
+            input = "stickers/001.webm"
+            file_unique_id = "unique-1"
+            hash = "7ff01539c9e8c7e5eeaf8f1456046ca8eef52b2758ac5adadc42089821d37ed8"
+            emoji = ["💃"]
+            keywords = []
+
+            [[sticker]]
+            input = "stickers/002.webp"
+            file_unique_id = "unique-2"
+            hash = "8232d47c806dc4796d8aa7e8c37ce2e83d1f684e64d3ac52c79c842cee14f542"
+            emoji = ["🗿"]
+            keywords = []
+        "##]].assert_eq(&lock);
+
+        let manifest = Manifest::load(&manifest_path).await.unwrap();
+
+        for (item, expected) in manifest.stickers.iter().zip(["dance.webm", "still.webp"]) {
+            let bytes = fs::read(manifest.input_path(item)).await.unwrap();
+            assert_eq!(bytes, expected.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Journal of the progress of `tstick pack upload`. It lives next to the
+//! uploaded files while the upload is in progress and lets the failed upload
+//! continue from the last sticker confirmed to be added to the set instead of
+//! adding the same stickers again. It's removed when the upload completes.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const HEADER: &str = "# This file is generated by `tstick pack upload` to resume it \
+    if it fails. Don't edit it manually.\n\n";
+
+/// Suffix of the journal files. These files are skipped when listing
+/// the stickers in the directory.
+const SUFFIX: &str = ".upload.toml";
+
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Journal {
+    /// Name of the set the stickers are uploaded to
+    pub(crate) name: String,
+
+    #[serde(default, rename = "sticker")]
+    pub(crate) stickers: Vec<JournalEntry>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct JournalEntry {
+    pub(crate) file: Utf8PathBuf,
+
+    /// SHA-256 of the uploaded file
+    pub(crate) hash: String,
+
+    /// Identifier returned by `uploadStickerFile`, that is reused instead of
+    /// uploading the file again
+    pub(crate) file_id: String,
+
+    /// The sticker is confirmed to be added to the set
+    #[serde(default)]
+    pub(crate) added: bool,
+}
+
+impl Journal {
+    /// Path to the journal of the upload of the files in the `dir` to the set
+    pub(crate) fn path(dir: &Utf8Path, name: &str) -> Utf8PathBuf {
+        dir.join(format!("{name}{SUFFIX}"))
+    }
+
+    pub(crate) fn is_journal(path: &Utf8Path) -> bool {
+        path.as_str().ends_with(SUFFIX)
+    }
+
+    /// Returns an empty journal if it doesn't exist or belongs to another set
+    pub(crate) async fn load(path: &Utf8Path, name: &str) -> Result<Self> {
+        let empty = Self {
+            name: name.to_owned(),
+            stickers: vec![],
+        };
+
+        if !path.try_exists()? {
+            return Ok(empty);
+        }
+
+        let content = fs::read_to_string(path).await?;
+
+        let journal: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse the upload journal `{path}`"))?;
+
+        if journal.name != name {
+            return Ok(empty);
+        }
+
+        Ok(journal)
+    }
+
+    pub(crate) async fn save(&self, path: &Utf8Path) -> Result {
+        let content = format!("{HEADER}{}", toml::to_string(self)?);
+        fs::write(path, content).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn remove(path: &Utf8Path) -> Result {
+        if path.try_exists()? {
+            fs::remove_file(path).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the entry of the file if it wasn't changed since it was
+    /// uploaded. The entry of the changed file is forgotten.
+    pub(crate) fn find(&mut self, file: &Utf8Path, hash: &str) -> Option<&mut JournalEntry> {
+        let index = self.stickers.iter().position(|entry| entry.file == file)?;
+
+        if self.stickers[index].hash != hash {
+            self.stickers.remove(index);
+            return None;
+        }
+
+        Some(&mut self.stickers[index])
+    }
+
+    pub(crate) fn mark_added<'a>(&mut self, files: impl IntoIterator<Item = &'a Utf8Path>) {
+        for file in files {
+            let entry = self.stickers.iter_mut().find(|entry| entry.file == file);
+            if let Some(entry) = entry {
+                entry.added = true;
+            }
+        }
+    }
+}
+
+pub(crate) async fn hash_file(path: &Utf8Path) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path).await?)))
+}
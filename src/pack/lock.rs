@@ -0,0 +1,66 @@
+//! Lockfile that remembers which published sticker every manifest item
+//! corresponds to and what was uploaded for it. It lives next to the
+//! manifest and is meant to be committed together with it.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const HEADER: &str = "# This file is generated by `tstick pack sync` and `tstick pack import`. \
+    Don't edit it manually.\n\n";
+
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Lockfile {
+    /// Name of the set the stickers were published to
+    pub(crate) name: Option<String>,
+
+    #[serde(default, rename = "sticker")]
+    pub(crate) stickers: Vec<LockedSticker>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LockedSticker {
+    /// Path to the source of the sticker as it's written in the manifest
+    pub(crate) input: Utf8PathBuf,
+
+    /// Identifier of the published sticker that is stable over time
+    pub(crate) file_unique_id: String,
+
+    /// SHA-256 of the uploaded file
+    pub(crate) hash: String,
+
+    pub(crate) emoji: Vec<String>,
+
+    #[serde(default)]
+    pub(crate) keywords: Vec<String>,
+}
+
+impl Lockfile {
+    /// Path to the lockfile of the given manifest
+    pub(crate) fn path(manifest: &Utf8Path) -> Utf8PathBuf {
+        manifest.with_extension("lock")
+    }
+
+    pub(crate) async fn load(path: &Utf8Path) -> Result<Self> {
+        if !path.try_exists()? {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path).await?;
+
+        toml::from_str(&content).with_context(|| format!("Failed to parse the lockfile `{path}`"))
+    }
+
+    pub(crate) async fn save(&self, path: &Utf8Path) -> Result {
+        let content = format!("{HEADER}{}", toml::to_string(self)?);
+        fs::write(path, content).await?;
+        Ok(())
+    }
+
+    pub(crate) fn find(&self, file_unique_id: &str) -> Option<&LockedSticker> {
+        self.stickers
+            .iter()
+            .find(|sticker| sticker.file_unique_id == file_unique_id)
+    }
+}
@@ -0,0 +1,297 @@
+//! Declarative description of a pack in a TOML file.
+//!
+//! ```toml
+//! [pack]
+//! name      = "fire_by_my_bot"
+//! title     = "Fire"
+//! output    = "out"
+//! publisher = "https://t.me/my_channel"
+//! kinds     = ["emoji", "sticker"]
+//!
+//! # Optional, only for `type = "custom_emoji"`
+//! [pack.repaint]
+//! source    = "luminance"
+//!
+//! [[sticker]]
+//! input    = "clips/fire.mp4"
+//! begin    = "1.5"
+//! end      = "4"
+//! loop     = "pingpong"
+//! emoji    = ["🔥"]
+//! keywords = ["fire", "hot"]
+//! ```
+//!
+//! The emoji and keywords that aren't specified for a sticker are taken from
+//! the `{stem}.meta.toml` file next to its input, and then from the `[pack]`
+//! section.
+
+use crate::meta::StickerMeta;
+use crate::prelude::*;
+use crate::telegram::StickerType;
+use crate::video::{AnimationPreset, PackKind, Repaint};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+
+/// Default name of the manifest file
+pub(crate) const MANIFEST_FILE_NAME: &str = "pack.toml";
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Manifest {
+    #[serde(default)]
+    pub(crate) pack: PackSettings,
+
+    /// Stickers in the order they appear in the set
+    #[serde(default, rename = "sticker")]
+    pub(crate) stickers: Vec<ManifestItem>,
+
+    /// Directory of the manifest file, which the paths are relative to
+    #[serde(skip)]
+    pub(crate) dir: Utf8PathBuf,
+}
+
+/// Pack-wide settings and defaults for the stickers
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PackSettings {
+    /// Short name of the published set used in the links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+
+    /// Title of the published set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+
+    #[serde(default, rename = "type")]
+    pub(crate) sticker_type: StickerType,
+
+    /// Directory where the generated files are put. It's `out` next to the
+    /// manifest by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<Utf8PathBuf>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) concurrency: Option<NonZeroUsize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) publisher: Option<String>,
+
+    /// Turn the emoji into silhouettes and create the set with
+    /// `needs_repainting`, so that Telegram recolors them into the color of
+    /// the text. Allowed only for the `custom_emoji` sets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) repaint: Option<Repaint>,
+
+    /// Kinds of files generated for the stickers that don't override them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) kinds: Vec<PackKind>,
+
+    /// Emoji of the stickers that don't override them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) emoji: Vec<String>,
+
+    /// Keywords of the stickers that don't override them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) keywords: Vec<String>,
+}
+
+/// A single sticker of the pack
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ManifestItem {
+    /// Path to the source media file relative to the manifest
+    pub(crate) input: Utf8PathBuf,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) begin: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) end: Option<String>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) trim_still: bool,
+
+    /// Same as the `--loop` option of the `video` command
+    #[serde(rename = "loop", skip_serializing_if = "Option::is_none")]
+    pub(crate) loop_mode: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) animate: Option<AnimationPreset>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) filter: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) caption: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) kinds: Vec<PackKind>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) emoji: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) keywords: Vec<String>,
+}
+
+impl Manifest {
+    pub(crate) async fn load(path: &Utf8Path) -> Result<Self> {
+        let content = fs::read_to_string(path).await?;
+
+        let mut manifest: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse the pack manifest `{path}`"))?;
+
+        manifest.dir = path.parent().map(ToOwned::to_owned).unwrap_or_default();
+
+        manifest
+            .load_sidecars()
+            .await
+            .with_context(|| format!("Failed to load the sticker metadata of `{path}`"))?;
+
+        manifest
+            .validate()
+            .with_context(|| format!("Invalid pack manifest `{path}`"))?;
+
+        Ok(manifest)
+    }
+
+    /// Fills the emoji and keywords of the stickers that don't specify them
+    /// from their sidecar files
+    async fn load_sidecars(&mut self) -> Result {
+        for i in 0..self.stickers.len() {
+            let item = &self.stickers[i];
+
+            if !item.emoji.is_empty() && !item.keywords.is_empty() {
+                continue;
+            }
+
+            let Some(meta) = StickerMeta::load(&self.input_path(item)).await? else {
+                continue;
+            };
+
+            let item = &mut self.stickers[i];
+
+            if item.emoji.is_empty() {
+                item.emoji = meta.emoji;
+            }
+
+            if item.keywords.is_empty() {
+                item.keywords = meta.keywords;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result {
+        if self.stickers.is_empty() {
+            bail!("The manifest doesn't contain any stickers");
+        }
+
+        if let Some(name) = &self.pack.name {
+            super::share::validate_name(name)?;
+        }
+
+        if let Some(title) = &self.pack.title {
+            super::share::validate_title(title)?;
+        }
+
+        if self.pack.repaint.is_some() && self.pack.sticker_type != StickerType::CustomEmoji {
+            bail!(
+                "Only the `custom_emoji` sets can be repainted, but the set type is `{}`",
+                self.pack.sticker_type
+            );
+        }
+
+        for item in &self.stickers {
+            item.validate(&self.pack)
+                .with_context(|| format!("Invalid sticker `{}`", item.input))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn input_path(&self, item: &ManifestItem) -> Utf8PathBuf {
+        self.dir.join(&item.input)
+    }
+
+    pub(crate) fn output_dir(&self) -> Utf8PathBuf {
+        self.dir
+            .join(self.pack.output.as_deref().unwrap_or("out".into()))
+    }
+}
+
+impl ManifestItem {
+    fn validate(&self, pack: &PackSettings) -> Result {
+        if self.kinds(pack).is_empty() {
+            bail!("No pack kinds are specified for the sticker or in the `[pack]` section");
+        }
+
+        if !self.kinds(pack).iter().all_unique() {
+            bail!("Duplicate pack kinds found: {:?}", self.kinds(pack));
+        }
+
+        crate::meta::validate(self.emoji(pack), self.keywords(pack))
+    }
+
+    /// Copy of the item without the fields that don't influence the
+    /// generated files
+    pub(crate) fn build_settings(&self) -> Self {
+        Self {
+            input: Default::default(),
+            emoji: vec![],
+            keywords: vec![],
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn kinds<'a>(&'a self, pack: &'a PackSettings) -> &'a [PackKind] {
+        or_default(&self.kinds, &pack.kinds)
+    }
+
+    pub(crate) fn emoji<'a>(&'a self, pack: &'a PackSettings) -> &'a [String] {
+        or_default(&self.emoji, &pack.emoji)
+    }
+
+    pub(crate) fn keywords<'a>(&'a self, pack: &'a PackSettings) -> &'a [String] {
+        or_default(&self.keywords, &pack.keywords)
+    }
+}
+
+fn or_default<'a, T>(value: &'a [T], default: &'a [T]) -> &'a [T] {
+    if value.is_empty() {
+        default
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn invalid_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().unwrap_utf8().join(MANIFEST_FILE_NAME);
+
+        let load = |content: &'static str| {
+            let path = path.clone();
+            async move {
+                fs::write(&path, content).await.unwrap();
+                let err = Manifest::load(&path).await.unwrap_err();
+                format!("{err:#}").replace(path.as_str(), "{manifest}")
+            }
+        };
+
+        expect!["Invalid pack manifest `{manifest}`: The manifest doesn't contain any stickers"]
+            .assert_eq(&load("[pack]\nkinds = ['emoji']").await);
+
+        expect!["Invalid pack manifest `{manifest}`: Invalid sticker `a.mp4`: No pack kinds are specified for the sticker or in the `[pack]` section"]
+            .assert_eq(&load("[[sticker]]\ninput = 'a.mp4'").await);
+
+        let err = load("[[sticker]]\ninput = 'a.mp4'\nkind = ['emoji']").await;
+        assert!(err.contains("unknown field `kind`"), "{err}");
+    }
+}
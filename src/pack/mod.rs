@@ -0,0 +1,29 @@
+//! Publishing of the generated files as Telegram sticker and custom emoji sets
+
+mod build;
+mod import;
+mod journal;
+mod lock;
+mod manifest;
+mod share;
+mod sync;
+mod upload;
+
+use crate::prelude::*;
+
+pub(crate) use build::BuildContext;
+pub(crate) use import::ImportContext;
+pub(crate) use manifest::MANIFEST_FILE_NAME;
+pub(crate) use share::QrOutput;
+pub(crate) use sync::SyncContext;
+pub(crate) use upload::UploadContext;
+
+/// Max number of stickers that can be passed to `createNewStickerSet`.
+/// The rest of them are added one by one.
+const MAX_INITIAL_STICKERS: usize = 50;
+
+/// Checks if the file is generated by the pack commands next to the stickers,
+/// so it must not be uploaded as a sticker
+pub(crate) fn is_generated_file(path: &Utf8Path) -> bool {
+    share::is_qr_png(path) || journal::Journal::is_journal(path)
+}
@@ -0,0 +1,293 @@
+//! Validation of the names and titles of the sets and the links to share them
+
+use crate::display;
+use crate::prelude::*;
+use crate::telegram::{BotApi, StickerType};
+use qrcodegen::{QrCode, QrCodeEcc};
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+/// Max number of characters in the short name of a set
+const MAX_NAME_LEN: usize = 64;
+
+/// Max number of characters in the title of a set
+const MAX_TITLE_LEN: usize = 64;
+
+/// Number of the light modules around the QR code that scanners require
+/// to find it
+const QR_QUIET_ZONE: i32 = 4;
+
+/// Size of a single module of the QR code in the PNG image in pixels
+const QR_PNG_MODULE_SIZE: u32 = 8;
+
+/// Suffix of the PNG files with the QR codes. These files are skipped when
+/// listing the stickers in the directory.
+const QR_PNG_SUFFIX: &str = ".qr.png";
+
+/// Where the QR code with the link to the set is written
+#[derive(clap::ValueEnum, strum::Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum QrOutput {
+    /// Render the QR code with the text characters in the terminal
+    Terminal,
+
+    /// Write the QR code to the `{name}.qr.png` file next to the outputs
+    Png,
+}
+
+/// Checks the short name of the set against the rules of Telegram. It must
+/// consist of the English letters, digits and underscores, begin with
+/// a letter, have no consecutive underscores and end with `_by_<bot_username>`.
+pub(crate) fn validate_name(name: &str) -> Result {
+    let problem = if name.is_empty() {
+        Some("it must not be empty".to_owned())
+    } else if let Some(char) = name
+        .chars()
+        .find(|char| !char.is_ascii_alphanumeric() && *char != '_')
+    {
+        Some(format!(
+            "it may contain only English letters, digits and underscores, but it has `{char}`"
+        ))
+    } else if name.len() > MAX_NAME_LEN {
+        Some(format!(
+            "it must be at most {MAX_NAME_LEN} characters long, but it has {}",
+            name.len()
+        ))
+    } else if !name.starts_with(|char: char| char.is_ascii_alphabetic()) {
+        Some("it must begin with a letter".to_owned())
+    } else if name.contains("__") {
+        Some("it must not contain consecutive underscores".to_owned())
+    } else if !matches!(
+        name.rsplit_once("_by_"),
+        Some((head, bot)) if !head.is_empty() && !bot.is_empty()
+    ) {
+        Some("it must end with `_by_<bot_username>`".to_owned())
+    } else {
+        None
+    };
+
+    if let Some(problem) = problem {
+        bail!("Invalid set name `{name}`: {problem}");
+    }
+
+    Ok(())
+}
+
+pub(crate) fn validate_title(title: &str) -> Result {
+    let len = title.chars().count();
+
+    if !(1..=MAX_TITLE_LEN).contains(&len) {
+        bail!(
+            "The title of the set must be 1-{MAX_TITLE_LEN} characters long, \
+            but it has {len}: `{title}`"
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks the name of the set and that it ends with the username of the bot,
+/// that the set is managed with. The case of the username doesn't matter.
+pub(crate) async fn validate_name_for_bot(api: &BotApi, name: &str) -> Result {
+    validate_name(name)?;
+
+    let bot = api.get_me().await?;
+    let username = bot.username.unwrap_or_default();
+    let suffix = format!("_by_{username}").to_lowercase();
+
+    if !name.to_lowercase().ends_with(&suffix) {
+        bail!("Invalid set name `{name}`: it must end with `_by_{username}` for this bot");
+    }
+
+    Ok(())
+}
+
+/// Returns the link that opens the set in Telegram
+pub(crate) fn link(name: &str, sticker_type: StickerType) -> String {
+    let path = match sticker_type {
+        StickerType::CustomEmoji => "addemoji",
+        StickerType::Regular | StickerType::Mask => "addstickers",
+    };
+    format!("https://t.me/{path}/{name}")
+}
+
+/// Logs the link to the published set and outputs the QR code with it
+/// if requested. The PNG with the QR code is written to the `dir`.
+pub(crate) async fn announce(
+    name: &str,
+    sticker_type: StickerType,
+    qr: Option<QrOutput>,
+    dir: &Utf8Path,
+) -> Result {
+    let link = link(name, sticker_type);
+
+    info!("🔗 Share the set: {}", display::bold(&link));
+
+    let Some(qr) = qr else {
+        return Ok(());
+    };
+
+    let code = QrCode::encode_text(&link, QrCodeEcc::Medium)
+        .context("Failed to encode the link into a QR code")?;
+
+    match qr {
+        QrOutput::Terminal => info!("📱 Scan the QR code:\n{}", render_terminal(&code)),
+        QrOutput::Png => {
+            let path = dir.join(format!("{name}{QR_PNG_SUFFIX}"));
+            fs::create_dir_all(dir).await?;
+            fs::write(&path, render_png(&code)?).await?;
+            info!("📱 Saved the QR code to {}", display::bold(&path));
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn is_qr_png(path: &Utf8Path) -> bool {
+    path.as_str().ends_with(QR_PNG_SUFFIX)
+}
+
+/// Renders two rows of modules per line with the half block characters.
+/// The light modules are drawn with the characters, because the terminals
+/// usually have a dark background.
+fn render_terminal(code: &QrCode) -> String {
+    let range = -QR_QUIET_ZONE..code.size() + QR_QUIET_ZONE;
+    let is_light = |x, y| !code.get_module(x, y);
+
+    range
+        .clone()
+        .step_by(2)
+        .map(|y| {
+            range
+                .clone()
+                .map(
+                    |x| match (is_light(x, y), is_light(x, y + 1) && y + 1 < range.end) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    },
+                )
+                .collect::<String>()
+        })
+        .join("\n")
+}
+
+fn render_png(code: &QrCode) -> Result<Vec<u8>> {
+    let modules = (code.size() + QR_QUIET_ZONE * 2) as u32;
+    let side = modules * QR_PNG_MODULE_SIZE;
+
+    let mut pixmap = Pixmap::new(side, side).context("Failed to create the QR code image")?;
+    pixmap.fill(Color::WHITE);
+
+    let mut paint = Paint::default();
+    paint.set_color(Color::BLACK);
+
+    let module_size = QR_PNG_MODULE_SIZE as f32;
+
+    for y in 0..code.size() {
+        for x in 0..code.size() {
+            if !code.get_module(x, y) {
+                continue;
+            }
+            let rect = Rect::from_xywh(
+                (x + QR_QUIET_ZONE) as f32 * module_size,
+                (y + QR_QUIET_ZONE) as f32 * module_size,
+                module_size,
+                module_size,
+            )
+            .context("Invalid QR code module rectangle")?;
+
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+
+    pixmap
+        .encode_png()
+        .context("Failed to encode the QR code image as PNG")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_validate_name() {
+        let names = [
+            "fire_by_bot",
+            "Fire2_by_Bot",
+            "",
+            "2fire_by_bot",
+            "_fire_by_bot",
+            "fire__x_by_bot",
+            "fire-x_by_bot",
+            "огонь_by_bot",
+            "fire",
+            "fire_by_",
+            &format!("{}_by_bot", "f".repeat(60)),
+        ];
+
+        let results = names
+            .iter()
+            .map(|name| match validate_name(name) {
+                Ok(()) => format!("ok: {name}"),
+                Err(err) => err.to_string(),
+            })
+            .join("\n");
+
+        expect![[r#"
+            ok: fire_by_bot
+            ok: Fire2_by_Bot
+            Invalid set name ``: it must not be empty
+            Invalid set name `2fire_by_bot`: it must begin with a letter
+            Invalid set name `_fire_by_bot`: it must begin with a letter
+            Invalid set name `fire__x_by_bot`: it must not contain consecutive underscores
+            Invalid set name `fire-x_by_bot`: it may contain only English letters, digits and underscores, but it has `-`
+            Invalid set name `огонь_by_bot`: it may contain only English letters, digits and underscores, but it has `о`
+            Invalid set name `fire`: it must end with `_by_<bot_username>`
+            Invalid set name `fire_by_`: it must end with `_by_<bot_username>`
+            Invalid set name `ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff_by_bot`: it must be at most 64 characters long, but it has 67"#]].assert_eq(&results);
+
+        let titles = ["", "Fire", &"🔥".repeat(64), &"🔥".repeat(65)]
+            .iter()
+            .map(|title| validate_title(title).is_ok())
+            .collect_vec();
+
+        assert_eq!(titles, [false, true, true, false]);
+    }
+
+    #[test]
+    fn smoke_qr_code() {
+        let link = link("fire_by_bot", StickerType::CustomEmoji);
+        let code = QrCode::encode_text(&link, QrCodeEcc::Medium).unwrap();
+
+        expect![[r#"
+            https://t.me/addemoji/fire_by_bot
+            █████████████████████████████████████
+            █████████████████████████████████████
+            ████ ▄▄▄▄▄ ██ █ ███▄▄▀▄▄ █ ▄▄▄▄▄ ████
+            ████ █   █ █ ▄ ▀ █▄█ ▀▄ ▄█ █   █ ████
+            ████ █▄▄▄█ █ ██ ▄   ▀▄ ▄▀█ █▄▄▄█ ████
+            ████▄▄▄▄▄▄▄█ ▀ ▀▄▀▄▀▄█▄█▄█▄▄▄▄▄▄▄████
+            ████ █▄▄▄▄▄█▀ █▄▀█▄ ▀▄█ ▀▀ ▄ ▄▄█▀████
+            ███████▀▄▄▄▄█▄▀▄▀█▀█▀▄ ▀██   ▀█▀█████
+            ████▀ ▀ ▄ ▄▄ ▄▀▄▀▀▀▀█▄▀ ▀▀▀▀▀▄▄█▀████
+            ████▀▄▀▀▀ ▄ ▄▄▄▀▄ ▄ ▀██▀ ▄█ ▄▄▄▀█████
+            ████▀▀███▀▄▄▀▄▄▀ █▄▄ ▀█ ▀▀▀▀▀▄ █▀████
+            ████ █ ▄█▀▄ ▀█ ▀██▀▄ ▄██ ▀█▀██▄▀█████
+            ████▄██▄▄▄▄▄▀▀█ ▄▀▀█▀▄▀▄ ▄▄▄ ▀   ████
+            ████ ▄▄▄▄▄ █▀ ▀▀  ▄▄█▄   █▄█ ▄▄██████
+            ████ █   █ █ █▀▀▀▀▄▄▀ █▀▄▄▄▄▄▀  ▀████
+            ████ █▄▄▄█ █▄ ▀▄ █▄▄ █▄▄▀▄▄█  ▄ █████
+            ████▄▄▄▄▄▄▄█▄█▄█▄█▄█▄██▄█▄█▄▄▄▄██████
+            █████████████████████████████████████
+            ▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀"#]]
+        .assert_eq(&format!("{link}\n{}", render_terminal(&code)));
+
+        let png = render_png(&code).unwrap();
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+
+        let side = (code.size() + QR_QUIET_ZONE * 2) as u32 * QR_PNG_MODULE_SIZE;
+        assert_eq!((pixmap.width(), pixmap.height()), (side, side));
+    }
+}
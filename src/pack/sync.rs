@@ -0,0 +1,959 @@
+use super::build::{BuildContext, ItemOutputs};
+use super::lock::{LockedSticker, Lockfile};
+use super::manifest::Manifest;
+use super::share::{self, QrOutput};
+use super::upload::{upload_sticker, UploadedSticker};
+use super::MAX_INITIAL_STICKERS;
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::telegram::{BotApi, Sticker, StickerFormat, StickerSet, StickerType};
+use crate::util::input;
+use crate::video::PackKind;
+use buildstructor::buildstructor;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+
+pub(crate) struct SyncContext {
+    api: BotApi,
+    user_id: i64,
+    manifest: Utf8PathBuf,
+
+    /// Apply the plan without asking for confirmation
+    yes: bool,
+
+    /// Delete the stickers of the set that aren't recorded in the lockfile
+    force: bool,
+
+    ffmpeg: Option<Arc<dyn Ffmpeg>>,
+
+    /// Output of the QR code with the link to the set
+    qr: Option<QrOutput>,
+}
+
+/// Desired state of a sticker described by the manifest item
+struct Local {
+    input: Utf8PathBuf,
+
+    /// The generated file that is uploaded for the sticker
+    file: Utf8PathBuf,
+    format: StickerFormat,
+    hash: String,
+
+    emoji: Vec<String>,
+    keywords: Vec<String>,
+}
+
+/// Changes that make the published set match the manifest. The items are
+/// referred to by their indices in the manifest.
+#[derive(Debug, Default)]
+struct Plan {
+    /// The set doesn't exist yet, so it's created with the added stickers
+    create: bool,
+
+    /// Stickers that don't belong to any item
+    delete: Vec<Sticker>,
+
+    /// Items whose files changed with the `file_id`s of their stickers
+    replace: Vec<(usize, String)>,
+
+    add: Vec<usize>,
+    set_emoji: Vec<usize>,
+    set_keywords: Vec<usize>,
+
+    /// Items and the positions they are moved to one by one
+    moves: Vec<(usize, usize)>,
+
+    /// Order of the items in the set after the stickers are deleted, replaced
+    /// and added, but before they are moved
+    staged: Vec<usize>,
+}
+
+#[buildstructor]
+impl SyncContext {
+    #[builder]
+    pub(crate) fn new(
+        api: BotApi,
+        user_id: i64,
+        manifest: Utf8PathBuf,
+        yes: bool,
+        force: bool,
+        ffmpeg: Option<Arc<dyn Ffmpeg>>,
+        qr: Option<QrOutput>,
+    ) -> Self {
+        Self {
+            api,
+            user_id,
+            manifest,
+            yes,
+            force,
+            ffmpeg,
+            qr,
+        }
+    }
+}
+
+impl SyncContext {
+    pub(crate) async fn run(self) -> Result {
+        let manifest = Manifest::load(&self.manifest).await?;
+
+        let name = manifest.pack.name.as_deref().context(
+            "The manifest doesn't specify the `name` of the set in the `[pack]` section",
+        )?;
+
+        // Check it before building anything
+        crate::meta::ensure_resolved(
+            manifest
+                .stickers
+                .iter()
+                .map(|item| (item.input.as_path(), item.emoji(&manifest.pack))),
+        )?;
+
+        let sticker_type = manifest.pack.sticker_type;
+
+        share::validate_name_for_bot(&self.api, name).await?;
+
+        let remote = self.api.find_sticker_set(name).await?;
+
+        match &remote {
+            Some(set) if set.sticker_type != sticker_type => bail!(
+                "The set `{name}` has the type `{}`, but the manifest requires `{sticker_type}`",
+                set.sticker_type,
+            ),
+            None if manifest.pack.title.is_none() => bail!(
+                "The set `{name}` doesn't exist yet, so the `title` in the `[pack]` \
+                section is required to create it",
+            ),
+            _ => {}
+        }
+
+        if let Some(set) = &remote {
+            let repainted = set.stickers.iter().any(|sticker| sticker.needs_repainting);
+
+            if !set.stickers.is_empty() && repainted != manifest.pack.repaint.is_some() {
+                warn!(
+                    "The set `{name}` was created {} `needs_repainting`, which can't be \
+                    changed. Create a new set to change it.",
+                    if repainted { "with" } else { "without" },
+                );
+            }
+        }
+
+        let outputs = BuildContext::builder()
+            .manifest(self.manifest.clone())
+            .force(false)
+            .and_ffmpeg(self.ffmpeg.clone())
+            .build()
+            .run()
+            .await?;
+
+        let mut locals = Vec::with_capacity(outputs.len());
+
+        for (item, outputs) in manifest.stickers.iter().zip(outputs) {
+            let local = Local {
+                input: item.input.clone(),
+                emoji: item.emoji(&manifest.pack).to_vec(),
+                keywords: item.keywords(&manifest.pack).to_vec(),
+                ..Local::from_outputs(sticker_type, outputs).await?
+            };
+
+            locals.push(local);
+        }
+
+        let lock_path = Lockfile::path(&self.manifest);
+        let mut lock = Lockfile::load(&lock_path).await?;
+
+        // The lockfile of another set is useless
+        if lock.name.as_deref() != Some(name) {
+            lock = Lockfile::default();
+        }
+
+        let plan = Plan::new(&locals, remote.as_ref(), &lock);
+
+        // Such stickers were published without the sync or the lockfile was
+        // lost, so there is no way to tell which items they correspond to
+        let unknown = plan
+            .delete
+            .iter()
+            .filter(|sticker| lock.find(&sticker.file_unique_id).is_none())
+            .count();
+
+        if unknown > 0 && !self.force {
+            bail!(
+                "The set `{name}` has {unknown} stickers that aren't recorded in the \
+                lockfile `{lock_path}`, so they can't be matched with the items of \
+                the manifest. Import the set with `tstick pack import` to get the \
+                manifest with the lockfile for it, or pass `--force` to delete these \
+                stickers and upload the items of the manifest instead.",
+            );
+        }
+
+        if plan.is_empty() {
+            info!("✅ The set {} is in sync", display::bold(&name));
+            return self.announce(&manifest, name).await;
+        }
+
+        info!("📋 Plan:\n{}", plan.describe(&locals)?);
+
+        input::read_confirmation("Apply the plan?", self.yes).await?;
+
+        let mut progress = Progress {
+            path: lock_path.clone(),
+            lock,
+        };
+
+        progress.lock.name = Some(name.to_owned());
+
+        let stickers = self
+            .apply(&manifest, name, &plan, &locals, &mut progress)
+            .await?;
+
+        let lock = Lockfile {
+            name: Some(name.to_owned()),
+            stickers: locals
+                .into_iter()
+                .zip(stickers)
+                .map(|(local, file_unique_id)| LockedSticker {
+                    input: local.input,
+                    file_unique_id,
+                    hash: local.hash,
+                    emoji: local.emoji,
+                    keywords: local.keywords,
+                })
+                .collect(),
+        };
+
+        lock.save(&lock_path).await?;
+
+        info!("🎉 Synced the set {}", display::bold(&name));
+
+        self.announce(&manifest, name).await
+    }
+
+    async fn announce(&self, manifest: &Manifest, name: &str) -> Result {
+        let dir = manifest.output_dir();
+        share::announce(name, manifest.pack.sticker_type, self.qr, &dir).await
+    }
+
+    /// Returns the `file_unique_id`s of the stickers of every item. The
+    /// changes are recorded in the lockfile as soon as they are applied, so
+    /// that the next sync continues from them if this one fails.
+    async fn apply(
+        &self,
+        manifest: &Manifest,
+        name: &str,
+        plan: &Plan,
+        locals: &[Local],
+        progress: &mut Progress,
+    ) -> Result<Vec<String>> {
+        let api = &self.api;
+
+        for sticker in &plan.delete {
+            api.delete_sticker_from_set(sticker).await?;
+            progress.delete(&sticker.file_unique_id).await?;
+        }
+
+        for (item, file_id) in &plan.replace {
+            let local = &locals[*item];
+            let uploaded = self.upload(local).await?;
+
+            api.replace_sticker_in_set(self.user_id, name, file_id, &uploaded.sticker)
+                .await?;
+
+            progress.add(local, &uploaded).await?;
+        }
+
+        let mut added = vec![];
+        for &item in &plan.add {
+            added.push((item, self.upload(&locals[item]).await?));
+        }
+
+        let mut added = &added[..];
+
+        if plan.create {
+            let title = manifest.pack.title.as_deref().unwrap_or_default();
+            let (initial, rest) = added.split_at(added.len().min(MAX_INITIAL_STICKERS));
+
+            let stickers = initial
+                .iter()
+                .map(|(_, uploaded)| uploaded.sticker.clone())
+                .collect_vec();
+
+            api.create_new_sticker_set(
+                self.user_id,
+                name,
+                title,
+                &stickers,
+                manifest.pack.sticker_type,
+                manifest.pack.repaint.is_some(),
+            )
+            .await?;
+
+            info!("🆕 Created the set {}", display::bold(&name));
+
+            for (item, uploaded) in initial {
+                progress.add(&locals[*item], uploaded).await?;
+            }
+
+            added = rest;
+        }
+
+        for (item, uploaded) in added {
+            api.add_sticker_to_set(self.user_id, name, &uploaded.sticker)
+                .await?;
+            progress.add(&locals[*item], uploaded).await?;
+        }
+
+        // The new stickers get new `file_id`s, that are only known from the set
+        let set = api.get_sticker_set(name).await?;
+
+        if set.stickers.len() != plan.staged.len() {
+            bail!(
+                "The set `{name}` has {} stickers, but {} were expected. \
+                It was probably modified concurrently. Run the sync again.",
+                set.stickers.len(),
+                plan.staged.len(),
+            );
+        }
+
+        let stickers: HashMap<_, _> = plan.staged.iter().copied().zip(set.stickers).collect();
+
+        for &item in &plan.set_emoji {
+            let local = &locals[item];
+            api.set_sticker_emoji_list(&stickers[&item].file_id, &local.emoji)
+                .await?;
+            progress
+                .update(local, |locked| locked.emoji.clone_from(&local.emoji))
+                .await?;
+        }
+
+        for &item in &plan.set_keywords {
+            let local = &locals[item];
+            api.set_sticker_keywords(&stickers[&item].file_id, &local.keywords)
+                .await?;
+            progress
+                .update(local, |locked| locked.keywords.clone_from(&local.keywords))
+                .await?;
+        }
+
+        for &(item, position) in &plan.moves {
+            api.set_sticker_position_in_set(&stickers[&item].file_id, position)
+                .await?;
+        }
+
+        Ok((0..locals.len())
+            .map(|item| stickers[&item].file_unique_id.clone())
+            .collect())
+    }
+
+    async fn upload(&self, local: &Local) -> Result<UploadedSticker> {
+        upload_sticker(
+            &self.api,
+            self.user_id,
+            &local.file,
+            local.format,
+            &local.emoji,
+            &local.keywords,
+        )
+        .await
+    }
+}
+
+/// Lockfile that is updated while the plan is applied
+struct Progress {
+    path: Utf8PathBuf,
+    lock: Lockfile,
+}
+
+impl Progress {
+    async fn delete(&mut self, file_unique_id: &str) -> Result {
+        self.lock
+            .stickers
+            .retain(|locked| locked.file_unique_id != file_unique_id);
+        self.lock.save(&self.path).await
+    }
+
+    /// Records the sticker added for the item instead of its previous one
+    async fn add(&mut self, local: &Local, uploaded: &UploadedSticker) -> Result {
+        self.lock
+            .stickers
+            .retain(|locked| locked.input != local.input);
+
+        self.lock.stickers.push(LockedSticker {
+            input: local.input.clone(),
+            file_unique_id: uploaded.file_unique_id.clone(),
+            hash: local.hash.clone(),
+            emoji: local.emoji.clone(),
+            keywords: local.keywords.clone(),
+        });
+
+        self.lock.save(&self.path).await
+    }
+
+    async fn update(&mut self, local: &Local, update: impl FnOnce(&mut LockedSticker)) -> Result {
+        let locked = self
+            .lock
+            .stickers
+            .iter_mut()
+            .find(|locked| locked.input == local.input);
+
+        if let Some(locked) = locked {
+            update(locked);
+        }
+
+        self.lock.save(&self.path).await
+    }
+}
+
+impl Local {
+    /// Picks the output that fits the set type. The input, emoji and keywords
+    /// are left empty.
+    async fn from_outputs(sticker_type: StickerType, outputs: ItemOutputs) -> Result<Self> {
+        let kinds = match sticker_type {
+            StickerType::CustomEmoji => [PackKind::Emoji, PackKind::StaticEmoji],
+            StickerType::Regular | StickerType::Mask => {
+                [PackKind::Sticker, PackKind::StaticSticker]
+            }
+        };
+
+        let file = kinds
+            .iter()
+            .find_map(|kind| outputs.get(kind))
+            .with_context(|| {
+                format!(
+                    "The `{sticker_type}` set requires one of the pack kinds {}, \
+                    but only {:?} were generated",
+                    kinds.iter().format(", "),
+                    outputs.keys().collect_vec(),
+                )
+            })?
+            .clone();
+
+        let bytes = fs::read(&file).await?;
+
+        Ok(Self {
+            format: StickerFormat::from_path(&file)?,
+            hash: format!("{:x}", Sha256::digest(&bytes)),
+            file,
+            input: Default::default(),
+            emoji: vec![],
+            keywords: vec![],
+        })
+    }
+}
+
+impl Plan {
+    fn new(locals: &[Local], remote: Option<&StickerSet>, lock: &Lockfile) -> Self {
+        let Some(remote) = remote else {
+            return Self {
+                create: true,
+                add: (0..locals.len()).collect(),
+                staged: (0..locals.len()).collect(),
+                ..Default::default()
+            };
+        };
+
+        let mut plan = Self::default();
+        let mut matched = BTreeSet::new();
+
+        // Items of the remote stickers in the order of the set
+        let mut current = vec![];
+
+        for sticker in &remote.stickers {
+            let item = lock
+                .find(&sticker.file_unique_id)
+                .and_then(|locked| {
+                    let item = locals
+                        .iter()
+                        .position(|local| local.input == locked.input)?;
+                    Some((item, locked))
+                })
+                .filter(|(item, _)| matched.insert(*item));
+
+            let Some((item, locked)) = item else {
+                plan.delete.push(sticker.clone());
+                continue;
+            };
+
+            current.push(item);
+
+            let local = &locals[item];
+
+            if local.hash != locked.hash {
+                plan.replace.push((item, sticker.file_id.clone()));
+                continue;
+            }
+
+            if local.emoji != locked.emoji {
+                plan.set_emoji.push(item);
+            }
+
+            if local.keywords != locked.keywords {
+                plan.set_keywords.push(item);
+            }
+        }
+
+        for item in 0..locals.len() {
+            if !matched.contains(&item) {
+                plan.add.push(item);
+                current.push(item);
+            }
+        }
+
+        plan.staged = current.clone();
+
+        for (position, item) in (0..locals.len()).enumerate() {
+            let index = current.iter().position(|&it| it == item).unwrap();
+
+            if index != position {
+                current.remove(index);
+                current.insert(position, item);
+                plan.moves.push((item, position));
+            }
+        }
+
+        plan
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.create
+            && self.delete.is_empty()
+            && self.replace.is_empty()
+            && self.add.is_empty()
+            && self.set_emoji.is_empty()
+            && self.set_keywords.is_empty()
+            && self.moves.is_empty()
+    }
+
+    fn describe(&self, locals: &[Local]) -> Result<String, fmt::Error> {
+        let f = &mut String::new();
+        {
+            if self.create {
+                writeln!(f, "🆕 create the set")?;
+            }
+
+            for sticker in &self.delete {
+                let emoji = sticker.emoji.as_deref().unwrap_or_default();
+                writeln!(f, "➖ delete {emoji} {}", sticker.file_unique_id)?;
+            }
+
+            for (item, _) in &self.replace {
+                writeln!(f, "🔁 replace {}", locals[*item].input)?;
+            }
+
+            for &item in &self.add {
+                let local = &locals[item];
+                writeln!(f, "➕ add {} {}", local.emoji.join(""), local.input)?;
+            }
+
+            for &item in &self.set_emoji {
+                let local = &locals[item];
+                writeln!(
+                    f,
+                    "😀 set emoji {} of {}",
+                    local.emoji.join(""),
+                    local.input
+                )?;
+            }
+
+            for &item in &self.set_keywords {
+                let local = &locals[item];
+                let keywords = local.keywords.join(", ");
+                writeln!(f, "🔎 set keywords [{keywords}] of {}", local.input)?;
+            }
+
+            for &(item, position) in &self.moves {
+                writeln!(f, "↕️ move {} to {position}", locals[item].input)?;
+            }
+        }
+        Ok(std::mem::take(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::manifest::MANIFEST_FILE_NAME;
+    use crate::telegram::testing::{Failure, FakeBotApi};
+    use crate::video::testing::SharedMockFfmpeg;
+    use axum::http::StatusCode;
+    use expect_test::{expect, Expect};
+
+    struct Fixture {
+        fake: FakeBotApi,
+        _temp_dir: tempfile::TempDir,
+        dir: Utf8PathBuf,
+    }
+
+    impl Fixture {
+        async fn sync(&self, manifest: &str) {
+            self.try_sync(manifest, false).await.unwrap();
+        }
+
+        async fn try_sync(&self, manifest: &str, force: bool) -> Result {
+            let path = self.dir.join(MANIFEST_FILE_NAME);
+            fs::write(&path, manifest).await.unwrap();
+
+            SyncContext::builder()
+                .api(self.fake.client())
+                .user_id(42)
+                .manifest(path)
+                .yes(true)
+                .force(force)
+                .ffmpeg(SharedMockFfmpeg::with_best_crf(25, PackKind::Sticker))
+                .build()
+                .run()
+                .await
+        }
+
+        /// Asserts the stickers of the set as the name of the output they
+        /// were created from with their emoji and keywords
+        async fn assert_set(&self, expected: Expect) {
+            let set = self.fake.sticker_set("fire_by_bot").unwrap();
+
+            let outputs = self.fake.uploaded_file_names();
+
+            let actual = set
+                .stickers
+                .iter()
+                .map(|sticker| {
+                    let keywords = self.fake.sticker_keywords(&sticker.file_id);
+                    format!(
+                        "{} {} [{}]",
+                        outputs[&sticker.file_id],
+                        sticker.emoji.as_deref().unwrap_or_default(),
+                        keywords.join(", "),
+                    )
+                })
+                .join("\n");
+
+            expected.assert_eq(&actual);
+        }
+
+        fn take_calls(&self) -> String {
+            self.fake.take_calls().join("\n")
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_sync() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().unwrap_utf8().to_owned();
+
+        for name in ["a", "b", "c", "d"] {
+            fs::write(dir.join(format!("{name}.mp4")), name)
+                .await
+                .unwrap();
+        }
+
+        let fixture = Fixture {
+            fake: FakeBotApi::start().await,
+            _temp_dir: temp_dir,
+            dir,
+        };
+
+        fixture
+            .sync(
+                "
+                [pack]
+                name  = 'fire_by_bot'
+                title = 'Fire'
+                kinds = ['sticker']
+                emoji = ['🔥']
+
+                [[sticker]]
+                input = 'a.mp4'
+
+                [[sticker]]
+                input = 'b.mp4'
+
+                [[sticker]]
+                input    = 'c.mp4'
+                keywords = ['hot']
+                ",
+            )
+            .await;
+
+        fixture
+            .assert_set(expect![[r#"
+                a-sticker.webm 🔥 []
+                b-sticker.webm 🔥 []
+                c-sticker.webm 🔥 [hot]"#]])
+            .await;
+
+        expect![[r#"
+            getMe
+            getStickerSet
+            uploadStickerFile
+            uploadStickerFile
+            uploadStickerFile
+            createNewStickerSet
+            getStickerSet"#]]
+        .assert_eq(&fixture.take_calls());
+
+        // The output is changed outside of the build, so it's replaced
+        fs::write(fixture.dir.join("out/c-sticker.webm"), "new")
+            .await
+            .unwrap();
+
+        fixture
+            .sync(
+                "
+                [pack]
+                name  = 'fire_by_bot'
+                title = 'Fire'
+                kinds = ['sticker']
+                emoji = ['🔥']
+
+                [[sticker]]
+                input = 'd.mp4'
+
+                [[sticker]]
+                input    = 'c.mp4'
+                keywords = ['hot']
+
+                [[sticker]]
+                input    = 'a.mp4'
+                emoji    = ['💃']
+                keywords = ['dance']
+                ",
+            )
+            .await;
+
+        fixture
+            .assert_set(expect![[r#"
+                d-sticker.webm 🔥 []
+                c-sticker.webm 🔥 [hot]
+                a-sticker.webm 💃 [dance]"#]])
+            .await;
+
+        expect![[r#"
+            getMe
+            getStickerSet
+            deleteStickerFromSet
+            uploadStickerFile
+            replaceStickerInSet
+            uploadStickerFile
+            getStickerSet
+            addStickerToSet
+            getStickerSet
+            setStickerEmojiList
+            setStickerKeywords
+            setStickerPositionInSet
+            setStickerPositionInSet"#]]
+        .assert_eq(&fixture.take_calls());
+
+        let lock = fs::read_to_string(fixture.dir.join("pack.lock"))
+            .await
+            .unwrap();
+
+        expect![[r##"
+            # This file is generated by `tstick pack sync` and `tstick pack import`. Don't edit it manually.
+
+            name = "fire_by_bot"
+
+            [[sticker]]
+            input = "d.mp4"
+            file_unique_id = "unique-5"
+            hash = "8a39d2abd3999ab73c34db2476849cddf303ce389b35826850f9a700589b4a90"
+            emoji = ["🔥"]
+            keywords = []
+
+            [[sticker]]
+            input = "c.mp4"
+            file_unique_id = "unique-4"
+            hash = "11507a0e2f5e69d5dfa40a62a1bd7b6ee57e6bcd85c67c9b8431b36fff21c437"
+            emoji = ["🔥"]
+            keywords = ["hot"]
+
+            [[sticker]]
+            input = "a.mp4"
+            file_unique_id = "unique-1"
+            hash = "8a39d2abd3999ab73c34db2476849cddf303ce389b35826850f9a700589b4a90"
+            emoji = ["💃"]
+            keywords = ["dance"]
+        "##]]
+        .assert_eq(&lock);
+
+        // Nothing changed, so nothing is done
+        fixture
+            .sync(
+                "
+                [pack]
+                name  = 'fire_by_bot'
+                kinds = ['sticker']
+                emoji = ['🔥']
+
+                [[sticker]]
+                input = 'd.mp4'
+
+                [[sticker]]
+                input    = 'c.mp4'
+                keywords = ['hot']
+
+                [[sticker]]
+                input    = 'a.mp4'
+                emoji    = ['💃']
+                keywords = ['dance']
+                ",
+            )
+            .await;
+
+        expect![[r#"
+            getMe
+            getStickerSet"#]]
+        .assert_eq(&fixture.take_calls());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn existing_set_without_lockfile() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().unwrap_utf8().to_owned();
+
+        for name in ["a", "b"] {
+            fs::write(dir.join(format!("{name}.mp4")), name)
+                .await
+                .unwrap();
+        }
+
+        let fixture = Fixture {
+            fake: FakeBotApi::start().await,
+            _temp_dir: temp_dir,
+            dir,
+        };
+
+        let manifest = "
+            [pack]
+            name  = 'fire_by_bot'
+            title = 'Fire'
+            kinds = ['sticker']
+            emoji = ['🔥']
+
+            [[sticker]]
+            input = 'a.mp4'
+
+            [[sticker]]
+            input = 'b.mp4'
+        ";
+
+        fixture.sync(manifest).await;
+        fixture.take_calls();
+
+        // The set exists, but it's unknown which items its stickers belong to
+        fs::remove_file(fixture.dir.join("pack.lock"))
+            .await
+            .unwrap();
+
+        let err = fixture.try_sync(manifest, false).await.unwrap_err();
+
+        expect!["The set `fire_by_bot` has 2 stickers that aren't recorded in the lockfile `{dir}/pack.lock`, so they can't be matched with the items of the manifest. Import the set with `tstick pack import` to get the manifest with the lockfile for it, or pass `--force` to delete these stickers and upload the items of the manifest instead."].assert_eq(&err.to_string().replace(fixture.dir.as_str(), "{dir}"));
+
+        // Nothing is changed in the set
+        expect![[r#"
+            getMe
+            getStickerSet"#]]
+        .assert_eq(&fixture.take_calls());
+
+        fixture.try_sync(manifest, true).await.unwrap();
+
+        fixture
+            .assert_set(expect![[r#"
+            a-sticker.webm 🔥 []
+            b-sticker.webm 🔥 []"#]])
+            .await;
+
+        expect![[r#"
+            getMe
+            getStickerSet
+            deleteStickerFromSet
+            deleteStickerFromSet
+            uploadStickerFile
+            uploadStickerFile
+            getStickerSet
+            addStickerToSet
+            getStickerSet
+            addStickerToSet
+            getStickerSet"#]]
+        .assert_eq(&fixture.take_calls());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn resume_failed_sync() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().unwrap_utf8().to_owned();
+
+        for name in ["a", "b", "c", "d"] {
+            fs::write(dir.join(format!("{name}.mp4")), name)
+                .await
+                .unwrap();
+        }
+
+        let fixture = Fixture {
+            fake: FakeBotApi::start().await,
+            _temp_dir: temp_dir,
+            dir,
+        };
+
+        let manifest = |inputs: &[&str]| {
+            let stickers = inputs
+                .iter()
+                .map(|input| format!("[[sticker]]\ninput = '{input}'\n"))
+                .join("\n");
+
+            format!(
+                "
+                [pack]
+                name  = 'fire_by_bot'
+                title = 'Fire'
+                kinds = ['sticker']
+                emoji = ['🔥']
+
+                {stickers}
+                "
+            )
+        };
+
+        fixture.sync(&manifest(&["a.mp4", "b.mp4"])).await;
+
+        // The output is changed outside of the build, so it's replaced
+        fs::write(fixture.dir.join("out/b-sticker.webm"), "new")
+            .await
+            .unwrap();
+
+        // The sticker is replaced and one more is added, but the last one
+        // fails to be added
+        let bad_request = Some(Failure::Server(StatusCode::BAD_REQUEST));
+        fixture.fake.inject("addStickerToSet", [None, bad_request]);
+
+        let manifest = manifest(&["a.mp4", "b.mp4", "c.mp4", "d.mp4"]);
+
+        let err = fixture.try_sync(&manifest, false).await.unwrap_err();
+
+        expect!["Bot API method `addStickerToSet` failed with 400: Bad Request"]
+            .assert_eq(&err.to_string());
+
+        fixture.take_calls();
+
+        // The applied changes aren't repeated
+        fixture.sync(&manifest).await;
+
+        fixture
+            .assert_set(expect![[r#"
+            a-sticker.webm 🔥 []
+            b-sticker.webm 🔥 []
+            c-sticker.webm 🔥 []
+            d-sticker.webm 🔥 []"#]])
+            .await;
+
+        expect![[r#"
+            getMe
+            getStickerSet
+            uploadStickerFile
+            getStickerSet
+            addStickerToSet
+            getStickerSet"#]]
+        .assert_eq(&fixture.take_calls());
+    }
+}
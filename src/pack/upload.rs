@@ -0,0 +1,537 @@
+use super::journal::{self, Journal, JournalEntry};
+use super::share::{self, QrOutput};
+use super::MAX_INITIAL_STICKERS;
+use crate::display;
+use crate::meta::StickerMeta;
+use crate::prelude::*;
+use crate::telegram::{BotApi, InputFile, InputSticker, StickerFormat, StickerType};
+use buildstructor::buildstructor;
+
+pub(crate) struct UploadContext {
+    api: BotApi,
+    user_id: i64,
+
+    /// Short name of the set used in the links
+    name: String,
+
+    /// Title of the set, which is required only for creating a new set
+    title: Option<String>,
+
+    sticker_type: StickerType,
+
+    /// Create the set with the custom emoji recolored into the color of
+    /// the text
+    needs_repainting: bool,
+
+    inputs: Vec<Utf8PathBuf>,
+
+    /// Emoji and keywords of the stickers that don't have them in the
+    /// sidecar files
+    emoji: Vec<String>,
+    keywords: Vec<String>,
+
+    /// Output of the QR code with the link to the set
+    qr: Option<QrOutput>,
+}
+
+#[buildstructor]
+impl UploadContext {
+    #[builder]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        api: BotApi,
+        user_id: i64,
+        name: String,
+        title: Option<String>,
+        sticker_type: Option<StickerType>,
+        needs_repainting: Option<bool>,
+        inputs: Vec<Utf8PathBuf>,
+        emoji: Vec<String>,
+        keywords: Vec<String>,
+        qr: Option<QrOutput>,
+    ) -> Result<Self> {
+        crate::meta::validate(&emoji, &keywords)?;
+        share::validate_name(&name)?;
+
+        if let Some(title) = &title {
+            share::validate_title(title)?;
+        }
+
+        let sticker_type = sticker_type.unwrap_or(StickerType::Regular);
+        let needs_repainting = needs_repainting.unwrap_or_default();
+
+        if needs_repainting && sticker_type != StickerType::CustomEmoji {
+            bail!(
+                "Only the custom emoji sets can be repainted, but the set type is `{sticker_type}`"
+            );
+        }
+
+        Ok(Self {
+            api,
+            user_id,
+            name,
+            title,
+            sticker_type,
+            needs_repainting,
+            inputs,
+            emoji,
+            keywords,
+            qr,
+        })
+    }
+}
+
+impl UploadContext {
+    pub(crate) async fn run(self) -> Result {
+        let mut files = vec![];
+        for input in &self.inputs {
+            files.extend(crate::fs::files(input).await?);
+        }
+
+        // The stickers are added to the set in the order of their names
+        files.sort();
+
+        if files.is_empty() {
+            bail!("No sticker files were found in the inputs");
+        }
+
+        let formats: Vec<_> = files
+            .iter()
+            .map(|file| StickerFormat::from_path(file))
+            .try_collect()?;
+
+        let mut metas = Vec::with_capacity(files.len());
+
+        for file in &files {
+            let meta = StickerMeta::load(file).await?.unwrap_or_default();
+            metas.push(StickerMeta {
+                emoji: or_default(meta.emoji, &self.emoji),
+                keywords: or_default(meta.keywords, &self.keywords),
+            });
+        }
+
+        crate::meta::ensure_resolved(
+            files
+                .iter()
+                .map(Utf8PathBuf::as_path)
+                .zip(metas.iter().map(|meta| meta.emoji.as_slice())),
+        )?;
+
+        share::validate_name_for_bot(&self.api, &self.name).await?;
+
+        let existing = self.api.find_sticker_set(&self.name).await?;
+
+        // Validate everything before uploading the files to fail fast
+        match &existing {
+            Some(set) if set.sticker_type != self.sticker_type => bail!(
+                "The set `{}` already exists with the type `{}`, but `{}` was requested",
+                self.name,
+                set.sticker_type,
+                self.sticker_type,
+            ),
+            None if self.title.is_none() => bail!(
+                "The set `{}` doesn't exist yet, so the title is required to create it",
+                self.name,
+            ),
+            _ => {}
+        }
+
+        // The journal is kept next to the uploaded files, as the QR code
+        let dir = files[0].parent().unwrap_or(Utf8Path::new(""));
+        let journal_path = Journal::path(dir, &self.name);
+        let mut journal = Journal::load(&journal_path, &self.name).await?;
+
+        // Nothing could have been added to the set that doesn't exist
+        if existing.is_none() {
+            for entry in &mut journal.stickers {
+                entry.added = false;
+            }
+        }
+
+        let mut pending = Vec::with_capacity(files.len());
+
+        for ((file, format), meta) in files.iter().zip(formats).zip(&metas) {
+            let hash = journal::hash_file(file).await?;
+
+            let sticker = match journal.find(file, &hash) {
+                Some(entry) if entry.added => continue,
+                Some(entry) => InputSticker {
+                    sticker: entry.file_id.clone(),
+                    format,
+                    emoji_list: meta.emoji.clone(),
+                    keywords: meta.keywords.clone(),
+                },
+                None => {
+                    let sticker = self
+                        .upload(file, format, meta)
+                        .instrument(info_span!("upload", file = %file))
+                        .await?;
+
+                    journal.stickers.push(JournalEntry {
+                        file: file.clone(),
+                        hash,
+                        file_id: sticker.sticker.clone(),
+                        added: false,
+                    });
+                    journal.save(&journal_path).await?;
+
+                    sticker
+                }
+            };
+
+            pending.push((file.as_path(), sticker));
+        }
+
+        let resumed = files.len() - pending.len();
+
+        if resumed > 0 {
+            info!(
+                "⏩ Resumed the upload, {} stickers were already added to the set",
+                display::bold(&resumed),
+            );
+        }
+
+        let rest = match (&existing, &self.title) {
+            (None, Some(title)) => {
+                let split = pending.len().min(MAX_INITIAL_STICKERS);
+                let (initial, rest) = pending.split_at(split);
+                let stickers = initial
+                    .iter()
+                    .map(|(_, sticker)| sticker.clone())
+                    .collect_vec();
+
+                self.api
+                    .create_new_sticker_set(
+                        self.user_id,
+                        &self.name,
+                        title,
+                        &stickers,
+                        self.sticker_type,
+                        self.needs_repainting,
+                    )
+                    .await?;
+
+                info!("🆕 Created the set {}", display::bold(&self.name));
+
+                journal.mark_added(initial.iter().map(|(file, _)| *file));
+                journal.save(&journal_path).await?;
+
+                rest
+            }
+            _ => &pending[..],
+        };
+
+        for (file, sticker) in rest {
+            self.api
+                .add_sticker_to_set(self.user_id, &self.name, sticker)
+                .await?;
+
+            journal.mark_added([*file]);
+            journal.save(&journal_path).await?;
+        }
+
+        Journal::remove(&journal_path).await?;
+
+        info!(
+            "🎉 Published {} stickers to the set {}",
+            display::bold(&files.len()),
+            display::bold(&self.name),
+        );
+
+        share::announce(&self.name, self.sticker_type, self.qr, dir).await
+    }
+
+    async fn upload(
+        &self,
+        path: &Utf8Path,
+        format: StickerFormat,
+        meta: &StickerMeta,
+    ) -> Result<InputSticker> {
+        upload_sticker(
+            &self.api,
+            self.user_id,
+            path,
+            format,
+            &meta.emoji,
+            &meta.keywords,
+        )
+        .await
+        .map(|uploaded| uploaded.sticker)
+    }
+}
+
+fn or_default(value: Vec<String>, default: &[String]) -> Vec<String> {
+    if value.is_empty() {
+        default.to_vec()
+    } else {
+        value
+    }
+}
+
+/// Sticker whose file was uploaded, but that may not be added to a set yet
+pub(super) struct UploadedSticker {
+    pub(super) sticker: InputSticker,
+
+    /// Identifier of the uploaded file, that the sticker keeps in the set
+    pub(super) file_unique_id: String,
+}
+
+/// Uploads the file and returns the sticker that can be added to a set
+pub(super) async fn upload_sticker(
+    api: &BotApi,
+    user_id: i64,
+    path: &Utf8Path,
+    format: StickerFormat,
+    emoji: &[String],
+    keywords: &[String],
+) -> Result<UploadedSticker> {
+    let file = InputFile {
+        file_name: path.file_name().unwrap_or_default().to_owned(),
+        bytes: fs::read(path).await?,
+    };
+
+    let file = api.upload_sticker_file(user_id, file, format).await?;
+
+    info!("⬆️ Uploaded the file {}", display::bold(&path));
+
+    let sticker = InputSticker {
+        sticker: file.file_id,
+        format,
+        emoji_list: emoji.to_vec(),
+        keywords: keywords.to_vec(),
+    };
+
+    Ok(UploadedSticker {
+        sticker,
+        file_unique_id: file.file_unique_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::testing::{Failure, FakeBotApi};
+    use axum::http::StatusCode;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_upload() {
+        let fake = FakeBotApi::start().await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        for name in ["b-emoji.webm", "a-emoji.webm"] {
+            fs::write(dir.join(name), name).await.unwrap();
+        }
+
+        // The sidecar files take precedence over the default emoji
+        fs::write(dir.join("a-emoji.meta.toml"), "emoji = ['💃']")
+            .await
+            .unwrap();
+
+        let upload = |title: Option<&str>, emoji: &[&str]| {
+            UploadContext::builder()
+                .api(fake.client())
+                .user_id(42)
+                .name("fire_by_bot")
+                .and_title(title.map(ToOwned::to_owned))
+                .sticker_type(StickerType::CustomEmoji)
+                .input(dir.to_owned())
+                .emoji(emoji.iter().map(|emoji| emoji.to_string()).collect())
+                .build()
+                .unwrap()
+                .run()
+        };
+
+        let err = upload(Some("Fire"), &[]).await.unwrap_err();
+        let err = err.to_string().replace(dir.as_str(), "{dir}");
+
+        expect![[r#"
+            The following stickers have no emoji:
+            - `{dir}/b-emoji.webm`
+            Add the emoji to the `{stem}.meta.toml` files next to them. Run `tstick meta` to generate the files with the suggestions."#]]
+        .assert_eq(&err);
+
+        assert!(fake.calls().is_empty(), "{:?}", fake.calls());
+
+        let err = UploadContext::builder()
+            .api(fake.client())
+            .user_id(42)
+            .name("fire_by_other_bot")
+            .title("Fire")
+            .input(dir.to_owned())
+            .emoji(vec!["🔥".to_owned()])
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap_err();
+
+        expect!["Invalid set name `fire_by_other_bot`: it must end with `_by_bot` for this bot"]
+            .assert_eq(&err.to_string());
+
+        assert_eq!(fake.take_calls(), ["getMe"]);
+
+        let err = upload(None, &["🔥"]).await.unwrap_err();
+
+        expect!["The set `fire_by_bot` doesn't exist yet, so the title is required to create it"]
+            .assert_eq(&err.to_string());
+
+        upload(Some("Fire"), &["🔥"]).await.unwrap();
+
+        // Uploading to the existing set appends the stickers to it
+        upload(None, &["🔥"]).await.unwrap();
+
+        let set = fake.sticker_set("fire_by_bot").unwrap();
+
+        let stickers = set
+            .stickers
+            .iter()
+            .map(|sticker| {
+                let bytes = fake.sticker_bytes(&sticker.file_id).unwrap();
+                let emoji = sticker.emoji.as_deref().unwrap_or_default();
+                format!("{} {emoji}", String::from_utf8(bytes).unwrap())
+            })
+            .collect_vec();
+
+        expect![[r#"
+            [
+                "a-emoji.webm 💃",
+                "b-emoji.webm 🔥",
+                "a-emoji.webm 💃",
+                "b-emoji.webm 🔥",
+            ]
+        "#]]
+        .assert_debug_eq(&stickers);
+
+        assert_eq!(set.sticker_type, StickerType::CustomEmoji);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn resume_upload() {
+        let fake = FakeBotApi::start().await;
+        let api = fake.client();
+
+        let file = InputFile {
+            file_name: "first.webm".to_owned(),
+            bytes: b"first.webm".to_vec(),
+        };
+
+        let file = api
+            .upload_sticker_file(42, file, StickerFormat::Video)
+            .await
+            .unwrap();
+
+        let sticker = InputSticker {
+            sticker: file.file_id,
+            format: StickerFormat::Video,
+            emoji_list: vec!["🔥".to_owned()],
+            keywords: vec![],
+        };
+
+        api.create_new_sticker_set(
+            42,
+            "fire_by_bot",
+            "Fire",
+            &[sticker],
+            StickerType::Regular,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        for name in ["a.webm", "b.webm", "c.webm"] {
+            fs::write(dir.join(name), name).await.unwrap();
+        }
+
+        let upload = || {
+            UploadContext::builder()
+                .api(api.clone())
+                .user_id(42)
+                .name("fire_by_bot")
+                .input(dir.to_owned())
+                .emoji(vec!["🔥".to_owned()])
+                .build()
+                .unwrap()
+                .run()
+        };
+
+        let server_error = Some(Failure::Server(StatusCode::INTERNAL_SERVER_ERROR));
+
+        // The second sticker fails even after all the retries
+        fake.inject(
+            "addStickerToSet",
+            std::iter::once(None).chain([server_error; 4]),
+        );
+        fake.take_calls();
+
+        let err = upload().await.unwrap_err();
+
+        expect!["Bot API method `addStickerToSet` failed with 500: Internal Server Error"]
+            .assert_eq(&err.to_string());
+
+        let journal = fs::read_to_string(dir.join("fire_by_bot.upload.toml"))
+            .await
+            .unwrap()
+            .replace(dir.as_str(), "{dir}");
+
+        expect![[r##"
+            # This file is generated by `tstick pack upload` to resume it if it fails. Don't edit it manually.
+
+            name = "fire_by_bot"
+
+            [[sticker]]
+            file = "{dir}/a.webm"
+            hash = "c1c70e935e0796f2212726f05d19433af3e6e4866160d31f253b2d5cab5e523d"
+            file_id = "file-2"
+            added = true
+
+            [[sticker]]
+            file = "{dir}/b.webm"
+            hash = "e1d886dc4b8c186b6824275d97b674c7d7940b43785592bd5f77d226c56bb41d"
+            file_id = "file-3"
+            added = false
+
+            [[sticker]]
+            file = "{dir}/c.webm"
+            hash = "1cb1e4c099f77dcdfbf21a0e6ef7a9518dcf72513b7d84607b97bda551f00bfc"
+            file_id = "file-4"
+            added = false
+        "##]].assert_eq(&journal);
+
+        fake.take_calls();
+
+        // The uploaded files are reused and the added stickers are skipped
+        upload().await.unwrap();
+
+        expect![[r#"
+            [
+                "getMe",
+                "getStickerSet",
+                "getStickerSet",
+                "addStickerToSet",
+                "getStickerSet",
+                "addStickerToSet",
+            ]
+        "#]]
+        .assert_debug_eq(&fake.take_calls());
+
+        assert!(!dir.join("fire_by_bot.upload.toml").exists());
+
+        let stickers = fake
+            .sticker_set("fire_by_bot")
+            .unwrap()
+            .stickers
+            .iter()
+            .map(|sticker| {
+                String::from_utf8(fake.sticker_bytes(&sticker.file_id).unwrap()).unwrap()
+            })
+            .collect_vec();
+
+        assert_eq!(stickers, ["first.webm", "a.webm", "b.webm", "c.webm"]);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::util::duration;
+use crate::video::{
+    AnimationPreset, Fade, LoopMode, MultiVideoGenContext, PackKind, Playback, Repaint, SplitMode,
+    StaticFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+/// Name of the file in the directory of the job where its record is saved
+const RECORD_FILE_NAME: &str = "job.json";
+
+/// Stem of the name of the uploaded file in the directory of the job. The name
+/// sent by the client isn't used, so that it doesn't clash with the record
+/// or the outputs of the job.
+const INPUT_FILE_STEM: &str = "input";
+
+/// Options of the conversion. They have the same meaning as the flags of
+/// the `video` command. The durations use the same syntax as the flags too.
+///
+/// The options that refer to the files on the server, such as the caption
+/// file or the watermark image, aren't supported. The custom ffmpeg filter
+/// isn't supported either, because filters like `movie` can read any file
+/// on the server.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct JobOptions {
+    pub(crate) kinds: Vec<PackKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) begin: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) end: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) split: Option<String>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) trim_still: bool,
+
+    #[serde(default, rename = "loop", skip_serializing_if = "Option::is_none")]
+    pub(crate) loop_mode: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) animate: Option<AnimationPreset>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) still_duration: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) still_fps: Option<NonZeroU32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) static_format: Option<StaticFormat>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) caption: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) repaint: Option<Repaint>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) reverse: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) speed: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) fade: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) publisher: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, strum::Display, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum JobStatus {
+    /// Waiting for the other jobs to finish
+    Queued,
+
+    /// `done` out of `total` output files are generated
+    Running {
+        done: usize,
+        total: usize,
+    },
+
+    Done {
+        outputs: Vec<JobOutput>,
+    },
+
+    Failed {
+        error: String,
+    },
+
+    Cancelled,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct JobOutput {
+    pub(crate) kind: PackKind,
+
+    /// Name of the file that is used to download it
+    pub(crate) file: String,
+
+    pub(crate) size: u64,
+}
+
+/// Description of the job returned by the API and saved in the job store
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct JobRecord {
+    pub(crate) id: u64,
+
+    /// Name of the uploaded input file
+    pub(crate) input: String,
+
+    pub(crate) options: JobOptions,
+
+    #[serde(flatten)]
+    pub(crate) status: JobStatus,
+}
+
+pub(crate) struct Job {
+    pub(crate) id: u64,
+    pub(crate) input: String,
+    pub(crate) options: JobOptions,
+
+    /// Directory with the input, the outputs and the record of the job
+    pub(crate) dir: Utf8PathBuf,
+
+    status: watch::Sender<JobStatus>,
+
+    /// Handle of the task that runs the job to cancel it
+    pub(crate) task: Mutex<Option<AbortHandle>>,
+
+    /// Makes the saves of the record not overwrite a newer status with
+    /// an older one
+    save_lock: tokio::sync::Mutex<()>,
+}
+
+impl JobOptions {
+    pub(crate) fn video_context(
+        &self,
+        input: Utf8PathBuf,
+        output: Utf8PathBuf,
+        ffmpeg: Arc<dyn Ffmpeg>,
+    ) -> Result<MultiVideoGenContext> {
+        let parse_duration = |name, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(duration::parse)
+                .transpose()
+                .with_context(|| format!("Invalid `{name}`"))
+        };
+
+        let split = self.split.as_deref().map(SplitMode::parse).transpose();
+        let loop_mode = self.loop_mode.as_deref().map(LoopMode::parse).transpose();
+        let fade = self.fade.as_deref().map(Fade::parse).transpose();
+
+        if let Some(speed) = self.speed {
+            if !speed.is_finite() || speed <= 0.0 {
+                bail!("Invalid `speed`: it must be a positive number, but got {speed}");
+            }
+        }
+
+        let playback = Playback {
+            reverse: self.reverse,
+            speed: self.speed,
+            fade: fade.context("Invalid `fade`")?,
+            ..Default::default()
+        };
+
+        MultiVideoGenContext::builder()
+            .pack_kinds(self.kinds.clone())
+            .input(input)
+            .output(output)
+            .and_begin(parse_duration("begin", &self.begin)?)
+            .and_end(parse_duration("end", &self.end)?)
+            .and_split(split.context("Invalid `split`")?)
+            .trim_still(self.trim_still)
+            .and_loop_mode(loop_mode.context("Invalid `loop`")?)
+            .and_animation(self.animate)
+            .and_still_duration(parse_duration("still_duration", &self.still_duration)?)
+            .and_still_fps(self.still_fps)
+            .and_static_format(self.static_format)
+            .and_caption(self.caption.clone())
+            .and_repaint(self.repaint.clone())
+            .playback(playback)
+            .and_publisher(self.publisher.clone())
+            .ffmpeg(ffmpeg)
+            // The outputs are owned by the job
+            .overwrite(true)
+            .build()
+    }
+}
+
+impl JobStatus {
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            Self::Done { .. } | Self::Failed { .. } | Self::Cancelled
+        )
+    }
+}
+
+impl Job {
+    pub(crate) fn new(record: JobRecord, dir: Utf8PathBuf) -> Self {
+        Self {
+            id: record.id,
+            input: record.input,
+            options: record.options,
+            dir,
+            status: watch::Sender::new(record.status),
+            task: Default::default(),
+            save_lock: Default::default(),
+        }
+    }
+
+    /// Reads the record of the job from its directory
+    pub(crate) async fn load(dir: Utf8PathBuf) -> Result<Self> {
+        let path = dir.join(RECORD_FILE_NAME);
+        let content = fs::read(&path).await?;
+
+        let record = serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse the job record `{path}`"))?;
+
+        Ok(Self::new(record, dir))
+    }
+
+    pub(crate) async fn save(&self) -> Result {
+        let _guard = self.save_lock.lock().await;
+        let content = serde_json::to_vec_pretty(&self.record())?;
+        fs::write(self.dir.join(RECORD_FILE_NAME), content).await?;
+        Ok(())
+    }
+
+    /// Path to the uploaded file. Only the extension of the name sent by
+    /// the client is kept, because the format of the file is detected by it.
+    pub(crate) fn input_path(&self) -> Utf8PathBuf {
+        let file_name = match Utf8Path::new(&self.input).extension() {
+            Some(extension) => format!("{INPUT_FILE_STEM}.{extension}"),
+            None => INPUT_FILE_STEM.to_owned(),
+        };
+        self.dir.join(file_name)
+    }
+
+    pub(crate) fn output_dir(&self) -> Utf8PathBuf {
+        self.dir.join("out")
+    }
+
+    pub(crate) fn record(&self) -> JobRecord {
+        JobRecord {
+            id: self.id,
+            input: self.input.clone(),
+            options: self.options.clone(),
+            status: self.status(),
+        }
+    }
+
+    pub(crate) fn status(&self) -> JobStatus {
+        self.status.borrow().clone()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<JobStatus> {
+        self.status.subscribe()
+    }
+
+    /// Updates the status and saves the record unless the job is already
+    /// finished. Returns `false` if the status wasn't updated.
+    pub(crate) async fn update(&self, status: JobStatus) -> Result<bool> {
+        let updated = self.status.send_if_modified(|current| {
+            if current.is_finished() {
+                return false;
+            }
+            *current = status;
+            true
+        });
+
+        if updated {
+            self.save().await?;
+        }
+
+        Ok(updated)
+    }
+}
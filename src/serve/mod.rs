@@ -0,0 +1,717 @@
+//! Local REST API that converts the uploaded media into emoji and stickers
+//! in the background jobs
+
+mod job;
+
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::video::MultiVideoGenContext;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use buildstructor::buildstructor;
+use futures::prelude::*;
+use job::{Job, JobOptions, JobOutput, JobRecord, JobStatus};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+
+/// Max size of the uploaded media file
+const MAX_UPLOAD_SIZE: usize = 100 * 1024 * 1024;
+
+pub(crate) struct ServeContext {
+    listen: SocketAddr,
+
+    /// Directory where the jobs are saved to survive the restarts. If it's
+    /// not set, the jobs are kept in a temporary directory.
+    store: Option<Utf8PathBuf>,
+
+    /// Max number of jobs that run at the same time
+    concurrency: NonZeroUsize,
+
+    ffmpeg: Arc<dyn Ffmpeg>,
+}
+
+/// State shared between the request handlers
+struct Server {
+    dir: Utf8PathBuf,
+    ffmpeg: Arc<dyn Ffmpeg>,
+
+    /// Limits the number of jobs that run at the same time
+    permits: Semaphore,
+
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+
+    /// ID of the next created job
+    next_id: AtomicU64,
+}
+
+/// Error returned by the API as `{"error": "..."}`
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+#[buildstructor]
+impl ServeContext {
+    #[builder]
+    pub(crate) fn new(
+        listen: SocketAddr,
+        store: Option<Utf8PathBuf>,
+        concurrency: Option<NonZeroUsize>,
+        ffmpeg: Option<Arc<dyn Ffmpeg>>,
+    ) -> Self {
+        Self {
+            listen,
+            store,
+            concurrency: concurrency
+                .unwrap_or_else(|| MultiVideoGenContext::default_concurrency("")),
+            ffmpeg: ffmpeg.unwrap_or_else(|| Arc::new(crate::ffmpeg::FfmpegProcess)),
+        }
+    }
+}
+
+impl ServeContext {
+    /// Serves the API until the process is stopped with Ctrl+C
+    pub(crate) async fn run(self) -> Result {
+        let listener = TcpListener::bind(self.listen)
+            .await
+            .with_context(|| format!("Failed to listen on {}", self.listen))?;
+
+        let shutdown = async {
+            tokio::signal::ctrl_c().await.ok();
+        };
+
+        self.serve(listener, shutdown).await
+    }
+
+    async fn serve(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result {
+        // Keeps the temporary directory alive until the server stops
+        let temp_dir;
+
+        let dir = match self.store {
+            Some(store) => {
+                fs::create_dir_all(&store).await?;
+                store
+            }
+            None => {
+                temp_dir = tempfile::tempdir()?;
+                temp_dir.path().unwrap_utf8().to_owned()
+            }
+        };
+
+        let server = Arc::new(Server {
+            dir,
+            ffmpeg: self.ffmpeg,
+            permits: Semaphore::new(self.concurrency.get()),
+            jobs: Default::default(),
+            next_id: AtomicU64::new(1),
+        });
+
+        server.clone().resume().await?;
+
+        let app = Router::new()
+            .route("/jobs", get(list_jobs).post(create_job))
+            .route("/jobs/{id}", get(get_job).delete(cancel_job))
+            .route("/jobs/{id}/events", get(job_events))
+            .route("/jobs/{id}/outputs/{file}", get(download_output))
+            .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+            .with_state(server);
+
+        info!(
+            "🌐 Listening on {}",
+            display::bold(&format!("http://{}", listener.local_addr()?))
+        );
+
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Server {
+    /// Loads the jobs from the store and restarts the ones that didn't finish
+    async fn resume(self: Arc<Self>) -> Result {
+        let mut entries = fs::read_dir(&self.dir).await?;
+        let mut unfinished = vec![];
+
+        // IDs of the directories that can't be loaded aren't reused
+        let mut max_id = 0;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let dir = Utf8PathBuf::try_from(entry.path())?;
+
+            // Skip the unrelated files in the store
+            let Some(id) = dir.file_name().and_then(|name| name.parse::<u64>().ok()) else {
+                continue;
+            };
+
+            max_id = max_id.max(id);
+
+            // The server could stop before the record of the job was saved
+            let job = match Job::load(dir.clone()).await {
+                Ok(job) => Arc::new(job),
+                Err(err) => {
+                    warn!("Skipping the job directory `{dir}`: {err:#}");
+                    continue;
+                }
+            };
+
+            if !job.status().is_finished() {
+                unfinished.push(job.clone());
+            }
+
+            self.jobs.lock().unwrap().insert(job.id, job);
+        }
+
+        if !unfinished.is_empty() {
+            info!(
+                "🔁 Resuming {} unfinished jobs",
+                display::bold(&unfinished.len())
+            );
+        }
+
+        self.next_id.store(max_id + 1, atomic::Ordering::Relaxed);
+
+        for job in unfinished {
+            job.update(JobStatus::Queued).await?;
+            self.clone().spawn(job);
+        }
+
+        Ok(())
+    }
+
+    fn job(&self, id: u64) -> ApiResult<Arc<Job>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("Job {id} not found")))
+    }
+
+    fn spawn(self: Arc<Self>, job: Arc<Job>) {
+        let span = info_span!("job", id = job.id);
+        let task_job = job.clone();
+
+        let task = tokio::spawn(
+            async move {
+                let job = task_job;
+
+                let status = match self.run_job(&job).await {
+                    Ok(outputs) => JobStatus::Done { outputs },
+                    Err(err) => {
+                        warn!("The job failed: {err:#}");
+                        JobStatus::Failed {
+                            error: format!("{err:#}"),
+                        }
+                    }
+                };
+
+                if let Err(err) = job.update(status).await {
+                    warn!("Failed to save the job: {err:#}");
+                }
+            }
+            .instrument(span),
+        );
+
+        *job.task.lock().unwrap() = Some(task.abort_handle());
+    }
+
+    async fn run_job(&self, job: &Job) -> Result<Vec<JobOutput>> {
+        let _permit = self.permits.acquire().await?;
+
+        let contexts = job
+            .options
+            .video_context(job.input_path(), job.output_dir(), self.ffmpeg.clone())?
+            .contexts()
+            .await?;
+
+        let total = contexts.len();
+
+        job.update(JobStatus::Running { done: 0, total }).await?;
+
+        fs::create_dir_all(job.output_dir()).await?;
+
+        let mut outputs = vec![];
+
+        for context in contexts {
+            let kind = context.pack_kind;
+            let output = context.output.clone();
+
+            context.generate_file().await?;
+
+            let file = output
+                .file_name()
+                .with_context(|| format!("BUG: the output `{output}` has no file name"))?;
+
+            outputs.push(JobOutput {
+                kind,
+                file: file.to_owned(),
+                size: fs::metadata(&output).await?.len(),
+            });
+
+            let done = outputs.len();
+            job.update(JobStatus::Running { done, total }).await?;
+        }
+
+        Ok(outputs)
+    }
+}
+
+/// Accepts a multipart form with the media in the `file` field and the
+/// JSON [`JobOptions`] in the `options` field
+async fn create_job(
+    State(server): State<Arc<Server>>,
+    mut form: Multipart,
+) -> ApiResult<(StatusCode, Json<JobRecord>)> {
+    let mut file = None;
+    let mut options = None;
+
+    while let Some(field) = form.next_field().await.map_err(ApiError::bad_request)? {
+        match field.name() {
+            Some("file") => {
+                // Only the name of the file is taken to not let it escape
+                // the directory of the job
+                let name = field
+                    .file_name()
+                    .and_then(|name| Utf8Path::new(name).file_name())
+                    .map(ToOwned::to_owned)
+                    .ok_or_else(|| ApiError::bad_request("The `file` field has no file name"))?;
+
+                let bytes = field.bytes().await.map_err(ApiError::bad_request)?;
+                file = Some((name, bytes));
+            }
+            Some("options") => {
+                let text = field.text().await.map_err(ApiError::bad_request)?;
+                let value: JobOptions = serde_json::from_str(&text)
+                    .map_err(|err| ApiError::bad_request(format!("Invalid `options`: {err}")))?;
+                options = Some(value);
+            }
+            name => {
+                let message = format!("Unknown field {name:?}, expected `file` or `options`");
+                return Err(ApiError::bad_request(message));
+            }
+        }
+    }
+
+    let (input, bytes) = file.ok_or_else(|| ApiError::bad_request("The `file` is missing"))?;
+    let options = options.ok_or_else(|| ApiError::bad_request("The `options` are missing"))?;
+
+    // Report the invalid options right away instead of failing the job
+    options
+        .video_context(input.clone().into(), "out".into(), server.ffmpeg.clone())
+        .map_err(ApiError::bad_request)?;
+
+    let id = server.next_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+    let record = JobRecord {
+        id,
+        input,
+        options,
+        status: JobStatus::Queued,
+    };
+
+    let job = Arc::new(Job::new(record, server.dir.join(id.to_string())));
+
+    fs::create_dir_all(&job.dir).await?;
+    fs::write(job.input_path(), bytes).await?;
+    job.save().await?;
+
+    server.jobs.lock().unwrap().insert(id, job.clone());
+
+    info!(id, input = %job.input, "📥 Queued a job");
+
+    server.clone().spawn(job.clone());
+
+    Ok((StatusCode::ACCEPTED, Json(job.record())))
+}
+
+async fn list_jobs(State(server): State<Arc<Server>>) -> Json<Vec<JobRecord>> {
+    let jobs = server.jobs.lock().unwrap();
+    Json(jobs.values().map(|job| job.record()).collect())
+}
+
+async fn get_job(
+    State(server): State<Arc<Server>>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<JobRecord>> {
+    Ok(Json(server.job(id)?.record()))
+}
+
+async fn cancel_job(
+    State(server): State<Arc<Server>>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<JobRecord>> {
+    let job = server.job(id)?;
+
+    if !job.update(JobStatus::Cancelled).await? {
+        let message = format!("The job is already {}", job.status());
+        return Err(ApiError::new(StatusCode::CONFLICT, message));
+    }
+
+    // Dropping the task kills the running ffmpeg process
+    if let Some(task) = job.task.lock().unwrap().take() {
+        task.abort();
+    }
+
+    info!(id, "🚫 Cancelled the job");
+
+    Ok(Json(job.record()))
+}
+
+/// Streams the status of the job as server-sent events until it finishes.
+/// The names of the events are the states of the job.
+async fn job_events(
+    State(server): State<Arc<Server>>,
+    Path(id): Path<u64>,
+) -> ApiResult<Sse<impl Stream<Item = Result<Event, axum::Error>>>> {
+    let receiver = server.job(id)?.subscribe();
+
+    let events = stream::unfold(Some((receiver, true)), |state| async move {
+        let (mut receiver, first) = state?;
+
+        // The current status is sent right away
+        if !first {
+            receiver.changed().await.ok()?;
+        }
+
+        let status = receiver.borrow_and_update().clone();
+        let event = Event::default()
+            .event(status.to_string())
+            .json_data(&status);
+
+        let next = (!status.is_finished()).then_some((receiver, false));
+
+        Some((event, next))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+async fn download_output(
+    State(server): State<Arc<Server>>,
+    Path((id, file)): Path<(u64, String)>,
+) -> ApiResult<Response> {
+    let job = server.job(id)?;
+
+    let JobStatus::Done { outputs } = job.status() else {
+        let message = format!("The job is {}, there are no outputs yet", job.status());
+        return Err(ApiError::new(StatusCode::CONFLICT, message));
+    };
+
+    // Only the files from the list are served to not expose anything else
+    if !outputs.iter().any(|output| output.file == file) {
+        let message = format!("Output `{file}` not found");
+        return Err(ApiError::new(StatusCode::NOT_FOUND, message));
+    }
+
+    let content_type = match Utf8Path::new(&file).extension() {
+        Some("webm") => "video/webm",
+        Some("webp") => "image/webp",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    };
+
+    let bytes = fs::read(job.output_dir().join(&file)).await?;
+
+    Ok(([(header::CONTENT_TYPE, content_type)], bytes).into_response())
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(err: impl std::fmt::Display) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, format!("{err:#}"))
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        let err = err.into();
+        error!("Request failed: {err:#}");
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::testing::SharedMockFfmpeg;
+    use crate::video::PackKind;
+    use expect_test::expect;
+    use reqwest::multipart::{Form, Part};
+    use serde_json::Value;
+    use tokio::sync::oneshot;
+
+    struct TestServer {
+        url: String,
+        client: reqwest::Client,
+        shutdown: oneshot::Sender<()>,
+        server: tokio::task::JoinHandle<Result>,
+    }
+
+    impl TestServer {
+        async fn start(store: &Utf8Path) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+
+            let (shutdown, stopped) = oneshot::channel();
+
+            let context = ServeContext::builder()
+                .listen(listener.local_addr().unwrap())
+                .store(store.to_owned())
+                .ffmpeg(SharedMockFfmpeg::with_best_crf(25, PackKind::Emoji))
+                .build();
+
+            let server = tokio::spawn(context.serve(listener, async {
+                stopped.await.ok();
+            }));
+
+            Self {
+                url,
+                client: reqwest::Client::new(),
+                shutdown,
+                server,
+            }
+        }
+
+        async fn stop(self) {
+            self.shutdown.send(()).unwrap();
+            self.server.await.unwrap().unwrap();
+        }
+
+        async fn create_job(&self, options: &str) -> (StatusCode, Value) {
+            self.upload("clip.mp4", options).await
+        }
+
+        async fn upload(&self, file_name: &str, options: &str) -> (StatusCode, Value) {
+            let file = Part::bytes(b"clip".to_vec()).file_name(file_name.to_owned());
+            let form = Form::new()
+                .part("file", file)
+                .text("options", options.to_owned());
+
+            let response = self
+                .client
+                .post(format!("{}/jobs", self.url))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap();
+
+            (response.status(), response.json().await.unwrap())
+        }
+
+        async fn request(&self, method: reqwest::Method, path: &str) -> reqwest::Response {
+            self.client
+                .request(method, format!("{}{path}", self.url))
+                .send()
+                .await
+                .unwrap()
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_serve() {
+        let store = tempfile::tempdir().unwrap();
+        let store = store.path().unwrap_utf8();
+
+        let server = TestServer::start(store).await;
+
+        let (status, job) = server
+            .create_job(r#"{"kinds": ["emoji"], "loop": "pingpong"}"#)
+            .await;
+
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        expect![[r#"
+            {
+              "id": 1,
+              "input": "clip.mp4",
+              "options": {
+                "kinds": [
+                  "emoji"
+                ],
+                "loop": "pingpong"
+              },
+              "state": "queued"
+            }"#]]
+        .assert_eq(&serde_json::to_string_pretty(&job).unwrap());
+
+        // The stream ends when the job finishes
+        let events = server
+            .request(reqwest::Method::GET, "/jobs/1/events")
+            .await
+            .text()
+            .await
+            .unwrap();
+
+        let last_event = events.trim().rsplit("\n\n").next().unwrap();
+
+        expect![[r#"
+            event: done
+            data: {"state":"done","outputs":[{"kind":"emoji","file":"input-emoji.webm","size":65536}]}"#]]
+        .assert_eq(last_event);
+
+        let output = server
+            .request(reqwest::Method::GET, "/jobs/1/outputs/input-emoji.webm")
+            .await;
+
+        assert_eq!(output.headers()[header::CONTENT_TYPE], "video/webm");
+        assert_eq!(output.bytes().await.unwrap().len(), 65536);
+
+        let error = |response: reqwest::Response| async move {
+            let status = response.status();
+            let body: Value = response.json().await.unwrap();
+            format!("{status}: {}", body["error"].as_str().unwrap())
+        };
+
+        expect!["409 Conflict: The job is already done"]
+            .assert_eq(&error(server.request(reqwest::Method::DELETE, "/jobs/1").await).await);
+
+        expect!["404 Not Found: Output `job.json` not found"].assert_eq(
+            &error(
+                server
+                    .request(reqwest::Method::GET, "/jobs/1/outputs/job.json")
+                    .await,
+            )
+            .await,
+        );
+
+        let (status, body) = server.create_job(r#"{"kinds": []}"#).await;
+        expect!["400 Bad Request: No pack kinds were specified"]
+            .assert_eq(&format!("{status}: {}", body["error"].as_str().unwrap()));
+
+        // The filters like `movie` could read the files on the server
+        let (status, body) = server
+            .create_job(r#"{"kinds": ["emoji"], "filter": "movie=/etc/passwd"}"#)
+            .await;
+        expect!["400 Bad Request: Invalid `options`: unknown field `filter`, expected one of `kinds`, `begin`, `end`, `split`, `trim_still`, `loop`, `animate`, `still_duration`, `still_fps`, `static_format`, `caption`, `repaint`, `reverse`, `speed`, `fade`, `publisher` at line 1 column 29"].assert_eq(&format!("{status}: {}", body["error"].as_str().unwrap()));
+
+        server.stop().await;
+
+        // The server stopped before saving the record of the job
+        fs::create_dir(store.join("5")).await.unwrap();
+
+        // The jobs survive the restart
+        let server = TestServer::start(store).await;
+
+        let jobs: Value = server
+            .request(reqwest::Method::GET, "/jobs")
+            .await
+            .json()
+            .await
+            .unwrap();
+
+        expect![[r#"
+            [
+              {
+                "id": 1,
+                "input": "clip.mp4",
+                "options": {
+                  "kinds": [
+                    "emoji"
+                  ],
+                  "loop": "pingpong"
+                },
+                "outputs": [
+                  {
+                    "file": "input-emoji.webm",
+                    "kind": "emoji",
+                    "size": 65536
+                  }
+                ],
+                "state": "done"
+              }
+            ]"#]]
+        .assert_eq(&serde_json::to_string_pretty(&jobs).unwrap());
+
+        let (_, job) = server.create_job(r#"{"kinds": ["emoji"]}"#).await;
+        assert_eq!(job["id"], 6);
+
+        server.stop().await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn upload_named_like_job_files() {
+        let store = tempfile::tempdir().unwrap();
+        let store = store.path().unwrap_utf8();
+
+        let server = TestServer::start(store).await;
+
+        let mut jobs = vec![];
+
+        for file_name in ["job.json", "out"] {
+            let (status, job) = server.upload(file_name, r#"{"kinds": ["emoji"]}"#).await;
+            assert_eq!(status, StatusCode::ACCEPTED);
+
+            let id = job["id"].as_u64().unwrap();
+
+            // The stream ends when the job finishes
+            let events = server
+                .request(reqwest::Method::GET, &format!("/jobs/{id}/events"))
+                .await
+                .text()
+                .await
+                .unwrap();
+
+            let job_dir = store.join(id.to_string());
+
+            // The record isn't overwritten by the upload and vice versa
+            let record = std::fs::read(job_dir.join("job.json")).unwrap();
+            let record: Value = serde_json::from_slice(&record).unwrap();
+            assert_eq!(record["input"], file_name);
+
+            let files = std::fs::read_dir(&job_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .sorted()
+                .join(", ");
+
+            let last_event = events.trim().rsplit("\n\n").next().unwrap();
+            let last_event = last_event.replace(job_dir.as_str(), "{job}");
+
+            jobs.push(format!("{file_name}: [{files}]\n{last_event}"));
+        }
+
+        expect![[r#"
+            job.json: [input.json, job.json]
+            event: failed
+            data: {"state":"failed","error":"Failed to render the Lottie animation `{job}/input.json`: Failed to parse the Lottie JSON: expected value at line 1 column 1"}
+
+            out: [input, job.json, out]
+            event: done
+            data: {"state":"done","outputs":[{"kind":"emoji","file":"input-emoji.webm","size":65536}]}"#]].assert_eq(&jobs.join("\n\n"));
+
+        server.stop().await;
+    }
+}
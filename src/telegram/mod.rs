@@ -0,0 +1,882 @@
+//! Client of the Telegram Bot API for managing the sticker sets.
+//!
+//! See <https://core.telegram.org/bots/api#stickers>
+
+mod retry;
+#[cfg(test)]
+pub(crate) mod testing;
+mod types;
+
+use crate::prelude::*;
+use buildstructor::buildstructor;
+use reqwest::multipart::{Form, Part};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+use std::future::{self, Future};
+use std::time::Duration;
+
+pub(crate) use retry::RetryPolicy;
+pub(crate) use types::*;
+
+pub(crate) const DEFAULT_BASE_URL: &str = "https://api.telegram.org";
+
+/// Error returned by the Bot API in the response body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ApiError {
+    pub(crate) method: &'static str,
+    pub(crate) error_code: u16,
+    pub(crate) description: String,
+
+    /// Number of seconds to wait before repeating the request that
+    /// exceeded the flood limits
+    pub(crate) retry_after: Option<u64>,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            method,
+            error_code,
+            description,
+            retry_after: _,
+        } = self;
+        write!(
+            f,
+            "Bot API method `{method}` failed with {error_code}: {description}"
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// Checks if the error says that the sticker set doesn't exist
+    pub(crate) fn is_sticker_set_invalid(&self) -> bool {
+        self.description.contains("STICKERSET_INVALID")
+    }
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+    error_code: Option<u16>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
+}
+
+/// Parameters of the request. Unlike [`Form`] they can be sent several times
+/// when the request is retried.
+#[derive(Default)]
+struct Params {
+    fields: Vec<(&'static str, Param)>,
+}
+
+enum Param {
+    Text(String),
+    File(InputFile),
+}
+
+impl Params {
+    fn text(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.fields.push((name, Param::Text(value.into())));
+        self
+    }
+
+    fn file(mut self, name: &'static str, file: InputFile) -> Self {
+        self.fields.push((name, Param::File(file)));
+        self
+    }
+
+    fn to_form(&self) -> Form {
+        self.fields
+            .iter()
+            .fold(Form::new(), |form, (name, param)| match param {
+                Param::Text(value) => form.text(*name, value.clone()),
+                Param::File(file) => form.part(
+                    *name,
+                    Part::bytes(file.bytes.clone()).file_name(file.file_name.clone()),
+                ),
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotApi {
+    http: reqwest::Client,
+
+    /// URL of the Bot API server. It may be a local Bot API server or
+    /// a mock server in tests.
+    base_url: String,
+
+    token: String,
+
+    retry: RetryPolicy,
+}
+
+#[buildstructor]
+impl BotApi {
+    #[builder]
+    pub(crate) fn new(token: String, base_url: Option<String>, retry: Option<RetryPolicy>) -> Self {
+        let base_url = base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_owned());
+
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            token,
+            retry: retry.unwrap_or_default(),
+        }
+    }
+}
+
+impl BotApi {
+    /// Uploads the sticker file to be used in the sticker set methods later
+    pub(crate) async fn upload_sticker_file(
+        &self,
+        user_id: i64,
+        sticker: InputFile,
+        format: StickerFormat,
+    ) -> Result<File> {
+        let params = Params::default()
+            .text("user_id", user_id.to_string())
+            .text("sticker_format", format.to_string())
+            .file("sticker", sticker);
+
+        self.call("uploadStickerFile", params).await
+    }
+
+    /// Creates a sticker set owned by the user. The `name` must end with
+    /// `_by_<bot_username>`. Only the custom emoji sets can be created with
+    /// `needs_repainting`.
+    ///
+    /// The request isn't repeated if the set turns out to exist after
+    /// the failed attempt.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn create_new_sticker_set(
+        &self,
+        user_id: i64,
+        name: &str,
+        title: &str,
+        stickers: &[InputSticker],
+        sticker_type: StickerType,
+        needs_repainting: bool,
+    ) -> Result {
+        let params = Params::default()
+            .text("user_id", user_id.to_string())
+            .text("name", name.to_owned())
+            .text("title", title.to_owned())
+            .text("stickers", serde_json::to_string(stickers)?)
+            .text("sticker_type", sticker_type.to_string())
+            .text("needs_repainting", needs_repainting.to_string());
+
+        let created = || async { Ok(self.find_sticker_set(name).await?.map(|_| true)) };
+
+        self.call_checked("createNewStickerSet", params, created)
+            .await?;
+        Ok(())
+    }
+
+    /// Adds the sticker to the end of the set created by the bot. The request
+    /// isn't repeated if the set turns out to have grown with the sticker
+    /// after the failed attempt.
+    pub(crate) async fn add_sticker_to_set(
+        &self,
+        user_id: i64,
+        name: &str,
+        sticker: &InputSticker,
+    ) -> Result {
+        let params = Params::default()
+            .text("user_id", user_id.to_string())
+            .text("name", name.to_owned())
+            .text("sticker", serde_json::to_string(sticker)?);
+
+        // The same file may already be in the set, so its presence alone
+        // doesn't prove that it was added
+        let before = self.get_sticker_set(name).await?.stickers.len();
+
+        let added = || async {
+            let set = self.get_sticker_set(name).await?;
+
+            Ok(
+                (set.stickers.len() > before && self.has_sticker(&set, sticker).await?)
+                    .then_some(true),
+            )
+        };
+
+        self.call_checked("addStickerToSet", params, added).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn get_sticker_set(&self, name: &str) -> Result<StickerSet> {
+        let params = Params::default().text("name", name.to_owned());
+        self.call("getStickerSet", params).await
+    }
+
+    /// Same as [`Self::get_sticker_set`], but returns `None` if the set
+    /// doesn't exist
+    pub(crate) async fn find_sticker_set(&self, name: &str) -> Result<Option<StickerSet>> {
+        match self.get_sticker_set(name).await {
+            Ok(set) => Ok(Some(set)),
+            Err(err) => match err.downcast_ref::<ApiError>() {
+                Some(api_err) if api_err.is_sticker_set_invalid() => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Checks if the set contains the sticker created from the uploaded file
+    async fn has_sticker(&self, set: &StickerSet, sticker: &InputSticker) -> Result<bool> {
+        // The sticker in the set has a different `file_id`, but the same
+        // `file_unique_id` as the uploaded file
+        let file = self.get_file(&sticker.sticker).await?;
+
+        Ok(set
+            .stickers
+            .iter()
+            .any(|it| it.file_unique_id == file.file_unique_id))
+    }
+
+    /// Replaces the sticker identified by its `file_id` with a new one
+    /// keeping its position in the set. The request isn't repeated if
+    /// the new sticker turns out to be in the set after the failed attempt.
+    pub(crate) async fn replace_sticker_in_set(
+        &self,
+        user_id: i64,
+        name: &str,
+        old_sticker: &str,
+        sticker: &InputSticker,
+    ) -> Result {
+        let params = Params::default()
+            .text("user_id", user_id.to_string())
+            .text("name", name.to_owned())
+            .text("old_sticker", old_sticker.to_owned())
+            .text("sticker", serde_json::to_string(sticker)?);
+
+        let replaced = || async {
+            let set = self.get_sticker_set(name).await?;
+            Ok(self.has_sticker(&set, sticker).await?.then_some(true))
+        };
+
+        self.call_checked("replaceStickerInSet", params, replaced)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the sticker from its set. The request isn't repeated if
+    /// the sticker turns out to be gone after the failed attempt.
+    pub(crate) async fn delete_sticker_from_set(&self, sticker: &Sticker) -> Result {
+        let params = Params::default().text("sticker", sticker.file_id.clone());
+
+        let deleted = || async {
+            let Some(name) = &sticker.set_name else {
+                return Ok(None);
+            };
+
+            // The set is gone together with its last sticker
+            let gone = match self.find_sticker_set(name).await? {
+                Some(set) => !set
+                    .stickers
+                    .iter()
+                    .any(|it| it.file_unique_id == sticker.file_unique_id),
+                None => true,
+            };
+
+            Ok(gone.then_some(true))
+        };
+
+        self.call_checked("deleteStickerFromSet", params, deleted)
+            .await?;
+        Ok(())
+    }
+
+    /// Moves the sticker identified by its `file_id` to the zero-based
+    /// position in the set
+    pub(crate) async fn set_sticker_position_in_set(
+        &self,
+        sticker: &str,
+        position: usize,
+    ) -> Result {
+        let params = Params::default()
+            .text("sticker", sticker.to_owned())
+            .text("position", position.to_string());
+
+        self.call::<bool>("setStickerPositionInSet", params).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn set_sticker_emoji_list(&self, sticker: &str, emoji: &[String]) -> Result {
+        let params = Params::default()
+            .text("sticker", sticker.to_owned())
+            .text("emoji_list", serde_json::to_string(emoji)?);
+
+        self.call::<bool>("setStickerEmojiList", params).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn set_sticker_keywords(&self, sticker: &str, keywords: &[String]) -> Result {
+        let params = Params::default()
+            .text("sticker", sticker.to_owned())
+            .text("keywords", serde_json::to_string(keywords)?);
+
+        self.call::<bool>("setStickerKeywords", params).await?;
+        Ok(())
+    }
+
+    /// Returns the info about the file with the `file_path` to download it
+    pub(crate) async fn get_file(&self, file_id: &str) -> Result<File> {
+        let params = Params::default().text("file_id", file_id.to_owned());
+        self.call("getFile", params).await
+    }
+
+    /// Downloads the file by the `file_path` returned from [`Self::get_file`]
+    pub(crate) async fn download_file(&self, file_path: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/file/bot{}/{file_path}", self.base_url, self.token);
+
+        debug!(file_path, "Downloading file");
+
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Failed to download the file `{file_path}`"))?;
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Failed to read the contents of the file `{file_path}`"))?;
+
+        Ok(bytes.to_vec())
+    }
+
+    /// Returns the bot's own user, which is useful to check the token
+    pub(crate) async fn get_me(&self) -> Result<User> {
+        self.call("getMe", Params::default()).await
+    }
+
+    /// Receives the incoming updates using long polling
+    pub(crate) async fn get_updates(&self, offset: i64, timeout: Duration) -> Result<Vec<Update>> {
+        let params = Params::default()
+            .text("offset", offset.to_string())
+            .text("timeout", timeout.as_secs().to_string())
+            .text("allowed_updates", r#"["message","callback_query"]"#);
+
+        self.call("getUpdates", params).await
+    }
+
+    pub(crate) async fn send_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+        reply_to: Option<i64>,
+        reply_markup: Option<&InlineKeyboardMarkup>,
+    ) -> Result<Message> {
+        let mut params = Params::default()
+            .text("chat_id", chat_id.to_string())
+            .text("text", text.to_owned());
+
+        if let Some(reply_to) = reply_to {
+            params = params.text(
+                "reply_parameters",
+                json!({ "message_id": reply_to }).to_string(),
+            );
+        }
+
+        if let Some(reply_markup) = reply_markup {
+            params = params.text("reply_markup", serde_json::to_string(reply_markup)?);
+        }
+
+        self.call("sendMessage", params).await
+    }
+
+    pub(crate) async fn send_document(
+        &self,
+        chat_id: i64,
+        document: InputFile,
+        caption: &str,
+        reply_to: Option<i64>,
+    ) -> Result<Message> {
+        let mut params = Params::default()
+            .text("chat_id", chat_id.to_string())
+            .text("caption", caption.to_owned())
+            .file("document", document);
+
+        if let Some(reply_to) = reply_to {
+            params = params.text(
+                "reply_parameters",
+                json!({ "message_id": reply_to }).to_string(),
+            );
+        }
+
+        self.call("sendDocument", params).await
+    }
+
+    /// Stops the loading animation of the pressed inline button and shows
+    /// the `text` as a notification to the user
+    pub(crate) async fn answer_callback_query(&self, id: &str, text: Option<&str>) -> Result {
+        let mut params = Params::default().text("callback_query_id", id.to_owned());
+
+        if let Some(text) = text {
+            params = params.text("text", text.to_owned());
+        }
+
+        self.call::<bool>("answerCallbackQuery", params).await?;
+        Ok(())
+    }
+
+    /// Sends the request and retries it according to the [`RetryPolicy`]
+    async fn call<T: DeserializeOwned>(&self, method: &'static str, params: Params) -> Result<T> {
+        self.call_checked(method, params, || future::ready(Ok(None)))
+            .await
+    }
+
+    /// Same as [`Self::call`], but for the requests that must not be applied
+    /// twice. If the previous attempt may have been handled despite the error,
+    /// the `check` is run before repeating the request. The request isn't
+    /// repeated if the `check` returns the result.
+    async fn call_checked<T, F, Fut>(
+        &self,
+        method: &'static str,
+        params: Params,
+        check: F,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<Option<T>>>,
+    {
+        let mut retries = 0;
+
+        loop {
+            let err = match self.try_call(method, params.to_form()).await {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+
+            let Some(delay) = self.retry.delay(retries, &err) else {
+                return Err(err);
+            };
+
+            retries += 1;
+
+            warn!(
+                "{err:#}. Retrying in {:.1}s ({retries}/{})",
+                delay.as_secs_f64(),
+                self.retry.max_retries,
+            );
+
+            tokio::time::sleep(delay).await;
+
+            let outcome_unknown = matches!(
+                retry::classify(&err),
+                Some(failure) if failure.is_outcome_unknown()
+            );
+
+            if !outcome_unknown {
+                continue;
+            }
+
+            if let Some(result) = check().await? {
+                info!("The previous attempt of `{method}` succeeded, so it isn't repeated");
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Sends the request as `multipart/form-data`, which is accepted by all
+    /// methods and is required for uploading the files
+    async fn try_call<T: DeserializeOwned>(&self, method: &'static str, form: Form) -> Result<T> {
+        let url = format!("{}/bot{}/{method}", self.base_url, self.token);
+
+        debug!(method, "Calling Bot API");
+
+        // The URL contains the bot token, so it must not leak into the errors
+        let response = self
+            .http
+            .post(url)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Failed to send the request to `{method}`"))?;
+
+        let status = response.status();
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Failed to read the response of `{method}`"))?;
+
+        let response = serde_json::from_slice::<Response<T>>(&body);
+
+        // Proxies in front of the Bot API respond with the HTML or plain text
+        // errors, that are still worth retrying
+        let response = match response {
+            Ok(response) => response,
+            Err(_) if status.as_u16() == 429 || status.is_server_error() => {
+                let body = String::from_utf8_lossy(&body);
+                let description = match body.trim() {
+                    "" => status.canonical_reason().unwrap_or_default(),
+                    body => body,
+                };
+
+                return Err(ApiError {
+                    method,
+                    error_code: status.as_u16(),
+                    description: description.to_owned(),
+                    retry_after: None,
+                }
+                .into());
+            }
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!(
+                        "Failed to parse the response of `{method}` with status {status}: {}",
+                        String::from_utf8_lossy(&body)
+                    )
+                });
+            }
+        };
+
+        if let (true, Some(result)) = (response.ok, response.result) {
+            return Ok(result);
+        }
+
+        Err(ApiError {
+            method,
+            error_code: response.error_code.unwrap_or(status.as_u16()),
+            description: response
+                .description
+                .unwrap_or_else(|| "no description".to_owned()),
+            retry_after: response.parameters.and_then(|params| params.retry_after),
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{Failure, FakeBotApi};
+    use super::*;
+    use axum::http::StatusCode;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_sticker_set_methods() {
+        let fake = FakeBotApi::start().await;
+        let api = fake.client();
+
+        let mut stickers = vec![];
+
+        for name in ["first", "second", "third"] {
+            let file = InputFile {
+                file_name: format!("{name}.webm"),
+                bytes: name.as_bytes().to_vec(),
+            };
+
+            let file = api
+                .upload_sticker_file(42, file, StickerFormat::Video)
+                .await
+                .unwrap();
+
+            stickers.push(InputSticker {
+                sticker: file.file_id,
+                format: StickerFormat::Video,
+                emoji_list: vec!["🔥".to_owned()],
+                keywords: vec![name.to_owned()],
+            });
+        }
+
+        assert_eq!(api.find_sticker_set("fire_by_bot").await.unwrap(), None);
+
+        api.create_new_sticker_set(
+            42,
+            "fire_by_bot",
+            "Fire",
+            &stickers[..2],
+            StickerType::Regular,
+            false,
+        )
+        .await
+        .unwrap();
+
+        api.add_sticker_to_set(42, "fire_by_bot", &stickers[2])
+            .await
+            .unwrap();
+
+        let set = api.get_sticker_set("fire_by_bot").await.unwrap();
+
+        api.set_sticker_position_in_set(&set.stickers[2].file_id, 0)
+            .await
+            .unwrap();
+
+        api.delete_sticker_from_set(&set.stickers[1]).await.unwrap();
+
+        let set = api.get_sticker_set("fire_by_bot").await.unwrap();
+
+        expect![[r#"
+            StickerSet {
+                name: "fire_by_bot",
+                title: "Fire",
+                sticker_type: Regular,
+                stickers: [
+                    Sticker {
+                        file_id: "file-3",
+                        file_unique_id: "unique-3",
+                        sticker_type: Regular,
+                        width: 512,
+                        height: 512,
+                        is_animated: false,
+                        is_video: true,
+                        emoji: Some(
+                            "🔥",
+                        ),
+                        set_name: Some(
+                            "fire_by_bot",
+                        ),
+                        custom_emoji_id: None,
+                        needs_repainting: false,
+                    },
+                    Sticker {
+                        file_id: "file-1",
+                        file_unique_id: "unique-1",
+                        sticker_type: Regular,
+                        width: 512,
+                        height: 512,
+                        is_animated: false,
+                        is_video: true,
+                        emoji: Some(
+                            "🔥",
+                        ),
+                        set_name: Some(
+                            "fire_by_bot",
+                        ),
+                        custom_emoji_id: None,
+                        needs_repainting: false,
+                    },
+                ],
+            }
+        "#]]
+        .assert_debug_eq(&set);
+
+        expect![[r#"
+            [
+                "uploadStickerFile",
+                "uploadStickerFile",
+                "uploadStickerFile",
+                "getStickerSet",
+                "createNewStickerSet",
+                "getStickerSet",
+                "addStickerToSet",
+                "getStickerSet",
+                "setStickerPositionInSet",
+                "deleteStickerFromSet",
+                "getStickerSet",
+            ]
+        "#]]
+        .assert_debug_eq(&fake.calls());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn api_error() {
+        let fake = FakeBotApi::start().await;
+
+        let err = fake.client().get_sticker_set("missing").await.unwrap_err();
+
+        expect!["Bot API method `getStickerSet` failed with 400: Bad Request: STICKERSET_INVALID"]
+            .assert_eq(&err.to_string());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn retries() {
+        let fake = FakeBotApi::start().await;
+        let api = fake.client();
+
+        let bad_gateway = Some(Failure::Server(StatusCode::BAD_GATEWAY));
+
+        fake.inject(
+            "getMe",
+            [bad_gateway, Some(Failure::FloodWait(1)), bad_gateway],
+        );
+
+        let start = std::time::Instant::now();
+
+        api.get_me().await.unwrap();
+
+        // The `retry_after` is honoured regardless of the retry policy
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(fake.take_calls(), ["getMe"; 4]);
+
+        // The retries are exhausted
+        fake.inject("getMe", [bad_gateway; 4]);
+
+        let err = api.get_me().await.unwrap_err();
+
+        expect!["Bot API method `getMe` failed with 502: Bad Gateway"].assert_eq(&err.to_string());
+        assert_eq!(fake.take_calls(), ["getMe"; 4]);
+
+        // The client errors aren't retried
+        let err = api.get_sticker_set("missing").await.unwrap_err();
+
+        expect!["Bot API method `getStickerSet` failed with 400: Bad Request: STICKERSET_INVALID"]
+            .assert_eq(&err.to_string());
+        assert_eq!(fake.take_calls(), ["getStickerSet"]);
+
+        // The errors of the proxies aren't JSON, but they are retried too
+        fake.inject("getMe", [Some(Failure::Proxy(StatusCode::BAD_GATEWAY)); 4]);
+
+        let err = api.get_me().await.unwrap_err();
+
+        expect!["Bot API method `getMe` failed with 502: <html><body><h1>502 Bad Gateway</h1></body></html>"].assert_eq(&err.to_string());
+        assert_eq!(fake.take_calls(), ["getMe"; 4]);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn non_idempotent_retries() {
+        let fake = FakeBotApi::start().await;
+        let api = fake.client();
+
+        let mut stickers = vec![];
+
+        for name in ["first", "second", "third", "fourth"] {
+            let file = InputFile {
+                file_name: format!("{name}.webm"),
+                bytes: name.as_bytes().to_vec(),
+            };
+
+            let file = api
+                .upload_sticker_file(42, file, StickerFormat::Video)
+                .await
+                .unwrap();
+
+            stickers.push(InputSticker {
+                sticker: file.file_id,
+                format: StickerFormat::Video,
+                emoji_list: vec!["🔥".to_owned()],
+                keywords: vec![],
+            });
+        }
+
+        fake.take_calls();
+
+        // The requests were handled, but their responses were lost
+        let lost = Some(Failure::Lost(StatusCode::BAD_GATEWAY));
+
+        fake.inject("createNewStickerSet", [lost]);
+        fake.inject("addStickerToSet", [lost]);
+
+        api.create_new_sticker_set(
+            42,
+            "fire_by_bot",
+            "Fire",
+            &stickers[..1],
+            StickerType::Regular,
+            false,
+        )
+        .await
+        .unwrap();
+
+        api.add_sticker_to_set(42, "fire_by_bot", &stickers[1])
+            .await
+            .unwrap();
+
+        let set = fake.sticker_set("fire_by_bot").unwrap();
+        assert_eq!(set.stickers.len(), 2);
+
+        expect![[r#"
+            [
+                "createNewStickerSet",
+                "getStickerSet",
+                "getStickerSet",
+                "addStickerToSet",
+                "getStickerSet",
+                "getFile",
+            ]
+        "#]]
+        .assert_debug_eq(&fake.take_calls());
+
+        // The request wasn't handled, so it's repeated
+        fake.inject(
+            "addStickerToSet",
+            [Some(Failure::Proxy(StatusCode::BAD_GATEWAY))],
+        );
+
+        api.add_sticker_to_set(42, "fire_by_bot", &stickers[2])
+            .await
+            .unwrap();
+
+        let set = fake.sticker_set("fire_by_bot").unwrap();
+        assert_eq!(set.stickers.len(), 3);
+
+        expect![[r#"
+            [
+                "getStickerSet",
+                "addStickerToSet",
+                "getStickerSet",
+                "addStickerToSet",
+            ]
+        "#]]
+        .assert_debug_eq(&fake.take_calls());
+        let set = fake.sticker_set("fire_by_bot").unwrap();
+
+        fake.inject("replaceStickerInSet", [lost]);
+        fake.inject("deleteStickerFromSet", [lost]);
+
+        api.replace_sticker_in_set(42, "fire_by_bot", &set.stickers[0].file_id, &stickers[3])
+            .await
+            .unwrap();
+
+        api.delete_sticker_from_set(&set.stickers[1]).await.unwrap();
+
+        // The same file is added again, so it being in the set doesn't
+        // mean that the failed request was handled
+        fake.inject(
+            "addStickerToSet",
+            [Some(Failure::Proxy(StatusCode::BAD_GATEWAY))],
+        );
+
+        api.add_sticker_to_set(42, "fire_by_bot", &stickers[2])
+            .await
+            .unwrap();
+
+        let set = fake.sticker_set("fire_by_bot").unwrap();
+        let files = set
+            .stickers
+            .iter()
+            .map(|sticker| &sticker.file_unique_id)
+            .collect_vec();
+
+        expect![[r#"
+            (
+                [
+                    "unique-4",
+                    "unique-3",
+                    "unique-3",
+                ],
+                [
+                    "replaceStickerInSet",
+                    "getStickerSet",
+                    "getFile",
+                    "deleteStickerFromSet",
+                    "getStickerSet",
+                    "getStickerSet",
+                    "addStickerToSet",
+                    "getStickerSet",
+                    "addStickerToSet",
+                ],
+            )
+        "#]]
+        .assert_debug_eq(&(files, fake.take_calls()));
+    }
+}
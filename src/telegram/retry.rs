@@ -0,0 +1,148 @@
+use super::ApiError;
+use std::time::Duration;
+
+/// Defines how the failed requests are retried. The requests are retried
+/// only if the Bot API asks to wait because of the flood limits (`429`),
+/// fails with a server error (`5xx`) or can't be reached because of the
+/// network. The other errors are returned as is.
+#[derive(Debug, Clone)]
+pub(crate) struct RetryPolicy {
+    /// Max number of retries of a single request
+    pub(crate) max_retries: u32,
+
+    /// Delay before the first retry after a server or network error. It doubles with
+    /// every next retry. It's also the max jitter added to the `retry_after`
+    /// of the flood limit errors, so that the concurrent requests don't hit
+    /// the limit again at the same time.
+    pub(crate) base_delay: Duration,
+
+    /// Max delay between the retries after the server or network errors
+    pub(crate) max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the next retry of the request that failed
+    /// with the `err` after the given number of `retries`, or `None` if it
+    /// must not be retried
+    pub(crate) fn delay(&self, retries: u32, err: &anyhow::Error) -> Option<Duration> {
+        if retries >= self.max_retries {
+            return None;
+        }
+
+        match classify(err)? {
+            Failure::FloodWait(retry_after) => {
+                // Telegram always specifies it, but it's better to wait at
+                // least a bit if it doesn't
+                let retry_after = Duration::from_secs(retry_after.unwrap_or(1));
+                return Some(retry_after + jitter(self.base_delay));
+            }
+            Failure::Server | Failure::Network => {}
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(retries))
+            .min(self.max_delay);
+
+        // Wait at least a half of the backoff, and a random part of the rest
+        Some(backoff / 2 + jitter(backoff / 2))
+    }
+}
+
+/// Failure of the request that is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// The request exceeded the flood limits and wasn't handled. Contains
+    /// the number of seconds to wait if the Bot API specified it.
+    FloodWait(Option<u64>),
+
+    /// The server failed, and the request may have been handled or not
+    Server,
+
+    /// The request or its response was lost on the way, so it may have been
+    /// handled or not
+    Network,
+}
+
+impl Failure {
+    /// Whether the failed request may have been handled by the Bot API anyway
+    pub(crate) fn is_outcome_unknown(self) -> bool {
+        !matches!(self, Self::FloodWait(_))
+    }
+}
+
+/// Returns `None` if the error must not be retried
+pub(crate) fn classify(err: &anyhow::Error) -> Option<Failure> {
+    if let Some(err) = err.downcast_ref::<ApiError>() {
+        return match err.error_code {
+            429 => Some(Failure::FloodWait(err.retry_after)),
+            500.. => Some(Failure::Server),
+            _ => None,
+        };
+    }
+
+    let err = err.downcast_ref::<reqwest::Error>()?;
+
+    // The errors of building the request or decoding the response won't go
+    // away with a retry
+    (err.is_connect() || err.is_timeout() || err.is_request() || err.is_body())
+        .then_some(Failure::Network)
+}
+
+fn jitter(max: Duration) -> Duration {
+    max.mul_f64(fastrand::f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn smoke_retry_delay() {
+        let policy = RetryPolicy {
+            max_retries: 20,
+            ..Default::default()
+        };
+
+        let err = |error_code, retry_after| {
+            anyhow::Error::from(ApiError {
+                method: "addStickerToSet",
+                error_code,
+                description: String::new(),
+                retry_after,
+            })
+        };
+
+        let flood = policy.delay(0, &err(429, Some(7))).unwrap();
+        assert!((7..8).contains(&flood.as_secs()), "{flood:?}");
+
+        for (retries, min, max) in [(0, 500, 1000), (2, 2000, 4000), (10, 30_000, 60_000)] {
+            let delay = policy.delay(retries, &err(502, None)).unwrap();
+            let delay = delay.as_millis();
+            assert!((min..=max).contains(&delay), "{retries}: {delay}");
+        }
+
+        assert_eq!(policy.delay(0, &err(400, None)), None);
+        assert_eq!(policy.delay(20, &err(429, Some(1))), None);
+
+        // Nothing listens on the port, so the connection is refused
+        let network = reqwest::get("http://127.0.0.1:1").await.unwrap_err();
+        let network = anyhow::Error::from(network).context("Failed to send the request");
+
+        assert_eq!(classify(&network), Some(Failure::Network));
+        assert!(policy.delay(0, &network).is_some());
+
+        let parse = serde_json::from_str::<u32>("<html>").unwrap_err();
+        assert_eq!(policy.delay(0, &parse.into()), None);
+    }
+}
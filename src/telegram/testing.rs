@@ -0,0 +1,617 @@
+//! Fake Bot API server that keeps the sticker sets in memory. It listens on
+//! a random local port, so the real client can be tested end to end.
+
+use super::{BotApi, File, RetryPolicy, Sticker, StickerSet, StickerType};
+use axum::extract::{Multipart, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const TOKEN: &str = "test-token";
+
+pub(crate) struct FakeBotApi {
+    base_url: String,
+    state: Arc<Mutex<FakeState>>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    sets: BTreeMap<String, StickerSet>,
+
+    /// Uploaded files by their `file_id`
+    files: HashMap<String, UploadedFile>,
+
+    /// Search keywords of the stickers by their `file_id`
+    keywords: HashMap<String, Vec<String>>,
+
+    /// Names of the sets created with `needs_repainting`
+    repainting: HashSet<String>,
+
+    /// Names of the called methods in the order of the calls
+    calls: Vec<String>,
+
+    next_id: usize,
+
+    /// Updates waiting to be received with `getUpdates`
+    updates: Vec<Value>,
+    next_update_id: i64,
+
+    /// Messages sent by the bot to the users
+    sent: Vec<SentMessage>,
+
+    /// Texts of the answers to the callback queries
+    callback_answers: Vec<String>,
+    next_message_id: i64,
+
+    /// Responses injected into the next calls of the methods by their names
+    injected: HashMap<String, VecDeque<Option<Failure>>>,
+}
+
+/// Failure that the server responds with instead of handling the call
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Failure {
+    /// `429 Too Many Requests` with the given `retry_after` in seconds
+    FloodWait(u64),
+
+    /// Server error with the given status, such as `502 Bad Gateway`
+    Server(StatusCode),
+
+    /// Plain text error with the given status from a proxy in front of
+    /// the server
+    Proxy(StatusCode),
+
+    /// The call is handled, but its response is lost and replaced with
+    /// the server error with the given status
+    Lost(StatusCode),
+}
+
+/// Message sent with `sendMessage` or `sendDocument`
+#[derive(Debug, Clone)]
+pub(crate) struct SentMessage {
+    pub(crate) message_id: i64,
+    pub(crate) chat_id: i64,
+
+    /// Text of the message or the caption of the document
+    pub(crate) text: String,
+
+    pub(crate) reply_to: Option<i64>,
+
+    /// Texts and callback data of the inline buttons
+    pub(crate) buttons: Vec<(String, String)>,
+
+    /// Name and size of the sent document
+    pub(crate) document: Option<(String, usize)>,
+}
+
+struct UploadedFile {
+    file_name: String,
+    bytes: Vec<u8>,
+    format: String,
+}
+
+impl FakeBotApi {
+    pub(crate) async fn start() -> Self {
+        let state = Arc::<Mutex<FakeState>>::default();
+
+        let app = Router::new()
+            .route("/{bot}/{method}", post(handle))
+            .route("/file/{bot}/{*path}", get(download))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self {
+            base_url: format!("http://{addr}"),
+            state,
+        }
+    }
+
+    pub(crate) fn client(&self) -> BotApi {
+        // Don't slow down the tests with the real delays
+        let retry = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        };
+
+        BotApi::builder()
+            .token(TOKEN)
+            .base_url(self.base_url.clone())
+            .retry(retry)
+            .build()
+    }
+
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Makes the next calls of the method get the given responses. `None`
+    /// lets the call be handled as usual.
+    pub(crate) fn inject(
+        &self,
+        method: &str,
+        responses: impl IntoIterator<Item = Option<Failure>>,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .injected
+            .entry(method.to_owned())
+            .or_default()
+            .extend(responses);
+    }
+
+    /// Returns the calls made since the previous call of this method
+    pub(crate) fn take_calls(&self) -> Vec<String> {
+        std::mem::take(&mut self.state.lock().unwrap().calls)
+    }
+
+    pub(crate) fn sticker_set(&self, name: &str) -> Option<StickerSet> {
+        self.state.lock().unwrap().sets.get(name).cloned()
+    }
+
+    pub(crate) fn sticker_keywords(&self, file_id: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.keywords.get(file_id).cloned().unwrap_or_default()
+    }
+
+    /// Stores the file as if it was sent by a user and returns its `file_id`
+    pub(crate) fn add_file(&self, file_name: &str, bytes: &[u8]) -> String {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+
+        let file_id = format!("file-{}", state.next_id);
+
+        let file = UploadedFile {
+            file_name: file_name.to_owned(),
+            bytes: bytes.to_vec(),
+            format: String::new(),
+        };
+
+        state.files.insert(file_id.clone(), file);
+        file_id
+    }
+
+    /// Queues the update that the bot receives with `getUpdates`. The
+    /// `update_id` is assigned automatically.
+    pub(crate) fn push_update(&self, mut update: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.next_update_id += 1;
+        update["update_id"] = json!(state.next_update_id);
+        state.updates.push(update);
+    }
+
+    pub(crate) fn sent_messages(&self) -> Vec<SentMessage> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    pub(crate) fn callback_answers(&self) -> Vec<String> {
+        self.state.lock().unwrap().callback_answers.clone()
+    }
+
+    /// Names of the uploaded files by their `file_id`
+    pub(crate) fn uploaded_file_names(&self) -> HashMap<String, String> {
+        let state = self.state.lock().unwrap();
+        state
+            .files
+            .iter()
+            .map(|(file_id, file)| (file_id.clone(), file.file_name.clone()))
+            .collect()
+    }
+
+    /// Contents of the uploaded file that the sticker was created from
+    pub(crate) fn sticker_bytes(&self, file_id: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.files.get(file_id).map(|file| file.bytes.clone())
+    }
+}
+
+async fn handle(
+    State(state): State<Arc<Mutex<FakeState>>>,
+    Path((bot, method)): Path<(String, String)>,
+    mut multipart: Multipart,
+) -> Response {
+    if bot != format!("bot{TOKEN}") {
+        return error(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+
+    let mut fields = HashMap::new();
+    let mut files = HashMap::new();
+
+    // Requests without parameters have an empty multipart body, which
+    // the parser reports as an incomplete stream
+    while let Ok(Some(field)) = multipart.next_field().await {
+        let name = field.name().unwrap().to_owned();
+
+        if let Some(file_name) = field.file_name() {
+            let file_name = file_name.to_owned();
+            files.insert(name, (file_name, field.bytes().await.unwrap().to_vec()));
+        } else {
+            fields.insert(name, field.text().await.unwrap());
+        }
+    }
+
+    // Imitate the long polling without blocking the other requests
+    if method == "getUpdates" {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    let mut state = state.lock().unwrap();
+
+    state.calls.push(method.clone());
+
+    let injected = state
+        .injected
+        .get_mut(&method)
+        .and_then(VecDeque::pop_front)
+        .flatten();
+
+    match injected {
+        Some(Failure::FloodWait(retry_after)) => {
+            let body = json!({
+                "ok": false,
+                "error_code": 429,
+                "description": format!("Too Many Requests: retry after {retry_after}"),
+                "parameters": { "retry_after": retry_after },
+            });
+            return (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+        }
+        Some(Failure::Server(status)) => {
+            return error(status, status.canonical_reason().unwrap_or_default());
+        }
+        Some(Failure::Proxy(status)) => {
+            let body = format!("<html><body><h1>{status}</h1></body></html>");
+            return (status, body).into_response();
+        }
+        Some(Failure::Lost(status)) => {
+            // The result is dropped along with the response
+            let _ = state.handle(&method, &fields, files);
+            return error(status, status.canonical_reason().unwrap_or_default());
+        }
+        None => {}
+    }
+
+    match state.handle(&method, &fields, files) {
+        Ok(result) => Json(json!({ "ok": true, "result": result })).into_response(),
+        Err(description) => error(
+            StatusCode::BAD_REQUEST,
+            &format!("Bad Request: {description}"),
+        ),
+    }
+}
+
+async fn download(
+    State(state): State<Arc<Mutex<FakeState>>>,
+    Path((bot, path)): Path<(String, String)>,
+) -> Response {
+    if bot != format!("bot{TOKEN}") {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let state = state.lock().unwrap();
+
+    let file = path
+        .strip_prefix("files/")
+        .and_then(|path| path.split('/').next())
+        .and_then(|file_id| state.files.get(file_id));
+
+    match file {
+        Some(file) => file.bytes.clone().into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn error(status: StatusCode, description: &str) -> Response {
+    let body = json!({
+        "ok": false,
+        "error_code": status.as_u16(),
+        "description": description,
+    });
+
+    (status, Json(body)).into_response()
+}
+
+impl FakeState {
+    fn handle(
+        &mut self,
+        method: &str,
+        fields: &HashMap<String, String>,
+        mut files: HashMap<String, (String, Vec<u8>)>,
+    ) -> Result<Value, String> {
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("parameter {name} is required"))
+        };
+
+        let json_field = |name: &str| -> Result<Value, String> {
+            serde_json::from_str(&field(name)?).map_err(|err| err.to_string())
+        };
+
+        match method {
+            "uploadStickerFile" => {
+                let (file_name, bytes) = files
+                    .remove("sticker")
+                    .ok_or("there is no sticker file in the request")?;
+
+                self.next_id += 1;
+
+                let file = File {
+                    file_id: format!("file-{}", self.next_id),
+                    file_unique_id: format!("unique-{}", self.next_id),
+                    file_size: Some(bytes.len() as u64),
+                    file_path: None,
+                };
+
+                let uploaded = UploadedFile {
+                    file_name,
+                    bytes,
+                    format: field("sticker_format")?,
+                };
+
+                self.files.insert(file.file_id.clone(), uploaded);
+
+                Ok(json!(file))
+            }
+            "createNewStickerSet" => {
+                let name = field("name")?;
+
+                if self.sets.contains_key(&name) {
+                    return Err("sticker set name is already occupied".to_owned());
+                }
+
+                let sticker_type = match fields.get("sticker_type") {
+                    Some(sticker_type) => serde_json::from_value(json!(sticker_type))
+                        .map_err(|err| err.to_string())?,
+                    None => StickerType::Regular,
+                };
+
+                if fields.get("needs_repainting").map(String::as_str) == Some("true") {
+                    if sticker_type != StickerType::CustomEmoji {
+                        return Err("needs_repainting is allowed only for custom emoji".to_owned());
+                    }
+                    self.repainting.insert(name.clone());
+                }
+
+                let stickers = json_field("stickers")?
+                    .as_array()
+                    .ok_or("stickers must be an array")?
+                    .iter()
+                    .map(|sticker| self.sticker(&name, sticker_type, sticker))
+                    .collect::<Result<_, _>>()?;
+
+                let set = StickerSet {
+                    name: name.clone(),
+                    title: field("title")?,
+                    sticker_type,
+                    stickers,
+                };
+
+                self.sets.insert(name, set);
+
+                Ok(json!(true))
+            }
+            "addStickerToSet" => {
+                let name = field("name")?;
+
+                let sticker_type = self.set(&name)?.sticker_type;
+                let sticker = self.sticker(&name, sticker_type, &json_field("sticker")?)?;
+
+                self.set(&name)?.stickers.push(sticker);
+
+                Ok(json!(true))
+            }
+            "replaceStickerInSet" => {
+                let name = field("name")?;
+                let old_sticker = field("old_sticker")?;
+
+                let sticker_type = self.set(&name)?.sticker_type;
+                let sticker = self.sticker(&name, sticker_type, &json_field("sticker")?)?;
+
+                let (set, index) = self.find_sticker(&old_sticker)?;
+
+                if set.name != name {
+                    return Err("STICKER_ID_INVALID".to_owned());
+                }
+
+                set.stickers[index] = sticker;
+
+                Ok(json!(true))
+            }
+            "setStickerEmojiList" => {
+                let emoji = json_field("emoji_list")?;
+                let emoji = emoji
+                    .as_array()
+                    .and_then(|emoji| emoji.first())
+                    .and_then(Value::as_str)
+                    .ok_or("emoji_list must not be empty")?
+                    .to_owned();
+
+                let (set, index) = self.find_sticker(&field("sticker")?)?;
+                set.stickers[index].emoji = Some(emoji);
+
+                Ok(json!(true))
+            }
+            "setStickerKeywords" => {
+                let sticker = field("sticker")?;
+                let keywords = serde_json::from_value(json_field("keywords")?)
+                    .map_err(|err| err.to_string())?;
+
+                self.find_sticker(&sticker)?;
+                self.keywords.insert(sticker, keywords);
+
+                Ok(json!(true))
+            }
+            "getFile" => {
+                let file_id = field("file_id")?;
+
+                let file = self.files.get(&file_id).ok_or("wrong file_id specified")?;
+
+                Ok(json!(File {
+                    file_unique_id: file_id.replace("file-", "unique-"),
+                    file_size: Some(file.bytes.len() as u64),
+                    file_path: Some(format!("files/{file_id}/{}", file.file_name)),
+                    file_id,
+                }))
+            }
+            "getMe" => Ok(json!({ "id": 1, "is_bot": true, "username": "bot" })),
+            "getUpdates" => {
+                let offset: i64 = field("offset")?.parse().map_err(|_| "invalid offset")?;
+
+                self.updates
+                    .retain(|update| update["update_id"].as_i64() >= Some(offset));
+
+                Ok(json!(self.updates))
+            }
+            "sendMessage" | "sendDocument" => {
+                let chat_id = field("chat_id")?.parse().map_err(|_| "invalid chat_id")?;
+
+                let reply_to = fields
+                    .get("reply_parameters")
+                    .and_then(|params| serde_json::from_str::<Value>(params).ok())
+                    .and_then(|params| params["message_id"].as_i64());
+
+                let buttons = fields
+                    .get("reply_markup")
+                    .and_then(|markup| serde_json::from_str::<Value>(markup).ok())
+                    .and_then(|markup| markup["inline_keyboard"].as_array().cloned())
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|row| row.as_array().cloned().unwrap_or_default())
+                    .map(|button| {
+                        let text = button["text"].as_str().unwrap_or_default();
+                        let data = button["callback_data"].as_str().unwrap_or_default();
+                        (text.to_owned(), data.to_owned())
+                    })
+                    .collect();
+
+                let document = files
+                    .remove("document")
+                    .map(|(file_name, bytes)| (file_name, bytes.len()));
+
+                let text = fields.get("text").or(fields.get("caption"));
+
+                self.next_message_id += 1;
+
+                self.sent.push(SentMessage {
+                    message_id: self.next_message_id,
+                    chat_id,
+                    text: text.cloned().unwrap_or_default(),
+                    reply_to,
+                    buttons,
+                    document,
+                });
+
+                Ok(json!({
+                    "message_id": self.next_message_id,
+                    "chat": { "id": chat_id },
+                }))
+            }
+            "answerCallbackQuery" => {
+                let text = fields.get("text").cloned().unwrap_or_default();
+                self.callback_answers.push(text);
+                Ok(json!(true))
+            }
+            "getStickerSet" => Ok(json!(self.set(&field("name")?)?)),
+            "deleteStickerFromSet" => {
+                let (set, index) = self.find_sticker(&field("sticker")?)?;
+                set.stickers.remove(index);
+                Ok(json!(true))
+            }
+            "setStickerPositionInSet" => {
+                let position: usize = field("position")?.parse().map_err(|_| "invalid position")?;
+
+                let (set, index) = self.find_sticker(&field("sticker")?)?;
+
+                if position >= set.stickers.len() {
+                    return Err("STICKER_POSITION_INVALID".to_owned());
+                }
+
+                let sticker = set.stickers.remove(index);
+                set.stickers.insert(position, sticker);
+
+                Ok(json!(true))
+            }
+            _ => Err(format!(
+                "method {method} is not supported by the fake server"
+            )),
+        }
+    }
+
+    fn set(&mut self, name: &str) -> Result<&mut StickerSet, String> {
+        self.sets
+            .get_mut(name)
+            .ok_or_else(|| "STICKERSET_INVALID".to_owned())
+    }
+
+    fn find_sticker(&mut self, file_id: &str) -> Result<(&mut StickerSet, usize), String> {
+        self.sets
+            .values_mut()
+            .find_map(|set| {
+                let index = set
+                    .stickers
+                    .iter()
+                    .position(|sticker| sticker.file_id == file_id)?;
+                Some((set, index))
+            })
+            .ok_or_else(|| "STICKER_ID_INVALID".to_owned())
+    }
+
+    /// Creates a sticker from the `InputSticker` JSON
+    fn sticker(
+        &mut self,
+        set_name: &str,
+        sticker_type: StickerType,
+        input: &Value,
+    ) -> Result<Sticker, String> {
+        let file_id = input["sticker"].as_str().ok_or("sticker is required")?;
+
+        let file = self
+            .files
+            .get(file_id)
+            .ok_or("wrong file identifier specified")?;
+
+        if input["format"].as_str() != Some(file.format.as_str()) {
+            return Err("the sticker format doesn't match the uploaded file".to_owned());
+        }
+
+        let emoji = input["emoji_list"]
+            .as_array()
+            .and_then(|emoji| emoji.first())
+            .and_then(Value::as_str)
+            .ok_or("emoji_list must not be empty")?;
+
+        let file_unique_id = file_id.replace("file-", "unique-");
+
+        let keywords = serde_json::from_value(input["keywords"].clone()).unwrap_or_default();
+        self.keywords.insert(file_id.to_owned(), keywords);
+
+        let (side, custom_emoji_id) = match sticker_type {
+            StickerType::CustomEmoji => (100, Some(format!("emoji-{file_unique_id}"))),
+            StickerType::Regular | StickerType::Mask => (512, None),
+        };
+
+        Ok(Sticker {
+            file_id: file_id.to_owned(),
+            file_unique_id,
+            sticker_type,
+            width: side,
+            height: side,
+            is_animated: file.format == "animated",
+            is_video: file.format == "video",
+            emoji: Some(emoji.to_owned()),
+            set_name: Some(set_name.to_owned()),
+            custom_emoji_id,
+            needs_repainting: self.repainting.contains(set_name),
+        })
+    }
+}
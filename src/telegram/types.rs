@@ -0,0 +1,198 @@
+//! Subset of the Bot API types used for managing the sticker sets and
+//! talking to the users.
+//!
+//! See <https://core.telegram.org/bots/api#available-types>
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// File uploaded to the Telegram servers
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct File {
+    /// Identifier that can be used to download or reuse the file
+    pub(crate) file_id: String,
+
+    /// Identifier that is the same over time and for different bots,
+    /// but can't be used to download or reuse the file
+    pub(crate) file_unique_id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) file_size: Option<u64>,
+
+    /// Path to download the file from, which is valid for at least an hour
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) file_path: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StickerSet {
+    pub(crate) name: String,
+    pub(crate) title: String,
+    pub(crate) sticker_type: StickerType,
+    pub(crate) stickers: Vec<Sticker>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Sticker {
+    pub(crate) file_id: String,
+    pub(crate) file_unique_id: String,
+
+    #[serde(rename = "type")]
+    pub(crate) sticker_type: StickerType,
+
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) is_animated: bool,
+    pub(crate) is_video: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) emoji: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) set_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) custom_emoji_id: Option<String>,
+
+    /// The custom emoji is recolored into the color of the text
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) needs_repainting: bool,
+}
+
+#[derive(
+    Deserialize,
+    Serialize,
+    strum::Display,
+    clap::ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum StickerType {
+    #[default]
+    Regular,
+    Mask,
+    CustomEmoji,
+}
+
+/// Format of the sticker file
+#[derive(Deserialize, Serialize, strum::Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum StickerFormat {
+    /// WEBP or PNG image
+    Static,
+    /// TGS animation
+    Animated,
+    /// WEBM video
+    Video,
+}
+
+impl StickerFormat {
+    /// Detects the format of the sticker file by its extension
+    pub(crate) fn from_path(path: &Utf8Path) -> Result<Self> {
+        let ext = path.extension().unwrap_or_default().to_ascii_lowercase();
+
+        Ok(match ext.as_str() {
+            "webp" | "png" => Self::Static,
+            "tgs" => Self::Animated,
+            "webm" => Self::Video,
+            _ => bail!(
+                "Unsupported sticker file `{path}`. Expected a WEBM video, \
+                a TGS animation or a WEBP/PNG image"
+            ),
+        })
+    }
+}
+
+/// Sticker to be added to a set
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InputSticker {
+    /// `file_id` of the file uploaded with [`super::BotApi::upload_sticker_file`]
+    pub(crate) sticker: String,
+
+    pub(crate) format: StickerFormat,
+
+    pub(crate) emoji_list: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) keywords: Vec<String>,
+}
+
+/// File to be uploaded in the `multipart/form-data` request
+#[derive(Debug, Clone)]
+pub(crate) struct InputFile {
+    pub(crate) file_name: String,
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// Incoming update received with [`super::BotApi::get_updates`]
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Update {
+    pub(crate) update_id: i64,
+    pub(crate) message: Option<Message>,
+    pub(crate) callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Message {
+    pub(crate) message_id: i64,
+    pub(crate) chat: Chat,
+
+    pub(crate) video: Option<MediaFile>,
+
+    /// GIF or a video without sound
+    pub(crate) animation: Option<MediaFile>,
+
+    pub(crate) document: Option<MediaFile>,
+
+    /// Available sizes of the photo from the smallest to the largest
+    pub(crate) photo: Option<Vec<MediaFile>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Chat {
+    pub(crate) id: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct User {
+    pub(crate) id: i64,
+    pub(crate) username: Option<String>,
+}
+
+/// Common fields of the video, animation, document and photo size objects
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct MediaFile {
+    pub(crate) file_id: String,
+}
+
+/// Press of an inline keyboard button
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct CallbackQuery {
+    pub(crate) id: String,
+    pub(crate) from: User,
+
+    /// Message with the button that was pressed
+    pub(crate) message: Option<Message>,
+
+    pub(crate) data: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct InlineKeyboardMarkup {
+    pub(crate) inline_keyboard: Vec<Vec<InlineKeyboardButton>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct InlineKeyboardButton {
+    pub(crate) text: String,
+
+    /// Data sent back in the [`CallbackQuery`] when the button is pressed
+    pub(crate) callback_data: String,
+}
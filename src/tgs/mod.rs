@@ -0,0 +1,335 @@
+//! Conversion of the Lottie animations into the TGS animated stickers.
+//!
+//! See the requirements in <https://core.telegram.org/stickers#animated-stickers-and-emoji>
+
+mod optimize;
+pub(crate) mod render;
+mod validate;
+
+use crate::display;
+use crate::prelude::*;
+use crate::util::byte_size::KIB;
+use buildstructor::buildstructor;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+pub(crate) use optimize::OptimizeReport;
+
+const MAX_TGS_BYTES: usize = 64 * KIB;
+const TGS_SIZE: u64 = 512;
+const TGS_FPS: u64 = 60;
+const TGS_DURATION: Duration = Duration::from_secs(3);
+
+const DEFAULT_PRECISION: u32 = 3;
+
+/// Result of converting a single Lottie animation
+#[derive(Debug)]
+pub(crate) struct Tgs {
+    /// Gzip-compressed Lottie JSON
+    pub(crate) bytes: Vec<u8>,
+
+    /// Size of the JSON before the optimization
+    pub(crate) original_json_len: usize,
+
+    /// Size of the JSON after the optimization
+    pub(crate) json_len: usize,
+
+    pub(crate) report: OptimizeReport,
+}
+
+/// Optimizes the Lottie JSON, validates it and compresses it into TGS.
+/// The input may be either the plain JSON or the already compressed TGS.
+pub(crate) fn convert(input: &[u8], precision: u32) -> Result<Tgs> {
+    let json = decompress(input)?;
+
+    let mut lottie: serde_json::Value =
+        serde_json::from_slice(&json).context("Failed to parse the Lottie JSON")?;
+
+    let report = optimize::optimize(&mut lottie, precision);
+
+    let violations = validate::validate(&lottie);
+
+    if !violations.is_empty() {
+        let violations = violations
+            .iter()
+            .format_with("\n", |violation, f| f(&format_args!("- {violation}")));
+
+        bail!("The animation doesn't meet the Telegram requirements:\n{violations}");
+    }
+
+    let optimized = serde_json::to_vec(&lottie)?;
+
+    let mut encoder = GzEncoder::new(vec![], Compression::best());
+    encoder.write_all(&optimized)?;
+    let bytes = encoder.finish()?;
+
+    Ok(Tgs {
+        bytes,
+        original_json_len: json.len(),
+        json_len: optimized.len(),
+        report,
+    })
+}
+
+/// Returns the Lottie JSON decompressing it if the input is TGS
+fn decompress(input: &[u8]) -> Result<Vec<u8>> {
+    if !input.starts_with(&[0x1f, 0x8b]) {
+        return Ok(input.to_vec());
+    }
+
+    let mut json = vec![];
+    GzDecoder::new(input)
+        .read_to_end(&mut json)
+        .context("Failed to decompress the TGS file")?;
+
+    Ok(json)
+}
+
+pub(crate) struct TgsContext {
+    inputs: Vec<Utf8PathBuf>,
+    output: Option<Utf8PathBuf>,
+    precision: u32,
+    overwrite: bool,
+}
+
+#[buildstructor]
+impl TgsContext {
+    #[builder]
+    pub(crate) fn new(
+        inputs: Vec<Utf8PathBuf>,
+        output: Option<Utf8PathBuf>,
+        precision: Option<u32>,
+        overwrite: bool,
+    ) -> Self {
+        Self {
+            inputs,
+            output,
+            precision: precision.unwrap_or(DEFAULT_PRECISION),
+            overwrite,
+        }
+    }
+}
+
+impl TgsContext {
+    pub(crate) async fn run(self) -> Result {
+        let mut inputs = vec![];
+        for input in &self.inputs {
+            if !fs::metadata(input).await?.is_dir() {
+                inputs.push(input.clone());
+                continue;
+            }
+
+            // The `.tgs` files in the directory may be the outputs of
+            // the previous run, so only the Lottie JSON files are taken
+            let files = crate::fs::files(input).await?;
+            inputs.extend(files.into_iter().filter(|file| is_json(file)));
+        }
+
+        let outputs: Vec<_> = inputs
+            .iter()
+            .map(|input| self.out_file(input))
+            .try_collect()?;
+
+        let mut sources = HashMap::new();
+
+        for (input, output) in inputs.iter().zip(&outputs) {
+            if input == output {
+                bail!(
+                    "The output file `{output}` would overwrite the input file. \
+                    Specify another directory with `--output`"
+                );
+            }
+
+            if let Some(other) = sources.insert(output, input) {
+                bail!("The inputs `{other}` and `{input}` would be converted into the same `{output}`");
+            }
+        }
+
+        crate::fs::validate_output_files_overwriting(self.overwrite, outputs.iter().cloned())
+            .await?;
+
+        for (input, output) in inputs.iter().zip(&outputs) {
+            self.convert_file(input, output)
+                .instrument(info_span!("tgs", input = %input))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn convert_file(&self, input: &Utf8Path, output: &Utf8Path) -> Result {
+        let bytes = fs::read(input).await?;
+
+        let tgs = convert(&bytes, self.precision)
+            .with_context(|| format!("Failed to convert `{input}` to TGS"))?;
+
+        let OptimizeReport {
+            rounded_numbers,
+            removed_assets,
+            removed_metadata,
+            removed_hidden,
+        } = tgs.report;
+
+        info!(
+            "🪄 Optimized the JSON from {} to {}: rounded {rounded_numbers} numbers, \
+            removed {removed_assets} unused assets, {removed_metadata} metadata \
+            entries and {removed_hidden} hidden layers",
+            display::bold_human_size(tgs.original_json_len),
+            display::bold_human_size(tgs.json_len),
+        );
+
+        let size = tgs.bytes.len();
+
+        if size > MAX_TGS_BYTES {
+            bail!(
+                "The compressed size {} exceeds the limit of {}. Try reducing \
+                the precision of the numbers with `--precision` or simplifying \
+                the animation",
+                display::bold_human_size(size),
+                display::bold_human_size(MAX_TGS_BYTES),
+            );
+        }
+
+        info!(
+            "✅ The compressed size {} fits into the limit of {}",
+            display::bold_human_size(size),
+            display::bold_human_size(MAX_TGS_BYTES),
+        );
+
+        crate::fs::write_output(output, &tgs.bytes).await
+    }
+
+    fn out_file(&self, input: &Utf8Path) -> Result<Utf8PathBuf> {
+        let out_dir = match &self.output {
+            Some(output) => output.as_path(),
+            None => input.parent().with_context(|| {
+                format!("There is no parent directory for the input file {input}")
+            })?,
+        };
+
+        let file_name = input
+            .file_stem()
+            .with_context(|| format!("Input must have a file name, but got `{input:?}`"))?;
+
+        Ok(out_dir.join(format!("{file_name}.tgs")))
+    }
+}
+
+fn is_json(path: &Utf8Path) -> bool {
+    matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_convert() {
+        let lottie = serde_json::json!({
+            "v": "5.7.4",
+            "nm": "Sticker",
+            "w": 512,
+            "h": 512,
+            "fr": 60,
+            "ip": 0,
+            "op": 180,
+            "assets": [],
+            "layers": [
+                {
+                    "ty": 4,
+                    "nm": "Circle",
+                    "ks": { "p": { "a": 0, "k": [256.0001, 255.99999, 0] } },
+                    "shapes": []
+                }
+            ]
+        });
+
+        let tgs = convert(lottie.to_string().as_bytes(), DEFAULT_PRECISION).unwrap();
+
+        let mut json = String::new();
+        GzDecoder::new(tgs.bytes.as_slice())
+            .read_to_string(&mut json)
+            .unwrap();
+
+        expect![[r#"{"assets":[],"fr":60,"h":512,"ip":0,"layers":[{"ks":{"p":{"a":0,"k":[256,256,0]}},"shapes":[],"ty":4}],"op":180,"v":"5.7.4","w":512}"#]]
+            .assert_eq(&json);
+
+        // The TGS input is accepted too
+        let tgs = convert(&tgs.bytes, DEFAULT_PRECISION).unwrap();
+        assert_eq!(tgs.report, OptimizeReport::default());
+    }
+
+    #[test]
+    fn convert_invalid() {
+        let lottie = serde_json::json!({ "w": 512, "h": 512, "fr": 60, "ip": 0, "op": 240 });
+
+        let err = convert(lottie.to_string().as_bytes(), DEFAULT_PRECISION).unwrap_err();
+
+        expect![[r#"
+            The animation doesn't meet the Telegram requirements:
+            - /op: The animation must not be longer than 3s, but it lasts 4.00s (240 frames)"#]]
+        .assert_eq(&err.to_string());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn run_twice_on_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let lottie =
+            serde_json::json!({ "w": 512, "h": 512, "fr": 60, "ip": 0, "op": 60, "layers": [] });
+        fs::write(dir.join("a.json"), lottie.to_string())
+            .await
+            .unwrap();
+
+        let run = |inputs: Vec<Utf8PathBuf>, output: Option<Utf8PathBuf>| {
+            TgsContext::builder()
+                .inputs(inputs)
+                .and_output(output)
+                .overwrite(true)
+                .build()
+                .run()
+        };
+
+        // The TGS written by the first run isn't taken as an input
+        for _ in 0..2 {
+            run(vec![dir.to_owned()], None).await.unwrap();
+        }
+
+        let files = crate::fs::files(dir)
+            .await
+            .unwrap()
+            .iter()
+            .map(|file| file.file_name().unwrap().to_owned())
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(files, ["a.json", "a.tgs"]);
+
+        let errors = [
+            (vec![dir.join("a.tgs")], None),
+            (
+                vec![dir.join("a.json"), dir.join("a.tgs")],
+                Some(dir.join("out")),
+            ),
+        ]
+        .map(|(inputs, output)| async {
+            run(inputs, output)
+                .await
+                .unwrap_err()
+                .to_string()
+                .replace(dir.as_str(), "{dir}")
+        });
+
+        let errors = futures::future::join_all(errors).await.join("\n");
+
+        expect![[r#"
+            The output file `{dir}/a.tgs` would overwrite the input file. Specify another directory with `--output`
+            The inputs `{dir}/a.json` and `{dir}/a.tgs` would be converted into the same `{dir}/out/a.tgs`"#]].assert_eq(&errors);
+    }
+}
@@ -0,0 +1,257 @@
+use serde_json::{Number, Value};
+use std::collections::BTreeSet;
+
+/// Keys that are used only by the editors and don't affect the rendering
+const METADATA_KEYS: &[&str] = &["meta", "nm", "mn", "cl", "ln"];
+
+/// Statistics of the optimizations applied to the animation
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct OptimizeReport {
+    pub(crate) rounded_numbers: usize,
+    pub(crate) removed_assets: usize,
+    pub(crate) removed_metadata: usize,
+    pub(crate) removed_hidden: usize,
+}
+
+/// Reduces the size of the Lottie JSON without changing how it looks
+pub(crate) fn optimize(lottie: &mut Value, precision: u32) -> OptimizeReport {
+    let mut report = OptimizeReport::default();
+
+    remove_hidden(lottie, &mut report);
+    remove_unused_assets(lottie, &mut report);
+    remove_metadata(lottie, &mut report);
+    round_numbers(lottie, precision, &mut report);
+
+    report
+}
+
+/// Removes the layers and the shapes that are hidden in the editor. The hidden
+/// layers that are parents of the remaining layers are kept, because their
+/// transforms still move their children.
+fn remove_hidden(value: &mut Value, report: &mut OptimizeReport) {
+    match value {
+        Value::Array(items) => {
+            let parents = used_parents(items);
+
+            let len = items.len();
+            items.retain(|item| {
+                !is_hidden(item) || matches!(layer_index(item), Some(ind) if parents.contains(&ind))
+            });
+            report.removed_hidden += len - items.len();
+
+            for item in items {
+                remove_hidden(item, report);
+            }
+        }
+        Value::Object(object) => {
+            for value in object.values_mut() {
+                remove_hidden(value, report);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_hidden(item: &Value) -> bool {
+    item.get("hd").and_then(Value::as_bool) == Some(true)
+}
+
+fn layer_index(item: &Value) -> Option<i64> {
+    item.get("ind").and_then(Value::as_i64)
+}
+
+/// Collects the `ind` of the layers that are referenced through `parent` by
+/// the visible layers, directly or through a chain of hidden parents
+fn used_parents(items: &[Value]) -> BTreeSet<i64> {
+    let parent = |item: &Value| item.get("parent").and_then(Value::as_i64);
+
+    let mut parents: BTreeSet<_> = items
+        .iter()
+        .filter(|item| !is_hidden(item))
+        .filter_map(parent)
+        .collect();
+
+    loop {
+        let len = parents.len();
+
+        let hidden_parents: Vec<_> = items
+            .iter()
+            .filter(|item| matches!(layer_index(item), Some(ind) if parents.contains(&ind)))
+            .filter_map(parent)
+            .collect();
+
+        parents.extend(hidden_parents);
+
+        if parents.len() == len {
+            return parents;
+        }
+    }
+}
+
+/// Removes the assets that aren't referenced by any layer. Assets may
+/// reference each other, so this is repeated until nothing changes.
+fn remove_unused_assets(lottie: &mut Value, report: &mut OptimizeReport) {
+    loop {
+        let mut used = BTreeSet::new();
+        collect_ref_ids(lottie, &mut used);
+
+        let Some(assets) = lottie.get_mut("assets").and_then(Value::as_array_mut) else {
+            return;
+        };
+
+        let len = assets.len();
+
+        assets.retain(|asset| match asset.get("id").and_then(Value::as_str) {
+            Some(id) => used.contains(id),
+            None => true,
+        });
+
+        let removed = len - assets.len();
+
+        if removed == 0 {
+            return;
+        }
+
+        report.removed_assets += removed;
+    }
+}
+
+fn collect_ref_ids(value: &Value, used: &mut BTreeSet<String>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_ref_ids(item, used);
+            }
+        }
+        Value::Object(object) => {
+            if let Some(Value::String(id)) = object.get("refId") {
+                used.insert(id.clone());
+            }
+            for value in object.values() {
+                collect_ref_ids(value, used);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes the names and other editor-specific metadata
+fn remove_metadata(value: &mut Value, report: &mut OptimizeReport) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                remove_metadata(item, report);
+            }
+        }
+        Value::Object(object) => {
+            let len = object.len();
+            object.retain(|key, _| !METADATA_KEYS.contains(&key.as_str()));
+            report.removed_metadata += len - object.len();
+
+            for value in object.values_mut() {
+                remove_metadata(value, report);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rounds the floating point numbers to the given number of decimal places.
+/// The numbers that become whole are stored as integers to save the `.0`.
+fn round_numbers(value: &mut Value, precision: u32, report: &mut OptimizeReport) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                round_numbers(item, precision, report);
+            }
+        }
+        Value::Object(object) => {
+            for value in object.values_mut() {
+                round_numbers(value, precision, report);
+            }
+        }
+        Value::Number(number) => {
+            let Some(float) = number.as_f64().filter(|_| number.is_f64()) else {
+                return;
+            };
+
+            let factor = 10f64.powi(precision as i32);
+            let rounded = (float * factor).round() / factor;
+
+            let rounded = if rounded.fract() == 0.0 && rounded.abs() < i64::MAX as f64 {
+                Number::from(rounded as i64)
+            } else {
+                match Number::from_f64(rounded) {
+                    Some(rounded) => rounded,
+                    None => return,
+                }
+            };
+
+            if rounded != *number {
+                report.rounded_numbers += 1;
+                *number = rounded;
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_optimize() {
+        let mut lottie = serde_json::json!({
+            "nm": "Comp 1",
+            "meta": { "g": "LottieFiles AE 3.1.1" },
+            "w": 512,
+            "assets": [
+                { "id": "comp_0", "layers": [{ "ty": 0, "refId": "comp_1" }] },
+                { "id": "comp_1", "layers": [] },
+                { "id": "comp_2", "layers": [{ "ty": 0, "refId": "comp_3" }] },
+                { "id": "comp_3", "layers": [] }
+            ],
+            "layers": [
+                { "nm": "Visible", "ty": 0, "refId": "comp_0", "ks": { "p": { "k": [255.99999, 12.3456] } } },
+                { "nm": "Hidden", "ty": 0, "refId": "comp_2", "hd": true }
+            ]
+        });
+
+        let report = optimize(&mut lottie, 2);
+
+        expect![[r#"
+            OptimizeReport {
+                rounded_numbers: 2,
+                removed_assets: 2,
+                removed_metadata: 3,
+                removed_hidden: 1,
+            }
+        "#]]
+        .assert_debug_eq(&report);
+
+        expect![[r#"{"assets":[{"id":"comp_0","layers":[{"refId":"comp_1","ty":0}]},{"id":"comp_1","layers":[]}],"layers":[{"ks":{"p":{"k":[256,12.35]}},"refId":"comp_0","ty":0}],"w":512}"#]]
+            .assert_eq(&lottie.to_string());
+    }
+
+    #[test]
+    fn hidden_parent_layers() {
+        let mut lottie = serde_json::json!({
+            "layers": [
+                { "ind": 1, "ty": 3, "hd": true },
+                { "ind": 2, "ty": 3, "hd": true, "parent": 1 },
+                { "ind": 3, "ty": 4, "parent": 2 },
+                { "ind": 4, "ty": 3, "hd": true },
+                { "ind": 5, "ty": 4, "hd": true, "parent": 4 }
+            ]
+        });
+
+        let report = optimize(&mut lottie, 2);
+
+        assert_eq!(report.removed_hidden, 2);
+
+        expect![[r#"{"layers":[{"hd":true,"ind":1,"ty":3},{"hd":true,"ind":2,"parent":1,"ty":3},{"ind":3,"parent":2,"ty":4}]}"#]]
+            .assert_eq(&lottie.to_string());
+    }
+}
@@ -0,0 +1,499 @@
+//! Rasterization of the Lottie animations into frames.
+//!
+//! Only the subset of the spec commonly used by the stickers is supported:
+//! shape, solid, null and precomposition layers with solid fills and strokes.
+//! The animations that use the rest of the features fail to render, because
+//! they would look different from the original.
+
+mod property;
+mod shape;
+
+use crate::prelude::*;
+use property::{numbers_at, scalar_at};
+use serde_json::Value;
+use shape::ShapeRenderer;
+use std::collections::{BTreeSet, HashMap};
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Rect, Transform};
+
+/// Limits the recursion for the precompositions and the parenting chains,
+/// which may be cyclic in malformed files
+const MAX_DEPTH: usize = 32;
+
+/// Lottie animation ready to be rendered
+pub(crate) struct Animation {
+    lottie: Value,
+    width: f64,
+    height: f64,
+    fps: f64,
+    in_point: f64,
+    out_point: f64,
+}
+
+impl Animation {
+    /// Parses the Lottie JSON, which may be compressed into TGS
+    pub(crate) fn parse(input: &[u8]) -> Result<Self> {
+        let json = super::decompress(input)?;
+
+        let lottie: Value =
+            serde_json::from_slice(&json).context("Failed to parse the Lottie JSON")?;
+
+        let number = |key: &str| {
+            lottie
+                .get(key)
+                .and_then(Value::as_f64)
+                .with_context(|| format!("The Lottie JSON doesn't specify `{key}`"))
+        };
+
+        let (width, height) = (number("w")?, number("h")?);
+        let (fps, in_point, out_point) = (number("fr")?, number("ip")?, number("op")?);
+
+        if width < 1.0 || height < 1.0 {
+            bail!("The size of the animation must be positive, but got {width}x{height}");
+        }
+
+        if fps <= 0.0 {
+            bail!("The frame rate of the animation must be positive, but got {fps}");
+        }
+
+        if out_point <= in_point {
+            bail!(
+                "The out point {out_point} of the animation must be after the in point {in_point}"
+            );
+        }
+
+        Ok(Self {
+            lottie,
+            width,
+            height,
+            fps,
+            in_point,
+            out_point,
+        })
+    }
+
+    pub(crate) fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// Renders the frames of the animation sampled at the `fps` scaled down to
+    /// fit into the `max_side` and passes them to the callback along with their
+    /// indices. The `fps` must not exceed the frame rate of the animation.
+    pub(crate) fn render(
+        &self,
+        max_side: u32,
+        fps: f64,
+        mut on_frame: impl FnMut(usize, &Pixmap) -> Result,
+    ) -> Result {
+        // Number of the animation frames per one rendered frame
+        let step = self.fps / fps.min(self.fps);
+        let frames = ((self.out_point - self.in_point) / step).ceil() as usize;
+
+        let scale = (f64::from(max_side) / self.width.max(self.height)).min(1.0);
+
+        let side = |side: f64| ((side * scale).round() as u32).max(1);
+
+        let mut pixmap = Pixmap::new(side(self.width), side(self.height))
+            .context("Failed to allocate the frame buffer")?;
+
+        let mut renderer = LayerRenderer {
+            assets: self.assets(),
+            unsupported: Unsupported::default(),
+        };
+
+        let layers = layers(&self.lottie);
+        let transform = Transform::from_scale(scale as f32, scale as f32);
+
+        for i in 0..frames {
+            pixmap.fill(Color::TRANSPARENT);
+
+            let frame = self.in_point + i as f64 * step;
+            renderer.render(layers, frame, transform, 1.0, &mut pixmap, 0);
+
+            let unsupported = &renderer.unsupported.0;
+
+            if !unsupported.is_empty() {
+                bail!(
+                    "The animation uses the features that aren't supported by the renderer: {}. \
+                    Convert it into a video with another tool and use the video as the input.",
+                    unsupported.iter().join(", "),
+                );
+            }
+
+            on_frame(i, &pixmap)?;
+        }
+
+        Ok(())
+    }
+
+    /// Precompositions by their IDs
+    fn assets(&self) -> HashMap<&str, &[Value]> {
+        self.lottie
+            .get("assets")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|asset| {
+                let id = asset.get("id")?.as_str()?;
+                Some((id, layers(asset)))
+            })
+            .collect()
+    }
+}
+
+/// Names of the unsupported features found in the animation
+#[derive(Default)]
+pub(super) struct Unsupported(BTreeSet<&'static str>);
+
+impl Unsupported {
+    pub(super) fn insert(&mut self, feature: &'static str) {
+        self.0.insert(feature);
+    }
+}
+
+struct LayerRenderer<'a> {
+    assets: HashMap<&'a str, &'a [Value]>,
+    unsupported: Unsupported,
+}
+
+impl LayerRenderer<'_> {
+    /// Draws the layers of the composition. The first layer is the topmost one.
+    fn render(
+        &mut self,
+        layers: &[Value],
+        frame: f64,
+        transform: Transform,
+        opacity: f32,
+        pixmap: &mut Pixmap,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            self.unsupported.insert("deeply nested precompositions");
+            return;
+        }
+
+        for layer in layers.iter().rev() {
+            if layer.get("hd").and_then(Value::as_bool) == Some(true) || !is_visible(layer, frame) {
+                continue;
+            }
+
+            // Matte sources are invisible by themselves
+            if layer.get("td").and_then(Value::as_u64) == Some(1) {
+                self.unsupported.insert("track mattes");
+                continue;
+            }
+
+            if layer.get("tt").is_some() {
+                self.unsupported.insert("track mattes");
+            }
+
+            if non_empty(layer, "masksProperties") {
+                self.unsupported.insert("masks");
+            }
+
+            if non_empty(layer, "ef") {
+                self.unsupported.insert("effects");
+            }
+
+            let ks = layer.get("ks").unwrap_or(&Value::Null);
+            let (_, layer_opacity) = transform_at(ks, frame);
+
+            let transform = transform.pre_concat(layer_transform(layers, layer, frame, 0));
+            let opacity = opacity * layer_opacity;
+
+            if opacity <= 0.0 {
+                continue;
+            }
+
+            match layer.get("ty").and_then(Value::as_u64) {
+                // Precomposition
+                Some(0) => {
+                    let Some(assets) = layer
+                        .get("refId")
+                        .and_then(Value::as_str)
+                        .and_then(|id| self.assets.get(id))
+                    else {
+                        continue;
+                    };
+
+                    let start = layer.get("st").and_then(Value::as_f64).unwrap_or_default();
+                    let stretch = layer.get("sr").and_then(Value::as_f64).unwrap_or(1.0);
+
+                    let frame = (frame - start) / stretch;
+
+                    self.render(assets, frame, transform, opacity, pixmap, depth + 1);
+                }
+                // Solid
+                Some(1) => solid(layer, transform, opacity, pixmap),
+                Some(2) => self.unsupported.insert("images"),
+                // Null is used only as a parent of other layers
+                Some(3) => {}
+                // Shape
+                Some(4) => {
+                    let shapes = layer
+                        .get("shapes")
+                        .and_then(Value::as_array)
+                        .map_or(&[][..], Vec::as_slice);
+
+                    ShapeRenderer {
+                        frame,
+                        pixmap,
+                        unsupported: &mut self.unsupported,
+                    }
+                    .render(shapes, transform, opacity);
+                }
+                Some(5) => self.unsupported.insert("text layers"),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns the transform of the layer combined with the transforms of its
+/// parents. Unlike the transform, the opacity isn't inherited from the parents.
+fn layer_transform(layers: &[Value], layer: &Value, frame: f64, depth: usize) -> Transform {
+    let (transform, _) = transform_at(layer.get("ks").unwrap_or(&Value::Null), frame);
+
+    let Some(parent) = layer.get("parent").and_then(Value::as_f64) else {
+        return transform;
+    };
+
+    let parent = layers
+        .iter()
+        .find(|layer| layer.get("ind").and_then(Value::as_f64) == Some(parent));
+
+    match parent {
+        Some(parent) if depth < MAX_DEPTH => {
+            layer_transform(layers, parent, frame, depth + 1).pre_concat(transform)
+        }
+        _ => transform,
+    }
+}
+
+/// Evaluates the transform and the opacity of the layer or the shape group.
+/// The position may be split into separately animated `x` and `y`.
+pub(super) fn transform_at(transform: &Value, frame: f64) -> (Transform, f32) {
+    let scalar = |key: &str| scalar_at(transform.get(key)?, frame);
+
+    let vector = |key: &str, default: f64| {
+        let numbers = transform
+            .get(key)
+            .and_then(|property| numbers_at(property, frame))
+            .unwrap_or_default();
+
+        let coord = |i: usize| numbers.get(i).copied().unwrap_or(default) as f32;
+
+        (coord(0), coord(1))
+    };
+
+    let position = match transform.get("p") {
+        Some(position) if position.get("s").and_then(Value::as_bool) == Some(true) => {
+            let coord = |key: &str| {
+                position
+                    .get(key)
+                    .and_then(|property| scalar_at(property, frame))
+                    .unwrap_or_default() as f32
+            };
+            (coord("x"), coord("y"))
+        }
+        _ => vector("p", 0.0),
+    };
+
+    let anchor = vector("a", 0.0);
+    let scale = vector("s", 100.0);
+
+    // 3D layers store the rotation around the Z axis in `rz`
+    let rotation = scalar("r").or_else(|| scalar("rz")).unwrap_or_default() as f32;
+    let opacity = scalar("o").map_or(1.0, |opacity| opacity / 100.0) as f32;
+
+    let transform = Transform::from_translate(position.0, position.1)
+        .pre_rotate(rotation)
+        .pre_scale(scale.0 / 100.0, scale.1 / 100.0)
+        .pre_translate(-anchor.0, -anchor.1);
+
+    (transform, opacity)
+}
+
+/// Fills the rectangle of the solid layer with its color
+fn solid(layer: &Value, transform: Transform, opacity: f32, pixmap: &mut Pixmap) {
+    let number = |key: &str| layer.get(key).and_then(Value::as_f64).map(|n| n as f32);
+
+    let (Some(width), Some(height)) = (number("sw"), number("sh")) else {
+        return;
+    };
+
+    let Some(color) = layer
+        .get("sc")
+        .and_then(Value::as_str)
+        .and_then(parse_hex_color)
+    else {
+        return;
+    };
+
+    let Some(rect) = Rect::from_xywh(0.0, 0.0, width, height) else {
+        return;
+    };
+
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(
+        color[0],
+        color[1],
+        color[2],
+        (opacity * 255.0).round() as u8,
+    );
+    paint.anti_alias = true;
+
+    pixmap.fill_path(
+        &PathBuilder::from_rect(rect),
+        &paint,
+        tiny_skia::FillRule::Winding,
+        transform,
+        None,
+    );
+}
+
+fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn is_visible(layer: &Value, frame: f64) -> bool {
+    let bound = |key: &str| layer.get(key).and_then(Value::as_f64);
+
+    let before_in = matches!(bound("ip"), Some(in_point) if frame < in_point);
+    let after_out = matches!(bound("op"), Some(out_point) if frame >= out_point);
+
+    !before_in && !after_out
+}
+
+fn layers(composition: &Value) -> &[Value] {
+    composition
+        .get("layers")
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn non_empty(layer: &Value, key: &str) -> bool {
+    matches!(layer.get(key), Some(Value::Array(items)) if !items.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_render() {
+        let lottie = serde_json::json!({
+            "w": 16,
+            "h": 16,
+            "fr": 60,
+            "ip": 0,
+            "op": 2,
+            "layers": [
+                {
+                    "ty": 4,
+                    "ks": {
+                        "p": {
+                            "a": 1,
+                            "k": [
+                                { "t": 0, "s": [4, 8] },
+                                { "t": 1, "s": [12, 8] }
+                            ]
+                        }
+                    },
+                    "shapes": [
+                        {
+                            "ty": "gr",
+                            "it": [
+                                { "ty": "el", "p": { "a": 0, "k": [0, 0] }, "s": { "a": 0, "k": [6, 6] } },
+                                { "ty": "fl", "c": { "a": 0, "k": [1, 0, 0, 1] }, "o": { "a": 0, "k": 100 } },
+                                { "ty": "tr", "o": { "a": 0, "k": 100 } }
+                            ]
+                        }
+                    ]
+                },
+                { "ty": 1, "sw": 16, "sh": 4, "sc": "#0000ff", "ks": { "o": { "a": 0, "k": 50 } } }
+            ]
+        });
+
+        let animation = Animation::parse(lottie.to_string().as_bytes()).unwrap();
+
+        // Downscale to check the scaling as well
+        let mut frames = vec![];
+        animation
+            .render(8, 60.0, |_, pixmap| {
+                let rows = pixmap.pixels().chunks(pixmap.width() as usize).map(|row| {
+                    row.iter()
+                        .map(|pixel| match (pixel.red(), pixel.blue(), pixel.alpha()) {
+                            (_, _, 0) => '.',
+                            (_, blue, _) if blue > 0 => 'b',
+                            (red, ..) if red > 200 => 'R',
+                            _ => 'r',
+                        })
+                        .collect::<String>()
+                });
+                frames.push(rows.collect_vec().join("\n"));
+                Ok(())
+            })
+            .unwrap();
+
+        expect![[r#"
+            bbbbbbbb
+            bbbbbbbb
+            .rr.....
+            rRRr....
+            rRRr....
+            .rr.....
+            ........
+            ........
+
+            bbbbbbbb
+            bbbbbbbb
+            .....rr.
+            ....rRRr
+            ....rRRr
+            .....rr.
+            ........
+            ........"#]]
+        .assert_eq(&frames.join("\n\n"));
+    }
+
+    #[test]
+    fn render_limits() {
+        let lottie = |layer: serde_json::Value| {
+            let lottie = serde_json::json!({
+                "w": 4, "h": 4, "fr": 60, "ip": 0, "op": 5, "layers": [layer]
+            });
+            Animation::parse(lottie.to_string().as_bytes()).unwrap()
+        };
+
+        let solid = serde_json::json!({ "ty": 1, "sw": 4, "sh": 4, "sc": "#ff0000", "ks": {} });
+
+        let mut frames = 0;
+        lottie(solid)
+            .render(4, 30.0, |_, _| {
+                frames += 1;
+                Ok(())
+            })
+            .unwrap();
+
+        // Every second frame of the 60 FPS animation is rendered at 30 FPS
+        assert_eq!(frames, 3);
+
+        let masked = serde_json::json!({
+            "ty": 1, "sw": 4, "sh": 4, "sc": "#ff0000", "ks": {},
+            "masksProperties": [{ "mode": "a" }]
+        });
+
+        let err = lottie(masked).render(4, 30.0, |_, _| Ok(())).unwrap_err();
+
+        expect!["The animation uses the features that aren't supported by the renderer: masks. Convert it into a video with another tool and use the video as the input."].assert_eq(&err.to_string());
+    }
+}
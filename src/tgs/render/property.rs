@@ -0,0 +1,217 @@
+//! Evaluation of the animatable Lottie properties at the given frame
+
+use serde_json::Value;
+
+/// Returns the value of the property at the given frame. The property is
+/// either static `{"a": 0, "k": value}` or animated `{"a": 1, "k": [keyframes]}`.
+pub(super) fn value_at(property: &Value, frame: f64) -> Option<Value> {
+    let value = property.get("k")?;
+
+    let Some(keyframes) = keyframes(value) else {
+        return Some(value.clone());
+    };
+
+    let (first, last) = (keyframes.first()?, keyframes.last()?);
+
+    if frame <= time(first) {
+        return start_value(first).cloned();
+    }
+
+    let Some(i) = keyframes
+        .windows(2)
+        .position(|pair| frame >= time(&pair[0]) && frame < time(&pair[1]))
+    else {
+        // After the last keyframe the value stays the same. Legacy files
+        // store the final value in the `e` of the penultimate keyframe.
+        return start_value(last)
+            .or_else(|| keyframes.iter().rev().find_map(|kf| kf.get("e")))
+            .cloned();
+    };
+
+    let (current, next) = (&keyframes[i], &keyframes[i + 1]);
+
+    let from = start_value(current)?;
+
+    if current.get("h").and_then(Value::as_f64) == Some(1.0) {
+        return Some(from.clone());
+    }
+
+    let to = current.get("e").or_else(|| start_value(next))?;
+
+    let progress = (frame - time(current)) / (time(next) - time(current));
+    let eased = ease(current, progress);
+
+    Some(lerp(from, to, eased))
+}
+
+/// Same as [`value_at`], but returns the property as numbers
+pub(super) fn numbers_at(property: &Value, frame: f64) -> Option<Vec<f64>> {
+    match value_at(property, frame)? {
+        Value::Number(number) => Some(vec![number.as_f64()?]),
+        Value::Array(items) => items.iter().map(Value::as_f64).collect(),
+        _ => None,
+    }
+}
+
+/// Same as [`value_at`], but returns the first number of the property
+pub(super) fn scalar_at(property: &Value, frame: f64) -> Option<f64> {
+    numbers_at(property, frame)?.first().copied()
+}
+
+fn keyframes(value: &Value) -> Option<&Vec<Value>> {
+    let items = value.as_array()?;
+    items
+        .first()
+        .filter(|first| first.get("t").is_some())
+        .map(|_| items)
+}
+
+fn time(keyframe: &Value) -> f64 {
+    keyframe
+        .get("t")
+        .and_then(Value::as_f64)
+        .unwrap_or_default()
+}
+
+/// The values of the keyframes are wrapped into arrays even for scalars
+/// and shapes, e.g. `"s": [100]` or `"s": [{"v": [...], ...}]`
+fn start_value(keyframe: &Value) -> Option<&Value> {
+    let value = keyframe.get("s")?;
+    match value.as_array() {
+        Some(items) if items.len() == 1 && !items[0].is_number() => items.first(),
+        _ => Some(value),
+    }
+}
+
+/// Applies the cubic bezier easing defined by the out tangent `o` of the
+/// current keyframe and the in tangent `i` of the next one
+fn ease(keyframe: &Value, progress: f64) -> f64 {
+    let tangent = |key: &str, axis: &str| {
+        let value = keyframe.get(key)?.get(axis)?;
+        value
+            .as_f64()
+            .or_else(|| value.as_array()?.first()?.as_f64())
+    };
+
+    let (Some(x1), Some(y1), Some(x2), Some(y2)) = (
+        tangent("o", "x"),
+        tangent("o", "y"),
+        tangent("i", "x"),
+        tangent("i", "y"),
+    ) else {
+        return progress;
+    };
+
+    cubic_bezier(x1, y1, x2, y2, progress)
+}
+
+/// Evaluates the CSS-like easing curve from `(0, 0)` to `(1, 1)` with the
+/// given control points at `x`
+fn cubic_bezier(x1: f64, y1: f64, x2: f64, y2: f64, x: f64) -> f64 {
+    let bezier = |p1: f64, p2: f64, t: f64| {
+        let u = 1.0 - t;
+        3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+    };
+
+    // The curve is monotonic by `x`, so the bisection always converges
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..32 {
+        let mid = (low + high) / 2.0;
+        if bezier(x1, x2, mid) < x {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    bezier(y1, y2, (low + high) / 2.0)
+}
+
+/// Interpolates the numbers inside of the values of the same structure
+fn lerp(from: &Value, to: &Value, t: f64) -> Value {
+    match (from, to) {
+        (Value::Number(from), Value::Number(to)) => {
+            let (Some(from), Some(to)) = (from.as_f64(), to.as_f64()) else {
+                return Value::Null;
+            };
+            serde_json::json!(from + (to - from) * t)
+        }
+        (Value::Array(from), Value::Array(to)) => Value::Array(
+            from.iter()
+                .zip(to)
+                .map(|(from, to)| lerp(from, to, t))
+                .collect(),
+        ),
+        (Value::Object(from), Value::Object(to)) => Value::Object(
+            from.iter()
+                .map(|(key, from)| {
+                    let value = to
+                        .get(key)
+                        .map_or_else(|| from.clone(), |to| lerp(from, to, t));
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        (from, _) => from.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_value_at() {
+        let property = serde_json::json!({
+            "a": 1,
+            "k": [
+                { "t": 0, "s": [0, 100], "o": { "x": [0.5], "y": [0] }, "i": { "x": [0.5], "y": [1] } },
+                { "t": 10, "s": [100, 0], "h": 1 },
+                { "t": 20, "s": [50, 50] }
+            ]
+        });
+
+        let values = [-5.0, 0.0, 2.5, 5.0, 15.0, 25.0]
+            .map(|frame| numbers_at(&property, frame).unwrap())
+            .map(|numbers| {
+                numbers
+                    .iter()
+                    .map(|n| format!("{n:.1}"))
+                    .collect::<Vec<_>>()
+            });
+
+        expect![[r#"
+            [
+                [
+                    "0.0",
+                    "100.0",
+                ],
+                [
+                    "0.0",
+                    "100.0",
+                ],
+                [
+                    "10.6",
+                    "89.4",
+                ],
+                [
+                    "50.0",
+                    "50.0",
+                ],
+                [
+                    "100.0",
+                    "0.0",
+                ],
+                [
+                    "50.0",
+                    "50.0",
+                ],
+            ]
+        "#]]
+        .assert_debug_eq(&values);
+
+        let property = serde_json::json!({ "a": 0, "k": 42 });
+        assert_eq!(scalar_at(&property, 100.0), Some(42.0));
+    }
+}
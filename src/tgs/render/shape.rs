@@ -0,0 +1,330 @@
+//! Rendering of the contents of the shape layers
+
+use super::property::{numbers_at, scalar_at, value_at};
+use super::{transform_at, Unsupported};
+use serde_json::Value;
+use tiny_skia::{
+    Color, FillRule, LineCap, LineJoin, Paint, Path, PathBuilder, Pixmap, Rect, Stroke, Transform,
+};
+
+/// Magic constant for approximating a quarter of a circle with a cubic bezier
+const KAPPA: f32 = 0.552_284_8;
+
+pub(super) struct ShapeRenderer<'a> {
+    pub(super) frame: f64,
+    pub(super) pixmap: &'a mut Pixmap,
+    pub(super) unsupported: &'a mut Unsupported,
+}
+
+impl ShapeRenderer<'_> {
+    /// Draws the shape items of the group. Styles apply to all the geometry
+    /// that precedes them in the group, including the nested groups. Items
+    /// at the start of the list are drawn on top of the ones after them.
+    pub(super) fn render(&mut self, items: &[Value], transform: Transform, opacity: f32) {
+        for (i, item) in items.iter().enumerate().rev() {
+            if is_hidden(item) {
+                continue;
+            }
+
+            match item_type(item) {
+                "gr" => {
+                    let Some((group, group_transform, group_opacity)) = self.group(item) else {
+                        continue;
+                    };
+                    self.render(
+                        group,
+                        transform.pre_concat(group_transform),
+                        opacity * group_opacity,
+                    );
+                }
+                "fl" => {
+                    let Some(path) = self.geometry(&items[..i], Transform::identity()) else {
+                        continue;
+                    };
+                    self.fill(&path, item, transform, opacity);
+                }
+                "st" => {
+                    let Some(path) = self.geometry(&items[..i], Transform::identity()) else {
+                        continue;
+                    };
+                    self.stroke(&path, item, transform, opacity);
+                }
+                "gf" | "gs" => self.unsupported.insert("gradients"),
+                "tm" => self.unsupported.insert("trim paths"),
+                "rp" => self.unsupported.insert("repeaters"),
+                "rd" => self.unsupported.insert("rounded corners"),
+                "mm" => self.unsupported.insert("merge paths"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the items of the group along with its transform and opacity.
+    /// The transform of the group is stored as the last item of the group.
+    fn group<'v>(&self, group: &'v Value) -> Option<(&'v [Value], Transform, f32)> {
+        let items = group.get("it")?.as_array()?;
+
+        let Some(last) = items.last().filter(|item| item_type(item) == "tr") else {
+            return Some((items, Transform::identity(), 1.0));
+        };
+
+        let (transform, opacity) = transform_at(last, self.frame);
+
+        Some((&items[..items.len() - 1], transform, opacity))
+    }
+
+    /// Collects all the paths of the items into a single path
+    fn geometry(&mut self, items: &[Value], transform: Transform) -> Option<Path> {
+        let mut builder = PathBuilder::new();
+        self.collect_geometry(items, transform, &mut builder);
+        builder.finish()
+    }
+
+    fn collect_geometry(
+        &mut self,
+        items: &[Value],
+        transform: Transform,
+        builder: &mut PathBuilder,
+    ) {
+        for item in items.iter().filter(|item| !is_hidden(item)) {
+            let path = match item_type(item) {
+                "gr" => {
+                    let Some((group, group_transform, _)) = self.group(item) else {
+                        continue;
+                    };
+                    self.collect_geometry(group, transform.pre_concat(group_transform), builder);
+                    continue;
+                }
+                "rc" => rect(item, self.frame),
+                "el" => ellipse(item, self.frame),
+                "sh" => bezier(item, self.frame),
+                "sr" => star(item, self.frame),
+                _ => continue,
+            };
+
+            if let Some(path) = path.and_then(|path| path.transform(transform)) {
+                builder.push_path(&path);
+            }
+        }
+    }
+
+    fn fill(&mut self, path: &Path, style: &Value, transform: Transform, opacity: f32) {
+        let Some(paint) = paint(style, self.frame, opacity) else {
+            return;
+        };
+
+        let fill_rule = match style.get("r").and_then(Value::as_u64) {
+            Some(2) => FillRule::EvenOdd,
+            _ => FillRule::Winding,
+        };
+
+        self.pixmap
+            .fill_path(path, &paint, fill_rule, transform, None);
+    }
+
+    fn stroke(&mut self, path: &Path, style: &Value, transform: Transform, opacity: f32) {
+        let Some(paint) = paint(style, self.frame, opacity) else {
+            return;
+        };
+
+        let width = property(style, "w", self.frame).unwrap_or(1.0) as f32;
+
+        if width <= 0.0 {
+            return;
+        }
+
+        let line_cap = match style.get("lc").and_then(Value::as_u64) {
+            Some(2) => LineCap::Round,
+            Some(3) => LineCap::Square,
+            _ => LineCap::Butt,
+        };
+
+        let line_join = match style.get("lj").and_then(Value::as_u64) {
+            Some(2) => LineJoin::Round,
+            Some(3) => LineJoin::Bevel,
+            _ => LineJoin::Miter,
+        };
+
+        let stroke = Stroke {
+            width,
+            miter_limit: style.get("ml").and_then(Value::as_f64).unwrap_or(4.0) as f32,
+            line_cap,
+            line_join,
+            dash: None,
+        };
+
+        if matches!(style.get("d"), Some(Value::Array(dashes)) if !dashes.is_empty()) {
+            self.unsupported.insert("dashed strokes");
+        }
+
+        self.pixmap
+            .stroke_path(path, &paint, &stroke, transform, None);
+    }
+}
+
+fn paint(style: &Value, frame: f64, opacity: f32) -> Option<Paint<'static>> {
+    let color = numbers_at(style.get("c")?, frame)?;
+    let style_opacity = property(style, "o", frame).unwrap_or(100.0) / 100.0;
+
+    let channel = |i: usize| color.get(i).copied().unwrap_or(1.0).clamp(0.0, 1.0) as f32;
+    let alpha = channel(3) * (style_opacity as f32 * opacity).clamp(0.0, 1.0);
+
+    let mut paint = Paint::default();
+    paint.set_color(Color::from_rgba(channel(0), channel(1), channel(2), alpha)?);
+    paint.anti_alias = true;
+
+    Some(paint)
+}
+
+fn rect(shape: &Value, frame: f64) -> Option<Path> {
+    let (x, y) = point(shape, "p", frame)?;
+    let (width, height) = point(shape, "s", frame)?;
+    let (left, top) = (x - width / 2.0, y - height / 2.0);
+
+    let roundness = property(shape, "r", frame)
+        .unwrap_or_default()
+        .min(width.min(height) as f64 / 2.0) as f32;
+
+    if roundness <= 0.0 {
+        return Some(PathBuilder::from_rect(Rect::from_xywh(
+            left, top, width, height,
+        )?));
+    }
+
+    let (right, bottom) = (left + width, top + height);
+    let (r, k) = (roundness, roundness * (1.0 - KAPPA));
+
+    let mut builder = PathBuilder::new();
+    builder.move_to(left + r, top);
+    builder.line_to(right - r, top);
+    builder.cubic_to(right - k, top, right, top + k, right, top + r);
+    builder.line_to(right, bottom - r);
+    builder.cubic_to(right, bottom - k, right - k, bottom, right - r, bottom);
+    builder.line_to(left + r, bottom);
+    builder.cubic_to(left + k, bottom, left, bottom - k, left, bottom - r);
+    builder.line_to(left, top + r);
+    builder.cubic_to(left, top + k, left + k, top, left + r, top);
+    builder.close();
+    builder.finish()
+}
+
+fn ellipse(shape: &Value, frame: f64) -> Option<Path> {
+    let (x, y) = point(shape, "p", frame)?;
+    let (width, height) = point(shape, "s", frame)?;
+
+    PathBuilder::from_oval(Rect::from_xywh(
+        x - width / 2.0,
+        y - height / 2.0,
+        width,
+        height,
+    )?)
+}
+
+/// Builds the path from the vertices `v` and the in/out tangents `i`/`o`,
+/// which are relative to their vertices
+fn bezier(shape: &Value, frame: f64) -> Option<Path> {
+    let path = value_at(shape.get("ks")?, frame)?;
+
+    let points = |key: &str| -> Option<Vec<(f32, f32)>> {
+        path.get(key)?
+            .as_array()?
+            .iter()
+            .map(|point| {
+                let point = point.as_array()?;
+                Some((
+                    point.first()?.as_f64()? as f32,
+                    point.get(1)?.as_f64()? as f32,
+                ))
+            })
+            .collect()
+    };
+
+    let (vertices, in_tangents, out_tangents) = (points("v")?, points("i")?, points("o")?);
+
+    let closed = path.get("c").and_then(Value::as_bool).unwrap_or_default();
+
+    let &(x, y) = vertices.first()?;
+
+    let mut builder = PathBuilder::new();
+    builder.move_to(x, y);
+
+    let segment = |builder: &mut PathBuilder, from: usize, to: usize| {
+        let ((x1, y1), (dx1, dy1)) = (vertices[from], out_tangents.get(from)?);
+        let ((x2, y2), (dx2, dy2)) = (vertices[to], in_tangents.get(to)?);
+        builder.cubic_to(x1 + dx1, y1 + dy1, x2 + dx2, y2 + dy2, x2, y2);
+        Some(())
+    };
+
+    for to in 1..vertices.len() {
+        segment(&mut builder, to - 1, to)?;
+    }
+
+    if closed {
+        segment(&mut builder, vertices.len() - 1, 0)?;
+        builder.close();
+    }
+
+    builder.finish()
+}
+
+/// Builds the polystar with straight edges. Star type `sy` is 1 for stars
+/// and 2 for polygons, which don't have the inner vertices.
+fn star(shape: &Value, frame: f64) -> Option<Path> {
+    let (x, y) = point(shape, "p", frame)?;
+    let points = property(shape, "pt", frame)?.round() as usize;
+    let outer = property(shape, "or", frame)?;
+    let rotation = property(shape, "r", frame).unwrap_or_default().to_radians();
+
+    let inner = match shape.get("sy").and_then(Value::as_u64) {
+        Some(1) => Some(property(shape, "ir", frame)?),
+        _ => None,
+    };
+
+    if points < 3 {
+        return None;
+    }
+
+    let vertices = (0..points).flat_map(|i| {
+        let angle = rotation + std::f64::consts::TAU * i as f64 / points as f64;
+        let half_step = std::f64::consts::PI / points as f64;
+
+        let outer = Some((outer, angle));
+        let inner = inner.map(|inner| (inner, angle + half_step));
+
+        outer.into_iter().chain(inner)
+    });
+
+    let mut builder = PathBuilder::new();
+
+    for (i, (radius, angle)) in vertices.enumerate() {
+        // The first vertex points upwards
+        let vx = x + (radius * angle.sin()) as f32;
+        let vy = y - (radius * angle.cos()) as f32;
+
+        if i == 0 {
+            builder.move_to(vx, vy);
+        } else {
+            builder.line_to(vx, vy);
+        }
+    }
+
+    builder.close();
+    builder.finish()
+}
+
+fn point(item: &Value, key: &str, frame: f64) -> Option<(f32, f32)> {
+    let numbers = numbers_at(item.get(key)?, frame)?;
+    Some((*numbers.first()? as f32, *numbers.get(1)? as f32))
+}
+
+fn property(item: &Value, key: &str, frame: f64) -> Option<f64> {
+    scalar_at(item.get(key)?, frame)
+}
+
+fn item_type(item: &Value) -> &str {
+    item.get("ty").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn is_hidden(item: &Value) -> bool {
+    item.get("hd").and_then(Value::as_bool) == Some(true)
+}
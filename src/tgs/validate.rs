@@ -0,0 +1,184 @@
+use super::{TGS_DURATION, TGS_FPS, TGS_SIZE};
+use serde_json::Value;
+use std::fmt;
+
+/// Violation of a Telegram constraint at the given location in the JSON
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Violation {
+    /// [JSON pointer](https://www.rfc-editor.org/rfc/rfc6901) to the value
+    pub(crate) path: String,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {}", self.message)
+    }
+}
+
+/// Layer type of the image layers according to the Lottie spec
+const IMAGE_LAYER_TYPE: u64 = 2;
+
+/// Checks the Lottie animation against the requirements for the animated
+/// stickers described in <https://core.telegram.org/stickers#animated-stickers-and-emoji>
+pub(crate) fn validate(lottie: &Value) -> Vec<Violation> {
+    let mut violations = vec![];
+
+    let mut violation = |path: &str, message: String| {
+        violations.push(Violation {
+            path: path.to_owned(),
+            message,
+        })
+    };
+
+    for (key, expected) in [("w", TGS_SIZE), ("h", TGS_SIZE)] {
+        match lottie.get(key).and_then(Value::as_f64) {
+            Some(actual) if actual == expected as f64 => {}
+            actual => violation(
+                &format!("/{key}"),
+                format!(
+                    "Expected the size of {expected} px, but got {}",
+                    display(actual)
+                ),
+            ),
+        }
+    }
+
+    let fps = lottie.get("fr").and_then(Value::as_f64);
+
+    match fps {
+        Some(fps) if fps == TGS_FPS as f64 => {}
+        fps => violation(
+            "/fr",
+            format!(
+                "Expected the frame rate of {TGS_FPS} fps, but got {}",
+                display(fps)
+            ),
+        ),
+    }
+
+    let in_point = lottie.get("ip").and_then(Value::as_f64);
+    let out_point = lottie.get("op").and_then(Value::as_f64);
+
+    match (in_point, out_point, fps) {
+        (Some(in_point), Some(out_point), Some(fps)) if fps > 0.0 => {
+            let duration = (out_point - in_point) / fps;
+            if duration > TGS_DURATION.as_secs_f64() {
+                violation(
+                    "/op",
+                    format!(
+                        "The animation must not be longer than {TGS_DURATION:?}, \
+                        but it lasts {duration:.2}s ({} frames)",
+                        out_point - in_point,
+                    ),
+                );
+            }
+        }
+        (None, ..) => violation("/ip", "The in point is missing".to_owned()),
+        (_, None, _) => violation("/op", "The out point is missing".to_owned()),
+        _ => {}
+    }
+
+    let mut path = vec![];
+    walk(lottie, &mut path, &mut violation);
+
+    violations
+}
+
+/// Looks for the images and expressions in the whole tree
+fn walk(value: &Value, path: &mut Vec<String>, violation: &mut impl FnMut(&str, String)) {
+    match value {
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(i.to_string());
+                walk(item, path, violation);
+                path.pop();
+            }
+        }
+        Value::Object(object) => {
+            let in_layers = matches!(path.iter().rev().nth(1), Some(key) if key == "layers");
+            let in_assets = matches!(path.iter().rev().nth(1), Some(key) if key == "assets");
+
+            if in_layers && object.get("ty").and_then(Value::as_u64) == Some(IMAGE_LAYER_TYPE) {
+                violation(&pointer(path), "Image layers are not allowed".to_owned());
+            }
+
+            // Image assets have a path to the file or an embedded data URL,
+            // while the precomposition assets have layers instead
+            if in_assets && object.contains_key("p") && !object.contains_key("layers") {
+                violation(&pointer(path), "Image assets are not allowed".to_owned());
+            }
+
+            for (key, value) in object {
+                path.push(key.clone());
+
+                // Animated properties store the expression in `x` as a string
+                if key == "x" && value.is_string() {
+                    violation(&pointer(path), "Expressions are not allowed".to_owned());
+                }
+
+                walk(value, path, violation);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Formats the path segments as a JSON pointer
+fn pointer(path: &[String]) -> String {
+    path.iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn display(value: Option<f64>) -> String {
+    value.map_or_else(|| "nothing".to_owned(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_validate() {
+        let lottie = serde_json::json!({
+            "w": 512,
+            "h": 256,
+            "fr": 30,
+            "ip": 0,
+            "op": 120,
+            "assets": [
+                { "id": "image_0", "w": 10, "h": 10, "p": "data:image/png;base64,AAAA" },
+                { "id": "comp_0", "layers": [] }
+            ],
+            "layers": [
+                {
+                    "ty": 4,
+                    "ks": {
+                        "o": { "a": 0, "k": 100, "x": "var $bm_rt = 50;" }
+                    }
+                },
+                { "ty": 2, "refId": "image_0" }
+            ]
+        });
+
+        let violations = validate(&lottie).iter().join("\n");
+
+        expect![[r#"
+            /h: Expected the size of 512 px, but got 256
+            /fr: Expected the frame rate of 60 fps, but got 30
+            /op: The animation must not be longer than 3s, but it lasts 4.00s (120 frames)
+            /assets/0: Image assets are not allowed
+            /layers/0/ks/o/x: Expressions are not allowed
+            /layers/1: Image layers are not allowed"#]]
+        .assert_eq(&violations);
+    }
+}
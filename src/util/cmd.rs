@@ -14,8 +14,6 @@ const DEFAULT_FF_OPTIONS: &[&str] = &["-loglevel", "error"];
 /// will be printed using multiline format.
 const LONG_CMD_THRESHOLD: usize = 100;
 
-// FIXME: this may be useful?
-#[allow(unused)]
 pub(crate) async fn get_media_duration(path: &Utf8Path) -> Result<Duration> {
     let args = [
         "-show_entries",
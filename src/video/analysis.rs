@@ -0,0 +1,128 @@
+//! Helpers for analysing the input media with ffmpeg filters before
+//! generating the output.
+
+use crate::ffmpeg::{escape_filter_value, Ffmpeg};
+use crate::prelude::*;
+use crate::util::iter;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Metadata attached by the ffmpeg filters to a single frame
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FrameMetadata {
+    /// Presentation time of the frame in seconds
+    pub(crate) pts_time: f64,
+    pub(crate) entries: BTreeMap<String, String>,
+}
+
+/// Runs the given analysis `filter` over the input and returns the metadata
+/// of every frame that passes through it. The `begin` and `end` bounds are
+/// applied as input options, so the timestamps are relative to `begin`.
+pub(crate) async fn frame_metadata(
+    ffmpeg: &dyn Ffmpeg,
+    input: &Utf8Path,
+    begin: Option<Duration>,
+    end: Option<Duration>,
+    filter: &str,
+) -> Result<Vec<FrameMetadata>> {
+    let bound = |name: &'static str, bound: Option<Duration>| {
+        bound
+            .into_iter()
+            .flat_map(move |bound| [name.to_owned(), bound.to_secs_f64().to_string()])
+    };
+
+    let print = format!("metadata=mode=print:file={}", escape_filter_value("pipe:1"));
+
+    let args = bound("-ss", begin)
+        .chain(bound("-to", end))
+        .chain(iter::strs(["-i", input.as_str(), "-an", "-filter:v"]))
+        .chain([format!("{filter},{print}")])
+        .chain(iter::strs(["-f", "null", "-"]))
+        .collect();
+
+    let output = ffmpeg.run(args).await?;
+
+    parse_frame_metadata(&String::from_utf8_lossy(&output))
+}
+
+/// Parses the output of the `metadata=mode=print` filter, that looks like this:
+///
+/// ```text
+/// frame:0    pts:0       pts_time:0
+/// lavfi.scene_score=0.012
+/// frame:1    pts:1024    pts_time:0.04
+/// lavfi.scene_score=0.521
+/// ```
+fn parse_frame_metadata(output: &str) -> Result<Vec<FrameMetadata>> {
+    let mut frames: Vec<FrameMetadata> = vec![];
+
+    for line in output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if line.starts_with("frame:") {
+            let pts_time = line
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("pts_time:"))
+                .with_context(|| format!("No `pts_time` in the frame metadata: `{line}`"))?
+                .parse()
+                .with_context(|| format!("Invalid `pts_time` in the frame metadata: `{line}`"))?;
+
+            frames.push(FrameMetadata {
+                pts_time,
+                entries: Default::default(),
+            });
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("Unexpected line in the frame metadata: `{line}`"))?;
+
+        let frame = frames
+            .last_mut()
+            .with_context(|| format!("Frame metadata entry goes before any frame: `{line}`"))?;
+
+        frame.entries.insert(key.to_owned(), value.to_owned());
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_parse_frame_metadata() {
+        let output = "\
+            frame:0    pts:0       pts_time:0\n\
+            lavfi.scene_score=0.012\n\
+            frame:12   pts:12288   pts_time:0.48\n\
+            lavfi.scene_score=0.521\n\
+            lavfi.freezedetect.freeze_start=0.4\n";
+
+        let actual = parse_frame_metadata(output).unwrap();
+
+        expect![[r#"
+            [
+                FrameMetadata {
+                    pts_time: 0.0,
+                    entries: {
+                        "lavfi.scene_score": "0.012",
+                    },
+                },
+                FrameMetadata {
+                    pts_time: 0.48,
+                    entries: {
+                        "lavfi.freezedetect.freeze_start": "0.4",
+                        "lavfi.scene_score": "0.521",
+                    },
+                },
+            ]
+        "#]]
+        .assert_debug_eq(&actual);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::prelude::*;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+const STILL_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "tif", "tiff", "webp"];
+
+const DEFAULT_STILL_DURATION: Duration = Duration::from_secs(2);
+const DEFAULT_STILL_FPS: u32 = 30;
+
+/// Parametric animations that turn a static image into a video
+#[derive(
+    serde::Deserialize,
+    serde::Serialize,
+    strum::Display,
+    clap::ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum AnimationPreset {
+    /// Jump up and fall back down
+    Bounce,
+    /// Grow and shrink periodically
+    Pulse,
+    /// Make a full turn around the center
+    Spin,
+    /// Move quickly from side to side
+    Shake,
+    /// Tilt from side to side
+    Wobble,
+    /// Gradually appear from full transparency
+    FadeIn,
+}
+
+/// Options that define how the still images are converted into a video
+#[derive(Debug, Clone)]
+pub(crate) struct StillOptions {
+    pub(crate) animation: Option<AnimationPreset>,
+    pub(crate) duration: Duration,
+    pub(crate) fps: NonZeroU32,
+}
+
+impl Default for StillOptions {
+    fn default() -> Self {
+        Self {
+            animation: None,
+            duration: DEFAULT_STILL_DURATION,
+            fps: NonZeroU32::new(DEFAULT_STILL_FPS).unwrap(),
+        }
+    }
+}
+
+impl StillOptions {
+    /// Input options that make ffmpeg read the still image as a video stream
+    pub(crate) fn input_args(&self) -> Vec<String> {
+        vec![
+            "-loop".to_owned(),
+            "1".to_owned(),
+            "-framerate".to_owned(),
+            self.fps.to_string(),
+            "-t".to_owned(),
+            self.duration.to_secs_f64().to_string(),
+        ]
+    }
+
+    /// Returns the filtergraph for the animation preset if it was requested
+    pub(crate) fn filter(&self) -> Option<String> {
+        self.animation
+            .map(|preset| preset.filter(self.duration.to_secs_f64()))
+    }
+}
+
+impl AnimationPreset {
+    /// Generates the filtergraph, where `period` is the duration of a single
+    /// animation cycle in seconds. All presets end in the same state they
+    /// start with, so the animation loops seamlessly.
+    fn filter(self, period: f64) -> String {
+        // Add transparent margins around the image for it to have space to move
+        let canvas = "format=rgba,\
+            pad=ceil(iw*1.25/2)*2:ceil(ih*1.25/2)*2:(ow-iw)/2:(oh-ih)/2:color=0x00000000";
+
+        // Overlays the moving image over a transparent copy of itself
+        let overlay = |fg: &str, position: &str| {
+            format!(
+                "{canvas},split[bg][fg];\
+                [bg]colorchannelmixer=aa=0[bg];\
+                [fg]{fg}[fg];\
+                [bg][fg]overlay={position}"
+            )
+        };
+
+        match self {
+            Self::Bounce => overlay("null", &format!("x=0:y=-H*0.1*abs(sin(PI*t/{period}))")),
+            Self::Shake => overlay("null", &format!("x=W*0.04*sin(12*PI*t/{period}):y=0")),
+            Self::Pulse => overlay(
+                &format!("scale=w=iw*(0.9+0.1*cos(2*PI*t/{period})):h=-1:eval=frame"),
+                "x=(W-w)/2:y=(H-h)/2",
+            ),
+            Self::Spin => {
+                format!("{canvas},rotate=a=2*PI*t/{period}:ow=hypot(iw\\,ih):oh=ow:c=none")
+            }
+            Self::Wobble => format!("{canvas},rotate=a=0.15*sin(2*PI*t/{period}):c=none"),
+            Self::FadeIn => format!("format=rgba,fade=t=in:st=0:d={}:alpha=1", period / 2.0),
+        }
+    }
+}
+
+/// Checks if the input is a still image judging by its extension
+pub(crate) fn is_still_image(path: &Utf8Path) -> bool {
+    let Some(ext) = path.extension() else {
+        return false;
+    };
+
+    STILL_IMAGE_EXTENSIONS
+        .iter()
+        .any(|still| ext.eq_ignore_ascii_case(still))
+}
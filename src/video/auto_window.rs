@@ -0,0 +1,196 @@
+use super::analysis::{self, FrameMetadata};
+use super::split::Segment;
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use std::time::Duration;
+
+/// Defines how "interesting" every frame of the input is
+#[derive(strum::Display, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum AutoWindowMetric {
+    /// Average difference between the consecutive frames
+    #[default]
+    Motion,
+
+    /// Scene change score detected by ffmpeg
+    Scene,
+
+    /// Average difference between the alpha channels of the consecutive
+    /// frames. Useful for the inputs with transparent background.
+    Alpha,
+}
+
+impl AutoWindowMetric {
+    /// Filter that attaches the score to the metadata of every frame
+    fn filter(self) -> &'static str {
+        match self {
+            Self::Motion => "tblend=all_mode=difference,signalstats",
+            Self::Scene => "select=gte(scene\\,0)",
+            Self::Alpha => "alphaextract,tblend=all_mode=difference,signalstats",
+        }
+    }
+
+    fn metadata_key(self) -> &'static str {
+        match self {
+            Self::Motion | Self::Alpha => "lavfi.signalstats.YAVG",
+            Self::Scene => "lavfi.scene_score",
+        }
+    }
+
+    /// Finds the window of the given duration with the highest total score
+    /// inside of the `begin` and `end` bounds. Returns `None` if the input
+    /// already fits into the window.
+    pub(crate) async fn find_window(
+        self,
+        ffmpeg: &dyn Ffmpeg,
+        input: &Utf8Path,
+        begin: Option<Duration>,
+        end: Option<Duration>,
+        window: Duration,
+    ) -> Result<Option<Segment>> {
+        let available = ffmpeg.probe_duration(input).await?;
+        let start = begin.unwrap_or_default();
+        let end = end.map_or(available, |end| end.min(available));
+        let duration = end.saturating_sub(start);
+
+        if duration <= window {
+            debug!("The input is {duration:?} long, so it already fits into the {window:?} window");
+            return Ok(None);
+        }
+
+        let frames =
+            analysis::frame_metadata(ffmpeg, input, begin, Some(end), self.filter()).await?;
+
+        let scores: Vec<_> = frames.iter().map(|frame| self.score(frame)).try_collect()?;
+
+        let Some(best) = best_window(&scores, window, duration) else {
+            bail!("No frames were analysed in the input {input}");
+        };
+
+        // The timestamps of the frames are relative to the `begin`
+        let segment = Segment {
+            begin: start + best.segment.begin,
+            end: start + best.segment.end,
+        };
+
+        let total: f64 = scores.iter().map(|(_, score)| score).sum();
+        let average = total * window.as_secs_f64() / duration.as_secs_f64();
+
+        info!(
+            "🎯 Picked the window {} with the highest {self} score {} \
+            (the average score of a window of the same duration is {})",
+            display::bold(&segment),
+            display::bold(&format_args!("{:.3}", best.score)),
+            display::bold(&format_args!("{average:.3}")),
+        );
+
+        Ok(Some(segment))
+    }
+
+    fn score(self, frame: &FrameMetadata) -> Result<(f64, f64)> {
+        let key = self.metadata_key();
+        let score = frame
+            .entries
+            .get(key)
+            .with_context(|| format!("No `{key}` in the metadata of the frame {frame:?}"))?
+            .parse()
+            .with_context(|| format!("Invalid `{key}` in the metadata of the frame {frame:?}"))?;
+
+        Ok((frame.pts_time, score))
+    }
+}
+
+#[derive(Debug)]
+struct ScoredWindow {
+    segment: Segment,
+    score: f64,
+}
+
+/// Finds the window that starts at one of the frames and has the highest sum
+/// of the scores of the frames inside of it. The `scores` are the pairs of
+/// the frame timestamp and its score sorted by timestamp.
+fn best_window(scores: &[(f64, f64)], window: Duration, end: Duration) -> Option<ScoredWindow> {
+    let window = window.as_secs_f64();
+    let end = end.as_secs_f64();
+
+    let mut best: Option<ScoredWindow> = None;
+    let mut sum = 0.0;
+    let mut right = 0;
+
+    for (left, &(begin, _)) in scores.iter().enumerate() {
+        // The window must not go beyond the end of the input
+        if begin + window > end {
+            break;
+        }
+
+        while right < scores.len() && scores[right].0 < begin + window {
+            sum += scores[right].1;
+            right += 1;
+        }
+
+        let is_better = match &best {
+            Some(best) => sum > best.score,
+            None => true,
+        };
+
+        if is_better {
+            best = Some(ScoredWindow {
+                segment: Segment {
+                    begin: Duration::from_secs_f64(begin),
+                    end: Duration::from_secs_f64(begin + window),
+                },
+                score: sum,
+            });
+        }
+
+        sum -= scores[left].1;
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::testing::SharedMockFfmpeg;
+    use crate::video::PackKind;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_find_window() {
+        // The mock media is 10 seconds long
+        let output = (0..100)
+            .map(|i| {
+                let time = f64::from(i) * 0.1;
+                // The most motion is between 5.5 and 8.5 seconds
+                let score = if (55..85).contains(&i) { 10.0 } else { 1.0 };
+                format!("frame:{i} pts:{i} pts_time:{time}\nlavfi.signalstats.YAVG={score}\n")
+            })
+            .join("");
+
+        let ffmpeg =
+            SharedMockFfmpeg::with_best_crf(0, PackKind::Emoji).with_analysis_output(&output);
+
+        let window = AutoWindowMetric::Motion
+            .find_window(&*ffmpeg, "input".into(), None, None, Duration::from_secs(3))
+            .await
+            .unwrap()
+            .unwrap();
+
+        expect!["5.50s..8.50s"].assert_eq(&window.to_string());
+
+        let window = AutoWindowMetric::Motion
+            .find_window(
+                &*ffmpeg,
+                "input".into(),
+                Some(Duration::from_secs(7)),
+                Some(Duration::from_secs(9)),
+                Duration::from_secs(3),
+            )
+            .await
+            .unwrap();
+
+        assert!(window.is_none());
+    }
+}
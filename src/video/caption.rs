@@ -0,0 +1,152 @@
+use crate::ffmpeg::escape_filter_value;
+use crate::prelude::*;
+use std::time::Duration;
+
+/// The default font size is defined relative to the bounding box of the
+/// pack kind, so that the caption stays readable after downscaling.
+const DEFAULT_SIZE_RATIO: f64 = 1.0 / 6.0;
+
+/// Distance between the caption and the edge of the frame relative to the
+/// font size
+const MARGIN_RATIO: f64 = 0.25;
+
+#[derive(strum::Display, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum CaptionPosition {
+    Top,
+    Center,
+    #[default]
+    Bottom,
+}
+
+/// Defines how the caption text is rendered on top of the video
+#[derive(Debug, Clone)]
+pub(crate) struct CaptionStyle {
+    pub(crate) position: CaptionPosition,
+    pub(crate) font_file: Option<Utf8PathBuf>,
+
+    /// Font size in pixels of the final output
+    pub(crate) size: Option<u32>,
+    pub(crate) color: String,
+
+    pub(crate) stroke_width: u32,
+    pub(crate) stroke_color: String,
+
+    /// If specified, the words of the caption are revealed one by one
+    /// with this interval
+    pub(crate) word_interval: Option<Duration>,
+}
+
+impl Default for CaptionStyle {
+    fn default() -> Self {
+        Self {
+            position: Default::default(),
+            font_file: None,
+            size: None,
+            color: "white".to_owned(),
+            stroke_width: 2,
+            stroke_color: "black".to_owned(),
+            word_interval: None,
+        }
+    }
+}
+
+impl CaptionStyle {
+    /// Returns the `drawtext` filters that render the caption. It is meant to
+    /// be applied after the video is scaled to fit into the `bounding_box`.
+    /// Returns `None` if the caption has no visible text.
+    pub(crate) fn filter(&self, text: &str, bounding_box: u64) -> Option<String> {
+        if text.trim().is_empty() {
+            return None;
+        }
+
+        let size = self
+            .size
+            .map(f64::from)
+            .unwrap_or_else(|| (bounding_box as f64 * DEFAULT_SIZE_RATIO).round());
+
+        let margin = (size * MARGIN_RATIO).round();
+
+        let y = match self.position {
+            CaptionPosition::Top => format!("{margin}"),
+            CaptionPosition::Center => "(h-text_h)/2".to_owned(),
+            CaptionPosition::Bottom => format!("h-text_h-{margin}"),
+        };
+
+        let font_file = self
+            .font_file
+            .iter()
+            .map(|path| format!(":fontfile={}", escape_filter_value(path.as_str())))
+            .join("");
+
+        let style = format!(
+            "fontsize={size}:fontcolor={}:borderw={}:bordercolor={}\
+            :x=(w-text_w)/2:y={y}:expansion=none{font_file}",
+            escape_filter_value(&self.color),
+            self.stroke_width,
+            escape_filter_value(&self.stroke_color),
+        );
+
+        let Some(interval) = self.word_interval else {
+            return Some(format!(
+                "drawtext=text={}:{style}",
+                escape_filter_value(text)
+            ));
+        };
+
+        // Render a separate `drawtext` for every prefix of the caption where
+        // each next prefix has one more word
+        let words = text.split_whitespace().collect_vec();
+        let interval = interval.to_secs_f64();
+
+        (1..=words.len())
+            .map(|len| {
+                let text = escape_filter_value(&words[..len].join(" "));
+                let start = interval * (len - 1) as f64;
+                let enable = if len == words.len() {
+                    format!("gte(t\\,{start})")
+                } else {
+                    format!("between(t\\,{start}\\,{})", start + interval)
+                };
+                format!("drawtext=text={text}:{style}:enable={enable}")
+            })
+            .join(",")
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_word_interval() {
+        let style = CaptionStyle {
+            word_interval: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+
+        let actual = style
+            .filter("Oh, it's: fine", 100)
+            .unwrap()
+            .replace(",drawtext", "\n,drawtext");
+
+        expect![[r#"
+            drawtext=text=Oh\,:fontsize=17:fontcolor=white:borderw=2:bordercolor=black:x=(w-text_w)/2:y=h-text_h-4:expansion=none:enable=between(t\,0\,0.5)
+            ,drawtext=text=Oh\, it\\\'s\\::fontsize=17:fontcolor=white:borderw=2:bordercolor=black:x=(w-text_w)/2:y=h-text_h-4:expansion=none:enable=between(t\,0.5\,1)
+            ,drawtext=text=Oh\, it\\\'s\\: fine:fontsize=17:fontcolor=white:borderw=2:bordercolor=black:x=(w-text_w)/2:y=h-text_h-4:expansion=none:enable=gte(t\,1)"#]]
+        .assert_eq(&actual);
+    }
+
+    #[test]
+    fn blank_caption() {
+        let style = CaptionStyle {
+            word_interval: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+
+        assert_eq!(style.filter(" \t\n", 100), None);
+        assert_eq!(CaptionStyle::default().filter("  ", 100), None);
+    }
+}
@@ -0,0 +1,516 @@
+use super::animation::{self, StillOptions};
+use super::single_gen::{SingleVideoGenContext, SingleVideoGenOptions};
+use super::{PackKind, MAX_DURATION};
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::util::path::Utf8StemmedPathBuf;
+use buildstructor::buildstructor;
+use futures::prelude::*;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// All parts are converted to the same frame rate before concatenation
+const CONCAT_FPS: u32 = 30;
+
+const DEFAULT_NAME: &str = "concat";
+
+const DEFAULT_TRANSITION_DURATION: Duration = Duration::from_millis(250);
+
+/// A single input of the concatenation
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConcatPart {
+    pub(crate) path: Utf8PathBuf,
+
+    /// Max duration of the part. Still images are shown during the
+    /// `--still-duration` by default, and videos are taken as a whole.
+    pub(crate) duration: Option<Duration>,
+}
+
+impl ConcatPart {
+    /// Parses the value in format `path[@duration]`. The path itself may
+    /// contain `@`, e.g. `me@2x.png`, so the suffix after the last `@` is
+    /// treated as the duration only if it's a valid one.
+    pub(crate) fn parse(arg: &str) -> Result<Self> {
+        let duration = arg.rsplit_once('@').and_then(|(path, duration)| {
+            let duration = crate::util::duration::parse(duration).ok()?;
+            Some((path, duration))
+        });
+
+        let part = match duration {
+            Some((path, duration)) => Self {
+                path: path.into(),
+                duration: Some(duration),
+            },
+            None => Self {
+                path: arg.into(),
+                duration: None,
+            },
+        };
+
+        if part.path.as_str().is_empty() {
+            bail!("The path of the part must not be empty, but got `{arg}`");
+        }
+
+        Ok(part)
+    }
+
+    fn is_still_image(&self) -> bool {
+        animation::is_still_image(&self.path)
+    }
+}
+
+/// Transition between the consecutive parts implemented via `xfade` filter
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Transition {
+    /// Name of the `xfade` transition, e.g. `fade`, `wipeleft`, `slideup`.
+    /// See the full list in <https://trac.ffmpeg.org/wiki/Xfade>
+    pub(crate) name: String,
+    pub(crate) duration: Duration,
+}
+
+impl Transition {
+    /// Parses the value in format `name[:duration]`
+    pub(crate) fn parse(arg: &str) -> Result<Self> {
+        let (name, duration) = match arg.split_once(':') {
+            Some((name, duration)) => (name, crate::util::duration::parse(duration)?),
+            None => (arg, DEFAULT_TRANSITION_DURATION),
+        };
+
+        if name.is_empty() || !name.chars().all(|char| char.is_ascii_alphanumeric()) {
+            bail!("Invalid transition name `{name}`");
+        }
+
+        if duration.is_zero() {
+            bail!("Transition duration must be greater than zero");
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            duration,
+        })
+    }
+}
+
+/// Several inputs concatenated into a single output
+#[derive(Debug)]
+pub(crate) struct Concat {
+    pub(crate) parts: Vec<ConcatPart>,
+    pub(crate) transition: Option<Transition>,
+}
+
+impl Concat {
+    /// Returns the input options for all the parts and the filtergraph that
+    /// normalizes their resolution and frame rate and concatenates them.
+    pub(crate) async fn inputs(
+        &self,
+        ffmpeg: &dyn Ffmpeg,
+        still: &StillOptions,
+        max_side: u64,
+    ) -> Result<(Vec<String>, String)> {
+        let mut args = vec![];
+        let mut durations = vec![];
+
+        for part in &self.parts {
+            let duration = match (part.duration, part.is_still_image()) {
+                (Some(duration), _) => duration,
+                (None, true) => still.duration,
+                (None, false) => ffmpeg.probe_duration(&part.path).await?,
+            };
+
+            if part.is_still_image() {
+                args.extend(["-loop".to_owned(), "1".to_owned()]);
+            }
+
+            args.extend([
+                "-t".to_owned(),
+                duration.to_secs_f64().to_string(),
+                "-i".to_owned(),
+                part.path.to_string(),
+            ]);
+
+            durations.push(duration);
+        }
+
+        let total = self.total_duration(&durations);
+
+        if total > MAX_DURATION {
+            warn!(
+                "The total duration of the concatenated parts is {total:.2?}, \
+                which exceeds the limit of {MAX_DURATION:?}. Telegram may reject \
+                such an emoji/sticker."
+            );
+        }
+
+        // All parts must have the same resolution, aspect ratio, frame rate and
+        // pixel format for concatenation
+        let normalized = (0..self.parts.len()).map(|i| {
+            format!(
+                "[{i}:v]scale={max_side}:{max_side}:force_original_aspect_ratio=decrease,\
+                pad={max_side}:{max_side}:-1:-1:color=0x00000000,\
+                setsar=1,fps={CONCAT_FPS},format=yuva420p,settb=AVTB[v{i}]"
+            )
+        });
+
+        // A single part has nothing to transition to
+        let transition = self.transition.as_ref().filter(|_| self.parts.len() > 1);
+
+        if self.transition.is_some() && transition.is_none() {
+            warn!("The transition is ignored, because there is only one part to concatenate");
+        }
+
+        let joined = match transition {
+            None => {
+                let inputs = (0..self.parts.len()).map(|i| format!("[v{i}]")).join("");
+                format!("{inputs}concat=n={}:v=1:a=0", self.parts.len())
+            }
+            Some(transition) => {
+                let fade = transition.duration.to_secs_f64();
+
+                // The offset of every transition is relative to the beginning
+                // of the output accumulated so far
+                let mut offset = 0.0;
+                let mut previous = "v0".to_owned();
+
+                durations
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .map(|(i, _)| {
+                        offset += durations[i - 1].to_secs_f64() - fade;
+                        let output = if i + 1 == durations.len() {
+                            String::new()
+                        } else {
+                            format!("[x{i}]")
+                        };
+                        let filter = format!(
+                            "[{previous}][v{i}]xfade=transition={}:duration={fade}:offset={offset}{output}",
+                            transition.name,
+                        );
+                        previous = format!("x{i}");
+                        filter
+                    })
+                    .join(";")
+            }
+        };
+
+        let graph = normalized.chain([joined]).join(";");
+
+        Ok((args, graph))
+    }
+
+    fn total_duration(&self, durations: &[Duration]) -> Duration {
+        let total: Duration = durations.iter().sum();
+        let transitions = self
+            .transition
+            .as_ref()
+            .map_or(Duration::ZERO, |transition| {
+                transition.duration * (durations.len().saturating_sub(1) as u32)
+            });
+        total.saturating_sub(transitions)
+    }
+}
+
+/// Concatenates several inputs into a single emoji/sticker
+pub(crate) struct ConcatGenContext {
+    pack_kinds: Vec<PackKind>,
+
+    parts: Vec<ConcatPart>,
+    spec_file: Option<Utf8PathBuf>,
+    transition: Option<Transition>,
+
+    output: Option<Utf8PathBuf>,
+    name: String,
+
+    concurrency: NonZeroUsize,
+
+    overwrite: bool,
+    options: Arc<SingleVideoGenOptions>,
+}
+
+#[buildstructor]
+impl ConcatGenContext {
+    #[builder]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        pack_kinds: Vec<PackKind>,
+
+        parts: Vec<ConcatPart>,
+        spec_file: Option<Utf8PathBuf>,
+        transition: Option<Transition>,
+
+        output: Option<Utf8PathBuf>,
+        name: Option<String>,
+
+        still_duration: Option<Duration>,
+
+        filter: Option<String>,
+        ffmpeg_args: Vec<String>,
+        ffmpeg: Option<Arc<dyn Ffmpeg>>,
+
+        concurrency: Option<NonZeroUsize>,
+        overwrite: bool,
+        publisher: Option<String>,
+    ) -> Result<Self> {
+        if pack_kinds.is_empty() {
+            bail!("No pack kinds were specified");
+        }
+
+        if parts.is_empty() == spec_file.is_none() {
+            bail!("Either the parts or the spec file must be specified, but not both");
+        }
+
+        let default_still = StillOptions::default();
+
+        let still = StillOptions {
+            duration: still_duration.unwrap_or(default_still.duration),
+            ..default_still
+        };
+
+        let options = SingleVideoGenOptions {
+            begin: None,
+            end: None,
+            loop_mode: None,
+            still,
+            filter,
+            ffmpeg_args,
+            ffmpeg: ffmpeg.unwrap_or_else(|| Arc::new(crate::ffmpeg::FfmpegProcess)),
+            caption_style: Default::default(),
+            watermark: None,
+            repaint: None,
+            playback: Default::default(),
+            static_format: Default::default(),
+            publisher,
+        };
+
+        Ok(Self {
+            pack_kinds,
+            parts,
+            spec_file,
+            transition,
+            output,
+            name: name.unwrap_or_else(|| DEFAULT_NAME.to_owned()),
+            concurrency: concurrency
+                .unwrap_or_else(|| super::MultiVideoGenContext::default_concurrency("")),
+            overwrite,
+            options: Arc::new(options),
+        })
+    }
+}
+
+impl ConcatGenContext {
+    /// Reads the parts from the spec file, where every line is a part in
+    /// format `path[@duration]`. Empty lines and lines starting with `#` are
+    /// ignored. Relative paths are resolved against the spec file directory.
+    async fn read_spec_file(spec_file: &Utf8Path) -> Result<Vec<ConcatPart>> {
+        let base_dir = spec_file.parent().unwrap_or(Utf8Path::new(""));
+
+        fs::read_to_string(spec_file)
+            .await?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let mut part = ConcatPart::parse(line)?;
+                part.path = base_dir.join(part.path);
+                Ok(part)
+            })
+            .collect()
+    }
+
+    pub(crate) async fn run(self) -> Result {
+        let parts = match &self.spec_file {
+            Some(spec_file) => Self::read_spec_file(spec_file).await?,
+            None => self.parts.clone(),
+        };
+
+        let Some(first) = parts.first() else {
+            bail!("There are no parts to concatenate");
+        };
+
+        if parts.len() < 2 {
+            warn!("There is only one part, so there is nothing to concatenate with");
+        }
+
+        let input = Utf8StemmedPathBuf::try_from(first.path.clone())?;
+
+        let output_dir = match &self.output {
+            Some(output) => output.clone(),
+            None => first.path.parent().unwrap_or(Utf8Path::new("")).to_owned(),
+        };
+
+        let concat = Arc::new(Concat {
+            parts,
+            transition: self.transition.clone(),
+        });
+
+        let contexts = self
+            .pack_kinds
+            .iter()
+            .map(|&pack_kind| SingleVideoGenContext {
+                options: self.options.clone(),
+                pack_kind,
+                input: input.clone(),
+                output: output_dir.join(format!(
+                    "{}-{pack_kind}.{}",
+                    self.name,
+                    self.options.extension(pack_kind)
+                )),
+                caption: None,
+                tile: None,
+                segment: None,
+                concat: Some(concat.clone()),
+                lottie: None,
+            })
+            .collect_vec();
+
+        crate::fs::validate_output_files_overwriting(
+            self.overwrite,
+            contexts.iter().map(|ctx| ctx.output.clone()),
+        )
+        .await?;
+
+        let start = std::time::Instant::now();
+
+        stream::iter(contexts)
+            .enumerate()
+            .map(|(id, context)| {
+                context
+                    .generate_file()
+                    .instrument(info_span!("task", id = id + 1))
+            })
+            .buffer_unordered(self.concurrency.get())
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        let elapsed = display::elpased(start);
+        info!("Finished in {}", elapsed);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+    use crate::video::testing::SharedMockFfmpeg;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_parse_part() {
+        let parts = [
+            "clip.mp4",
+            "logo.png@0.5",
+            "a@00:01.5",
+            "clips/me@2x.png",
+            "clips/me@2x.png@1",
+        ]
+        .map(|arg| ConcatPart::parse(arg).unwrap());
+
+        expect![[r#"
+            [
+                ConcatPart {
+                    path: "clip.mp4",
+                    duration: None,
+                },
+                ConcatPart {
+                    path: "logo.png",
+                    duration: Some(
+                        500ms,
+                    ),
+                },
+                ConcatPart {
+                    path: "a",
+                    duration: Some(
+                        1.5s,
+                    ),
+                },
+                ConcatPart {
+                    path: "clips/me@2x.png",
+                    duration: None,
+                },
+                ConcatPart {
+                    path: "clips/me@2x.png",
+                    duration: Some(
+                        1s,
+                    ),
+                },
+            ]
+        "#]]
+        .assert_debug_eq(&parts);
+
+        let err = ConcatPart::parse("@0.5").unwrap_err();
+
+        expect!["The path of the part must not be empty, but got `@0.5`"]
+            .assert_eq(&format!("{err:#}"));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn single_part_with_transition() {
+        let concat = Concat {
+            parts: vec![ConcatPart::parse("logo.png@0.5").unwrap()],
+            transition: Some(Transition::parse("fade").unwrap()),
+        };
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_crf(25, PackKind::Emoji);
+
+        let (_, graph) = concat
+            .inputs(&*mock_ffmpeg, &StillOptions::default(), 100)
+            .await
+            .unwrap();
+
+        expect![[r#"
+            [0:v]scale=100:100:force_original_aspect_ratio=decrease,pad=100:100:-1:-1:color=0x00000000,setsar=1,fps=30,format=yuva420p,settb=AVTB[v0];
+            [v0]concat=n=1:v=1:a=0"#]].assert_eq(&graph.replace(';', ";\n"));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_concat_with_transition() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let spec_file = dir.join("spec.txt");
+        fs::write(
+            &spec_file,
+            "# Reaction\nfirst.png@0.5\n\nsecond.mp4@1\nthird.png\n",
+        )
+        .await
+        .unwrap();
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_crf(25, PackKind::Emoji);
+
+        ConcatGenContext::builder()
+            .pack_kind(PackKind::Emoji)
+            .spec_file(spec_file)
+            .transition(Transition::parse("wipeleft:0.25").unwrap())
+            .output(dir.to_owned())
+            .name("reaction")
+            .overwrite(false)
+            .ffmpeg(mock_ffmpeg.clone())
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+
+        assert!(dir.join("reaction-emoji.webm").exists());
+
+        let mut args = mock_ffmpeg.unwrap().args_log.into_iter().next().unwrap();
+
+        for arg in &mut args {
+            *arg = arg.replace(dir.as_str(), "{dir}");
+        }
+
+        let args = args
+            .iter()
+            .take_while(|arg| *arg != "-passlogfile")
+            .join("\n")
+            .replace(';', ";\n");
+
+        testing::expect_file("ffmpeg_calls/smoke_concat_with_transition.txt")
+            .await
+            .assert_eq(&args);
+    }
+}
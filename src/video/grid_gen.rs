@@ -0,0 +1,298 @@
+use super::animation::StillOptions;
+use super::single_gen::{SingleVideoGenContext, SingleVideoGenOptions};
+use super::{LoopMode, PackKind};
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::util::path::Utf8StemmedPathBuf;
+use buildstructor::buildstructor;
+use futures::prelude::*;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Custom emoji are always square, and the grid is made of them
+const GRID_PACK_KIND: PackKind = PackKind::Emoji;
+
+/// A single cell of the grid that the input's frame is split into
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GridTile {
+    pub(crate) col: u32,
+    pub(crate) row: u32,
+    pub(crate) cols: u32,
+    pub(crate) rows: u32,
+}
+
+impl GridTile {
+    /// The frame is padded to the aspect ratio of the grid first, so that
+    /// every tile is square and no additional padding is added to the tiles
+    /// individually, which would break the continuity of the mosaic.
+    pub(crate) fn filter(&self) -> String {
+        let Self {
+            col,
+            row,
+            cols,
+            rows,
+        } = self;
+
+        format!(
+            "pad=\
+            ceil(max(iw\\,ih*{cols}/{rows})):\
+            ceil(max(ih\\,iw*{rows}/{cols})):\
+            (ow-iw)/2:(oh-ih)/2:color=0x00000000,\
+            crop=iw/{cols}:ih/{rows}:iw/{cols}*{col}:ih/{rows}*{row}"
+        )
+    }
+}
+
+/// Splits the input video into a grid of custom emoji, that form a single
+/// big animation when they are typed in rows.
+pub(crate) struct GridGenContext {
+    input: Utf8PathBuf,
+    output: Option<Utf8PathBuf>,
+
+    cols: NonZeroU32,
+    rows: NonZeroU32,
+
+    concurrency: NonZeroUsize,
+
+    overwrite: bool,
+    options: Arc<SingleVideoGenOptions>,
+}
+
+#[buildstructor]
+impl GridGenContext {
+    #[builder]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        input: Utf8PathBuf,
+        output: Option<Utf8PathBuf>,
+
+        cols: NonZeroU32,
+        rows: NonZeroU32,
+
+        begin: Option<Duration>,
+        end: Option<Duration>,
+
+        loop_mode: Option<LoopMode>,
+
+        filter: Option<String>,
+        ffmpeg_args: Vec<String>,
+        ffmpeg: Option<Arc<dyn Ffmpeg>>,
+
+        concurrency: Option<NonZeroUsize>,
+        overwrite: bool,
+        publisher: Option<String>,
+    ) -> Self {
+        let options = SingleVideoGenOptions {
+            begin,
+            end,
+            loop_mode,
+            still: StillOptions::default(),
+            filter,
+            ffmpeg_args,
+            ffmpeg: ffmpeg.unwrap_or_else(|| Arc::new(crate::ffmpeg::FfmpegProcess)),
+            caption_style: Default::default(),
+            watermark: None,
+            repaint: None,
+            playback: Default::default(),
+            static_format: Default::default(),
+            publisher,
+        };
+
+        Self {
+            input,
+            output,
+            cols,
+            rows,
+            concurrency: concurrency
+                .unwrap_or_else(|| super::MultiVideoGenContext::default_concurrency("")),
+            overwrite,
+            options: Arc::new(options),
+        }
+    }
+}
+
+impl GridGenContext {
+    pub(crate) async fn run(self) -> Result {
+        let input = Utf8StemmedPathBuf::try_from(self.input.clone())?;
+
+        let out_dir = self.out_dir()?;
+        let stem = input.file_stem();
+
+        let contexts = self
+            .tiles()
+            .enumerate()
+            .map(|(i, tile)| SingleVideoGenContext {
+                options: self.options.clone(),
+                pack_kind: GRID_PACK_KIND,
+                input: input.clone(),
+                output: out_dir.join(format!("{stem}-{}-{GRID_PACK_KIND}.webm", i + 1)),
+                caption: None,
+                tile: Some(tile),
+                segment: None,
+                concat: None,
+                lottie: None,
+            })
+            .collect_vec();
+
+        let ordering_file = out_dir.join(format!("{stem}-grid.txt"));
+
+        crate::fs::validate_output_files_overwriting(
+            self.overwrite,
+            contexts
+                .iter()
+                .map(|ctx| ctx.output.clone())
+                .chain([ordering_file.clone()]),
+        )
+        .await?;
+
+        let start = std::time::Instant::now();
+
+        let searched: Vec<_> = stream::iter(contexts.clone())
+            .enumerate()
+            .map(|(id, context)| {
+                context
+                    .search_crf()
+                    .instrument(info_span!("tile", id = id + 1))
+            })
+            .buffered(self.concurrency.get())
+            .try_collect()
+            .await?;
+
+        // The highest CRF fits all tiles, because the size of the output
+        // decreases with the CRF growth. It's applied to all tiles to make
+        // sure there are no visible quality jumps between them.
+        let crf = searched
+            .iter()
+            .map(|(crf, _)| *crf)
+            .max()
+            .unwrap_or_default();
+
+        info!(
+            "Using the CRF {} for all tiles to keep the quality consistent",
+            display::bold(&crf)
+        );
+
+        stream::iter(contexts.iter().cloned().zip(searched))
+            .enumerate()
+            .map(|(id, (context, (tile_crf, output)))| {
+                async move {
+                    let path = context.output.clone();
+                    let output = if tile_crf == crf {
+                        output
+                    } else {
+                        context.generate_bytes_with_crf(crf).await?
+                    };
+                    crate::fs::write_output(&path, &output).await
+                }
+                .instrument(info_span!("tile", id = id + 1))
+            })
+            .buffer_unordered(self.concurrency.get())
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        fs::write(&ordering_file, self.ordering(&contexts)).await?;
+
+        info!(
+            "📋 Saved the upload order of the tiles at {}",
+            display::bold(&ordering_file)
+        );
+
+        let elapsed = display::elpased(start);
+        info!("Finished in {}", elapsed);
+
+        Ok(())
+    }
+
+    /// Returns the tiles in the row-by-row order
+    fn tiles(&self) -> impl Iterator<Item = GridTile> {
+        let (cols, rows) = (self.cols.get(), self.rows.get());
+        (0..rows)
+            .cartesian_product(0..cols)
+            .map(move |(row, col)| GridTile {
+                col,
+                row,
+                cols,
+                rows,
+            })
+    }
+
+    /// Renders the list of the output files in the order they need to be
+    /// uploaded to the pack to form the grid
+    fn ordering(&self, contexts: &[SingleVideoGenContext]) -> String {
+        let rows = contexts
+            .chunks(self.cols.get() as usize)
+            .enumerate()
+            .format_with("\n", |(row, contexts), f| {
+                let files = contexts
+                    .iter()
+                    .filter_map(|ctx| ctx.output.file_name())
+                    .format("\n");
+
+                f(&format_args!("# Row {}\n{files}", row + 1))
+            });
+
+        format!(
+            "# Upload the files to the emoji pack in this order to form \
+            a {}x{} grid\n{rows}\n",
+            self.cols, self.rows
+        )
+    }
+
+    fn out_dir(&self) -> Result<Utf8PathBuf> {
+        if let Some(output) = &self.output {
+            return Ok(output.clone());
+        }
+
+        self.input
+            .parent()
+            .map(ToOwned::to_owned)
+            .with_context(|| format!("There is no parent directory for the input {}", self.input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+    use crate::video::testing::SharedMockFfmpeg;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_grid() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+        let input = dir.join("clip.mp4");
+
+        fs::write(&input, "hello").await.unwrap();
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_crf(25, GRID_PACK_KIND);
+
+        GridGenContext::builder()
+            .input(input)
+            .cols(NonZeroU32::new(3).unwrap())
+            .rows(NonZeroU32::new(2).unwrap())
+            .overwrite(false)
+            .ffmpeg(mock_ffmpeg.clone())
+            .build()
+            .run()
+            .await
+            .unwrap();
+
+        let ordering = fs::read_to_string(dir.join("clip-grid.txt")).await.unwrap();
+
+        testing::expect_file("grid/smoke_grid_ordering.txt")
+            .await
+            .assert_eq(&ordering);
+
+        let args_log = mock_ffmpeg.unwrap().args_log;
+
+        let last_tile_filter = args_log
+            .iter()
+            .rev()
+            .flat_map(|args| args.iter().skip_while(|arg| *arg != "-filter:v").nth(1))
+            .find(|filter| filter.contains("crop=iw/3:ih/2:iw/3*2:ih/2*1"));
+
+        assert!(last_tile_filter.is_some(), "{args_log:#?}");
+    }
+}
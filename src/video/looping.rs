@@ -0,0 +1,152 @@
+use super::MAX_DURATION;
+use crate::prelude::*;
+use std::time::Duration;
+
+const DEFAULT_CROSSFADE_DURATION: Duration = Duration::from_millis(500);
+
+/// Defines how the end of the video is joined with its beginning to make
+/// the infinite playback loop on Telegram look seamless.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LoopMode {
+    /// Blend the tail of the segment into its head during the given duration.
+    /// The output becomes shorter than the source segment by that duration.
+    Crossfade { duration: Duration },
+
+    /// Play the segment forward and then backward.
+    /// The output becomes twice as long as the source segment.
+    Pingpong,
+}
+
+impl LoopMode {
+    /// Parses the value in format `crossfade[:duration]` or `pingpong`
+    pub(crate) fn parse(arg: &str) -> Result<Self> {
+        let (mode, param) = match arg.split_once(':') {
+            Some((mode, param)) => (mode, Some(param)),
+            None => (arg, None),
+        };
+
+        match (mode, param) {
+            ("crossfade", param) => {
+                let duration = param
+                    .map(crate::util::duration::parse)
+                    .transpose()?
+                    .unwrap_or(DEFAULT_CROSSFADE_DURATION);
+
+                if duration.is_zero() {
+                    bail!("Crossfade duration must be greater than zero");
+                }
+
+                Ok(Self::Crossfade { duration })
+            }
+            ("pingpong", None) => Ok(Self::Pingpong),
+            ("pingpong", Some(_)) => bail!("`pingpong` loop mode doesn't accept parameters"),
+            _ => bail!("Unknown loop mode `{mode}`, expected `crossfade[:duration]` or `pingpong`"),
+        }
+    }
+
+    /// Max duration of the source segment such that the looped output
+    /// still fits into [`MAX_DURATION`]
+    pub(crate) fn source_budget(&self) -> Duration {
+        match self {
+            Self::Crossfade { duration } => MAX_DURATION + *duration,
+            Self::Pingpong => MAX_DURATION / 2,
+        }
+    }
+
+    /// Duration of the looped output for the given duration of the source segment
+    pub(crate) fn output_duration(&self, segment: Duration) -> Duration {
+        match self {
+            Self::Crossfade { duration } => segment.saturating_sub(*duration),
+            Self::Pingpong => segment * 2,
+        }
+    }
+
+    /// Whether the filter needs to know the exact duration of the source segment
+    pub(crate) fn needs_segment_duration(&self) -> bool {
+        matches!(self, Self::Crossfade { .. })
+    }
+
+    /// Returns the filtergraph that implements the loop. The `segment` is the
+    /// duration of the input for the filter. It's required only if
+    /// [`Self::needs_segment_duration`] returns `true`.
+    pub(crate) fn filter(&self, segment: Option<Duration>) -> Result<String> {
+        match self {
+            Self::Crossfade { duration } => {
+                let segment = segment.context("BUG: segment duration is required for crossfade")?;
+
+                if segment <= *duration * 2 {
+                    bail!(
+                        "The video segment ({segment:?}) must be longer than two \
+                        crossfade durations ({duration:?})"
+                    );
+                }
+
+                let fade = duration.to_secs_f64();
+                let offset = (segment - *duration * 2).to_secs_f64();
+
+                // The body starts right after the head, and its last `fade`
+                // seconds are blended with the head. This way the last frame of
+                // the output matches the first one.
+                Ok(format!(
+                    "split[body][head];\
+                    [body]trim=start={fade},setpts=PTS-STARTPTS[body];\
+                    [head]trim=end={fade},setpts=PTS-STARTPTS[head];\
+                    [body][head]xfade=transition=fade:duration={fade}:offset={offset}"
+                ))
+            }
+            Self::Pingpong => Ok("split[fwd][bwd];\
+                [bwd]reverse[bwd];\
+                [fwd][bwd]concat=n=2:v=1:a=0"
+                .to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::{expect, Expect};
+
+    fn assert_parse(arg: &str, expected: Expect) {
+        let actual = LoopMode::parse(arg)
+            .map(|mode| format!("{mode:?}"))
+            .unwrap_or_else(|err| format!("Error: {err:?}"));
+
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn smoke_parse() {
+        assert_parse("pingpong", expect!["Pingpong"]);
+        assert_parse("crossfade", expect!["Crossfade { duration: 500ms }"]);
+        assert_parse("crossfade:1.5", expect!["Crossfade { duration: 1.5s }"]);
+        assert_parse("crossfade:00:01", expect!["Crossfade { duration: 1s }"]);
+    }
+
+    #[test]
+    fn error_parse() {
+        assert_parse(
+            "crossfade:0",
+            expect!["Error: Crossfade duration must be greater than zero"],
+        );
+        assert_parse(
+            "pingpong:1",
+            expect!["Error: `pingpong` loop mode doesn't accept parameters"],
+        );
+        assert_parse(
+            "bounce",
+            expect![
+                "Error: Unknown loop mode `bounce`, expected `crossfade[:duration]` or `pingpong`"
+            ],
+        );
+    }
+
+    #[test]
+    fn crossfade_segment_too_short() {
+        let mode = LoopMode::Crossfade {
+            duration: Duration::from_secs(1),
+        };
+        mode.filter(Some(Duration::from_secs(2))).unwrap_err();
+        mode.filter(Some(Duration::from_secs_f64(2.5))).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+//! Lottie animations can't be decoded by ffmpeg, so they are rendered into
+//! a lossless intermediate video with alpha that goes through the pipeline
+//! instead of the original input
+
+use super::MAX_FPS;
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::tgs::render::Animation;
+use crate::util::iter;
+
+const LOTTIE_EXTENSIONS: &[&str] = &["tgs", "json"];
+
+/// Checks if the input is a Lottie animation judging by its extension
+pub(crate) fn is_lottie(path: &Utf8Path) -> bool {
+    let Some(ext) = path.extension() else {
+        return false;
+    };
+
+    LOTTIE_EXTENSIONS
+        .iter()
+        .any(|lottie| ext.eq_ignore_ascii_case(lottie))
+}
+
+/// Intermediate video rendered from the Lottie animation. It lives in a
+/// temporary directory, which is removed when this value is dropped.
+#[derive(Debug)]
+pub(crate) struct RenderedLottie {
+    pub(crate) path: Utf8PathBuf,
+    _temp_dir: tempfile::TempDir,
+}
+
+impl RenderedLottie {
+    /// Renders the frames of the animation scaled down to fit into `max_side`
+    /// at no more than [`MAX_FPS`] and encodes them with the lossless FFV1 codec
+    pub(crate) async fn render(
+        ffmpeg: &dyn Ffmpeg,
+        input: &Utf8Path,
+        max_side: u32,
+    ) -> Result<Self> {
+        let start = std::time::Instant::now();
+
+        let bytes = fs::read(input).await?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path().unwrap_utf8().to_owned();
+
+        let frames_dir = dir.clone();
+
+        let fps = tokio::task::spawn_blocking(move || {
+            let animation = Animation::parse(&bytes)?;
+
+            // Telegram rejects the videos with higher frame rate, and Lottie
+            // animations are usually 60 FPS
+            let fps = animation.fps().min(MAX_FPS);
+
+            animation.render(max_side, fps, |i, pixmap| {
+                let frame = frames_dir.join(format!("frame_{i:05}.png"));
+                pixmap
+                    .save_png(&frame)
+                    .with_context(|| format!("Failed to save the frame {frame}"))
+            })?;
+
+            anyhow::Ok(fps)
+        })
+        .await?
+        .with_context(|| format!("Failed to render the Lottie animation `{input}`"))?;
+
+        let path = dir.join("lottie.mkv");
+        let frames = dir.join("frame_%05d.png");
+
+        let args = iter::strs(["-y", "-framerate"])
+            .chain([fps.to_string()])
+            .chain(iter::strs(["-i", frames.as_str()]))
+            .chain(iter::strs([
+                "-vcodec",
+                "ffv1",
+                "-pix_fmt",
+                "bgra",
+                path.as_str(),
+            ]))
+            .collect();
+
+        ffmpeg.run(args).await?;
+
+        info!(
+            "🎞️ Rendered the Lottie animation in {}",
+            display::elpased(start)
+        );
+
+        Ok(Self {
+            path,
+            _temp_dir: temp_dir,
+        })
+    }
+}
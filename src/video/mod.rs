@@ -1,28 +1,87 @@
+mod analysis;
+mod animation;
+mod auto_window;
+mod caption;
+mod concat_gen;
+mod grid_gen;
+mod looping;
+mod lottie;
 mod multi_gen;
+mod playback;
+mod repaint;
 mod single_gen;
+mod split;
+mod static_image;
+mod svg;
+mod trim_still;
+mod watermark;
 mod webm_vp9_two_pass;
 
 #[cfg(test)]
-mod testing;
+pub(crate) mod testing;
 
+use crate::prelude::*;
 use crate::util::byte_size::KIB;
+use std::time::Duration;
 
+pub(crate) use animation::AnimationPreset;
+pub(crate) use auto_window::AutoWindowMetric;
+pub(crate) use caption::{CaptionPosition, CaptionStyle};
+pub(crate) use concat_gen::{ConcatGenContext, ConcatPart, Transition};
+pub(crate) use grid_gen::GridGenContext;
+pub(crate) use looping::LoopMode;
 pub(crate) use multi_gen::MultiVideoGenContext;
+pub(crate) use playback::{parse_speed, Fade, Flip, Playback, Rotation};
+pub(crate) use repaint::{MatteSource, Repaint};
+pub(crate) use split::SplitMode;
+pub(crate) use static_image::StaticFormat;
+pub(crate) use watermark::{Watermark, WatermarkContent, WatermarkCorner};
+
+/// Extensions of the animated inputs that ffmpeg decodes directly
+const VIDEO_EXTENSIONS: &[&str] = &["webm", "mp4", "mov", "mkv", "avi", "gif", "m4v"];
 
 const MAX_EMOJI_BYTES: usize = 64 * KIB;
 const MAX_STICKER_BYTES: usize = 256 * KIB;
+const MAX_STATIC_BYTES: usize = 512 * KIB;
+const MAX_THUMBNAIL_BYTES: usize = 32 * KIB;
+
+/// Telegram doesn't accept video emoji or stickers longer than this
+const MAX_DURATION: Duration = Duration::from_secs(3);
+
+/// Max frame rate of the video emoji and stickers allowed by Telegram
+const MAX_FPS: f64 = 30.0;
 
 const EMOJI_BOUNDING_BOX: u64 = 100;
 const STICKER_BOUNDING_BOX: u64 = 512;
+const THUMBNAIL_BOUNDING_BOX: u64 = 100;
 
 /// Max value of CRF according to [the docs](https://trac.ffmpeg.org/wiki/Encode/VP9)
 const MAX_CRF: usize = 63;
 
-#[derive(strum::Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(
+    serde::Deserialize,
+    serde::Serialize,
+    strum::Display,
+    clap::ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "kebab-case")]
 #[strum(serialize_all = "kebab-case")]
 pub(crate) enum PackKind {
     Emoji,
     Sticker,
+    StaticEmoji,
+    StaticSticker,
+
+    /// Icon of the pack shown in the list of packs
+    Thumbnail,
 }
 
 impl PackKind {
@@ -30,21 +89,44 @@ impl PackKind {
         match self {
             Self::Emoji => MAX_EMOJI_BYTES,
             Self::Sticker => MAX_STICKER_BYTES,
+            Self::StaticEmoji | Self::StaticSticker => MAX_STATIC_BYTES,
+            Self::Thumbnail => MAX_THUMBNAIL_BYTES,
         }
     }
 
-    /// Telegram supports rectangle stickers, but not emojis.
+    /// Telegram supports rectangle stickers, but not emojis and thumbnails.
     fn must_be_square(&self) -> bool {
         match self {
-            Self::Emoji => true,
-            Self::Sticker => false,
+            Self::Emoji | Self::StaticEmoji | Self::Thumbnail => true,
+            Self::Sticker | Self::StaticSticker => false,
         }
     }
 
     fn bounding_box(&self) -> u64 {
         match self {
-            Self::Emoji => EMOJI_BOUNDING_BOX,
-            Self::Sticker => STICKER_BOUNDING_BOX,
+            Self::Emoji | Self::StaticEmoji => EMOJI_BOUNDING_BOX,
+            Self::Sticker | Self::StaticSticker => STICKER_BOUNDING_BOX,
+            Self::Thumbnail => THUMBNAIL_BOUNDING_BOX,
         }
     }
+
+    /// Static emoji and stickers are single images instead of videos
+    fn is_static(&self) -> bool {
+        match self {
+            Self::Emoji | Self::Sticker | Self::Thumbnail => false,
+            Self::StaticEmoji | Self::StaticSticker => true,
+        }
+    }
+}
+
+/// Checks if the input can be converted judging by its extension
+pub(crate) fn is_media(path: &Utf8Path) -> bool {
+    let is_video = match path.extension() {
+        Some(ext) => VIDEO_EXTENSIONS
+            .iter()
+            .any(|video| ext.eq_ignore_ascii_case(video)),
+        None => false,
+    };
+
+    is_video || animation::is_still_image(path) || svg::is_svg(path) || lottie::is_lottie(path)
 }
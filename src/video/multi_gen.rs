@@ -1,12 +1,19 @@
+use super::animation::StillOptions;
+use super::lottie::{self, RenderedLottie};
 use super::single_gen::{SingleVideoGenContext, SingleVideoGenOptions};
-use super::PackKind;
+use super::split::Segment;
+use super::trim_still;
+use super::{
+    AnimationPreset, AutoWindowMetric, CaptionStyle, LoopMode, PackKind, Playback, Repaint,
+    SplitMode, StaticFormat, Watermark,
+};
 use crate::display;
 use crate::ffmpeg::Ffmpeg;
 use crate::prelude::*;
 use crate::util::path::Utf8StemmedPathBuf;
 use buildstructor::buildstructor;
 use futures::prelude::*;
-use std::num::NonZeroUsize;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -18,6 +25,22 @@ pub(crate) struct MultiVideoGenContext {
 
     concurrency: NonZeroUsize,
 
+    /// A single caption for all outputs
+    caption: Option<String>,
+
+    /// Path to a file where every line is a separate caption that produces
+    /// a separate output
+    caption_file: Option<Utf8PathBuf>,
+
+    /// Split every input into several outputs
+    split: Option<SplitMode>,
+
+    /// Pick the most interesting window of every input automatically
+    auto_window: Option<AutoWindowMetric>,
+
+    /// Trim the still frames at the head and the tail of every input
+    trim_still: bool,
+
     overwrite: bool,
     options: Arc<SingleVideoGenOptions>,
 }
@@ -25,6 +48,7 @@ pub(crate) struct MultiVideoGenContext {
 #[buildstructor]
 impl MultiVideoGenContext {
     #[builder]
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         pack_kinds: Vec<PackKind>,
 
@@ -34,10 +58,30 @@ impl MultiVideoGenContext {
         begin: Option<Duration>,
         end: Option<Duration>,
 
+        split: Option<SplitMode>,
+        auto_window: Option<AutoWindowMetric>,
+        trim_still: Option<bool>,
+
+        loop_mode: Option<LoopMode>,
+
+        animation: Option<AnimationPreset>,
+        still_duration: Option<Duration>,
+        still_fps: Option<NonZeroU32>,
+
         filter: Option<String>,
         ffmpeg_args: Vec<String>,
         ffmpeg: Option<Arc<dyn Ffmpeg>>,
 
+        caption: Option<String>,
+        caption_file: Option<Utf8PathBuf>,
+        caption_style: Option<CaptionStyle>,
+
+        watermark: Option<Watermark>,
+        repaint: Option<Repaint>,
+
+        playback: Option<Playback>,
+        static_format: Option<StaticFormat>,
+
         concurrency: Option<NonZeroUsize>,
         overwrite: bool,
         publisher: Option<String>,
@@ -50,12 +94,47 @@ impl MultiVideoGenContext {
             bail!("Duplicate pack kinds found, but they must be unique: {pack_kinds:?}");
         }
 
+        if caption.is_some() && caption_file.is_some() {
+            bail!("Caption and caption file can't be specified at the same time");
+        }
+
+        if split.is_some() && caption_file.is_some() {
+            bail!("Split mode and caption file can't be specified at the same time");
+        }
+
+        if auto_window.is_some() && (begin.is_some() || split.is_some()) {
+            bail!("Automatic window selection can't be used with `begin` or split mode");
+        }
+
+        if let Some(watermark) = &watermark {
+            watermark.validate()?;
+        }
+
+        if let Some(repaint) = &repaint {
+            repaint.validate()?;
+        }
+
+        let default_still = StillOptions::default();
+
+        let still = StillOptions {
+            animation,
+            duration: still_duration.unwrap_or(default_still.duration),
+            fps: still_fps.unwrap_or(default_still.fps),
+        };
+
         let options = SingleVideoGenOptions {
             begin,
             end,
+            loop_mode,
+            still,
             filter,
             ffmpeg_args,
             ffmpeg: ffmpeg.unwrap_or_else(|| Arc::new(crate::ffmpeg::FfmpegProcess)),
+            caption_style: caption_style.unwrap_or_default(),
+            watermark,
+            repaint,
+            playback: playback.unwrap_or_default(),
+            static_format: static_format.unwrap_or_default(),
             publisher,
         };
 
@@ -63,6 +142,11 @@ impl MultiVideoGenContext {
             pack_kinds,
             inputs,
             output,
+            caption,
+            caption_file,
+            split,
+            auto_window,
+            trim_still: trim_still.unwrap_or_default(),
             options: Arc::new(options),
             overwrite,
             concurrency: concurrency.unwrap_or_else(|| Self::default_concurrency("")),
@@ -85,24 +169,140 @@ impl MultiVideoGenContext {
 impl MultiVideoGenContext {
     fn contexts_for_pack_kind(
         &self,
-        inputs: &[Utf8StemmedPathBuf],
+        inputs: &[Input],
         pack_kind: PackKind,
     ) -> Result<Vec<SingleVideoGenContext>> {
         // This hack with `cloned()` is needed due to a compiler bug (rust/issues/102211)
         inputs
             .iter()
-            .map(move |input| {
-                let output = self.out_file(pack_kind, input.as_path())?;
+            .flat_map(|input| input.variants.iter().map(move |variant| (input, variant)))
+            .map(move |(input, variant)| {
+                let output = self.out_file(pack_kind, input.path.as_path(), variant.index)?;
                 Ok(SingleVideoGenContext {
                     options: self.options.clone(),
                     pack_kind,
-                    input: input.clone(),
+                    input: input.path.clone(),
                     output,
+                    caption: variant.caption.clone(),
+                    tile: None,
+                    segment: variant.segment,
+                    concat: None,
+                    lottie: input.lottie.clone(),
                 })
             })
             .collect()
     }
 
+    /// Renders the Lottie input into a video that ffmpeg can read
+    async fn render_lottie(
+        &self,
+        input: &Utf8StemmedPathBuf,
+    ) -> Result<Option<Arc<RenderedLottie>>> {
+        if !lottie::is_lottie(input.as_path()) {
+            return Ok(None);
+        }
+
+        // Render in the resolution of the largest output to avoid upscaling
+        let max_side = self
+            .pack_kinds
+            .iter()
+            .map(PackKind::bounding_box)
+            .max()
+            .unwrap_or_default();
+
+        let rendered =
+            RenderedLottie::render(&*self.options.ffmpeg, input.as_path(), max_side as u32)
+                .instrument(info_span!("lottie", input = %input.as_path()))
+                .await?;
+
+        Ok(Some(Arc::new(rendered)))
+    }
+
+    /// Returns the outputs generated from the `input`. The `source` is the
+    /// media that ffmpeg reads for the input.
+    async fn variants(
+        &self,
+        input: &Utf8StemmedPathBuf,
+        source: &Utf8Path,
+        captions: &Captions,
+    ) -> Result<Vec<Variant>> {
+        let ffmpeg = &*self.options.ffmpeg;
+        let span = |name| info_span!("analysis", name, input = %input.as_path());
+
+        let (mut begin, mut end) = (self.options.begin, self.options.end);
+        let mut segment = None;
+
+        if self.trim_still {
+            let trimmed = trim_still::trim_still(ffmpeg, source, begin, end)
+                .instrument(span("trim-still"))
+                .await?;
+
+            (begin, end) = (Some(trimmed.begin), Some(trimmed.end));
+            segment = Some(trimmed);
+        }
+
+        if let Some(metric) = self.auto_window {
+            let window = self.options.source_budget();
+
+            let found = metric
+                .find_window(ffmpeg, source, begin, end, window)
+                .instrument(span("auto-window"))
+                .await?;
+
+            segment = found.or(segment);
+        }
+
+        let Some(split) = &self.split else {
+            let mut variants = captions.variants();
+
+            for variant in &mut variants {
+                variant.segment = segment;
+            }
+
+            return Ok(variants);
+        };
+
+        let caption = match captions {
+            Captions::Single(caption) => caption,
+            Captions::Batch(_) => bail!("BUG: split mode doesn't support batch captions"),
+        };
+
+        let segments = split
+            .segments(ffmpeg, source, begin, end, self.options.source_budget())
+            .instrument(span("split"))
+            .await?;
+
+        Ok(segments
+            .into_iter()
+            .enumerate()
+            .map(|(i, segment)| Variant {
+                index: Some(i + 1),
+                caption: caption.clone(),
+                segment: Some(segment),
+            })
+            .collect())
+    }
+
+    async fn captions(&self) -> Result<Captions> {
+        let Some(caption_file) = &self.caption_file else {
+            return Ok(Captions::Single(self.caption.clone()));
+        };
+
+        let captions = fs::read_to_string(caption_file)
+            .await?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ToOwned::to_owned)
+            .collect_vec();
+
+        if captions.is_empty() {
+            bail!("The caption file `{caption_file}` doesn't contain any captions");
+        }
+
+        Ok(Captions::Batch(captions))
+    }
+
     async fn input_files(&self) -> Result<Vec<Utf8StemmedPathBuf>> {
         stream::iter(self.inputs.iter().cloned())
             .map(crate::fs::files)
@@ -115,17 +315,43 @@ impl MultiVideoGenContext {
             .try_collect()
     }
 
-    pub(crate) async fn run(self) -> Result {
+    /// Analyses the inputs and returns the contexts of every output file
+    /// without generating them
+    pub(crate) async fn contexts(&self) -> Result<Vec<SingleVideoGenContext>> {
         let input_files = self.input_files().await?;
 
         crate::fs::validate_duplicate_input_names(&input_files)?;
 
-        let contexts: Vec<_> = self
-            .pack_kinds
+        let captions = self.captions().await?;
+
+        let inputs = stream::iter(input_files)
+            .then(|path| async {
+                let lottie = self.render_lottie(&path).await?;
+
+                let source = lottie
+                    .as_ref()
+                    .map_or(path.as_path(), |lottie| lottie.path.as_path());
+
+                let variants = self.variants(&path, source, &captions).await?;
+
+                anyhow::Ok(Input {
+                    path,
+                    lottie,
+                    variants,
+                })
+            })
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        self.pack_kinds
             .iter()
-            .map(|&kind| self.contexts_for_pack_kind(&input_files, kind))
+            .map(|&kind| self.contexts_for_pack_kind(&inputs, kind))
             .flatten_ok()
-            .try_collect()?;
+            .try_collect()
+    }
+
+    pub(crate) async fn run(self) -> Result {
+        let contexts = self.contexts().await?;
 
         crate::fs::validate_output_files_overwriting(
             self.overwrite,
@@ -152,7 +378,12 @@ impl MultiVideoGenContext {
         Ok(())
     }
 
-    fn out_file(&self, pack_kind: PackKind, input: &Utf8Path) -> Result<Utf8PathBuf> {
+    fn out_file(
+        &self,
+        pack_kind: PackKind,
+        input: &Utf8Path,
+        index: Option<usize>,
+    ) -> Result<Utf8PathBuf> {
         let out_dir = self.output.as_deref().map(Ok).unwrap_or_else(|| {
             input.parent().with_context(|| {
                 format!("There is no parent directory for the input file {}", input)
@@ -163,15 +394,65 @@ impl MultiVideoGenContext {
             .file_stem()
             .with_context(|| format!("Input must have a file name, but got `{input:?}`"))?;
 
-        Ok(out_dir.join(format!("{file_name}-{pack_kind}.webm")))
+        let index = index.map(|index| format!("-{index}")).unwrap_or_default();
+
+        let extension = self.options.extension(pack_kind);
+
+        Ok(out_dir.join(format!("{file_name}{index}-{pack_kind}.{extension}")))
     }
 }
 
+enum Captions {
+    Single(Option<String>),
+    Batch(Vec<String>),
+}
+
+impl Captions {
+    fn variants(&self) -> Vec<Variant> {
+        match self {
+            Self::Single(caption) => vec![Variant {
+                index: None,
+                caption: caption.clone(),
+                segment: None,
+            }],
+            Self::Batch(captions) => captions
+                .iter()
+                .enumerate()
+                .map(|(i, caption)| Variant {
+                    index: Some(i + 1),
+                    caption: Some(caption.clone()),
+                    segment: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+struct Input {
+    path: Utf8StemmedPathBuf,
+
+    /// Video rendered from the Lottie input
+    lottie: Option<Arc<RenderedLottie>>,
+
+    variants: Vec<Variant>,
+}
+
+/// A single output generated from an input for every pack kind
+struct Variant {
+    /// The 1-based index used in the output file name if the input
+    /// produces several outputs
+    index: Option<usize>,
+    caption: Option<String>,
+    segment: Option<Segment>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::util::testing;
     use crate::video::testing::SharedMockFfmpeg;
+    use crate::video::{Fade, Flip, MatteSource, Rotation, WatermarkContent, WatermarkCorner};
+    use expect_test::expect;
     use lazy_regex::regex_replace;
 
     #[test_log::test(tokio::test)]
@@ -187,26 +468,385 @@ mod tests {
             .await;
     }
 
+    #[test_log::test(tokio::test)]
+    async fn smoke_loop_crossfade() {
+        FfmpegCall::builder()
+            .expected("smoke_loop_crossfade")
+            .begin(Duration::from_secs(1))
+            .loop_mode(LoopMode::parse("crossfade:0.5").unwrap())
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_still_animation() {
+        FfmpegCall::builder()
+            .expected("smoke_still_animation")
+            .input_suffix(".png")
+            .animation(AnimationPreset::Bounce)
+            .still_duration(Duration::from_secs_f64(1.5))
+            .loop_mode(LoopMode::parse("crossfade:0.25").unwrap())
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_caption() {
+        FfmpegCall::builder()
+            .expected("smoke_caption")
+            .caption("Hello, world!")
+            .caption_style(CaptionStyle {
+                font_file: Some("C:\\Windows\\Fonts\\arial.ttf".into()),
+                ..Default::default()
+            })
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn caption_file_generates_output_per_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let input = dir.join("clip.mp4");
+        let caption_file = dir.join("captions.txt");
+
+        fs::write(&input, "hello").await.unwrap();
+        fs::write(&caption_file, "First\n\n  Second  \n")
+            .await
+            .unwrap();
+
+        MultiVideoGenContext::builder()
+            .input(input)
+            .pack_kind(PackKind::Emoji)
+            .overwrite(false)
+            .ffmpeg(SharedMockFfmpeg::with_best_crf(25, PackKind::Emoji))
+            .caption_file(caption_file)
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+
+        let outputs = crate::fs::files(dir)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|path| path.file_name().map(ToOwned::to_owned))
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(
+            outputs,
+            [
+                "captions.txt",
+                "clip-1-emoji.webm",
+                "clip-2-emoji.webm",
+                "clip.mp4"
+            ]
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_watermark() {
+        FfmpegCall::builder()
+            .expected("smoke_watermark")
+            .watermark(Watermark {
+                content: WatermarkContent::Image("logo.png".into()),
+                corner: WatermarkCorner::BottomLeft,
+                opacity: 0.7,
+                scale: 0.25,
+                skip: vec![PackKind::Sticker],
+            })
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_repaint() {
+        FfmpegCall::builder()
+            .expected("smoke_repaint")
+            .caption("hi")
+            .repaint(Repaint {
+                source: MatteSource::Luminance,
+                ..Default::default()
+            })
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_split() {
+        FfmpegCall::builder()
+            .expected("smoke_split")
+            .split(SplitMode::parse("ranges:2-3.5,5-6").unwrap())
+            .loop_mode(LoopMode::Pingpong)
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_loop_pingpong() {
+        FfmpegCall::builder()
+            .expected("smoke_loop_pingpong")
+            .end(Duration::from_secs(2))
+            .loop_mode(LoopMode::Pingpong)
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_playback() {
+        // With the 2x speed the 4 seconds of the source fit into 2 seconds
+        // of the output, so the fade out starts at 1.5 seconds
+        FfmpegCall::builder()
+            .expected("smoke_playback")
+            .begin(Duration::from_secs(1))
+            .end(Duration::from_secs(5))
+            .playback(Playback {
+                reverse: true,
+                speed: Some(2.0),
+                rotate: Some(Rotation::Deg270),
+                flip: Some(Flip::V),
+                fade: Some(Fade::parse("0.25:0.5").unwrap()),
+            })
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_thumbnail() {
+        FfmpegCall::builder()
+            .expected("smoke_thumbnail")
+            .pack_kind(PackKind::Thumbnail)
+            .end(Duration::from_secs(2))
+            .assert()
+            .await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_static_sticker() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let input = dir.join("input.mp4");
+        fs::write(&input, "hello").await.unwrap();
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_quality(80, PackKind::StaticSticker);
+
+        MultiVideoGenContext::builder()
+            .input(input)
+            .pack_kind(PackKind::StaticSticker)
+            .begin(Duration::from_secs(1))
+            .loop_mode(LoopMode::Pingpong)
+            .overwrite(false)
+            .ffmpeg(mock_ffmpeg.clone())
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+
+        assert!(dir.join("input-static-sticker.webp").exists());
+
+        let mock = mock_ffmpeg.unwrap();
+
+        expect!["[50, 75, 88, 81, 78, 79, 80]"].assert_eq(&format!("{:?}", mock.crfs_log));
+
+        let args = mock.args_log[0]
+            .iter()
+            .map(|arg| arg.replace(dir.as_str(), "{dir}"))
+            .join("\n");
+
+        testing::expect_file("ffmpeg_calls/smoke_static_sticker.txt")
+            .await
+            .assert_eq(&args);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_static_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let input = dir.join("input.mp4");
+        fs::write(&input, "hello").await.unwrap();
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_colors(100, PackKind::StaticEmoji);
+
+        MultiVideoGenContext::builder()
+            .input(input)
+            .pack_kind(PackKind::StaticEmoji)
+            .static_format(StaticFormat::Png)
+            .overwrite(false)
+            .ffmpeg(mock_ffmpeg.clone())
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+
+        assert!(dir.join("input-static-emoji.png").exists());
+
+        let mock = mock_ffmpeg.unwrap();
+
+        expect!["[129, 65, 97, 113, 105, 101, 99, 100]"].assert_eq(&format!("{:?}", mock.crfs_log));
+
+        let args = mock.args_log[0]
+            .iter()
+            .map(|arg| arg.replace(dir.as_str(), "{dir}"))
+            .join("\n");
+
+        testing::expect_file("ffmpeg_calls/smoke_static_png.txt")
+            .await
+            .assert_eq(&args);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_svg() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let input = dir.join("input.svg");
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="5"/>"#;
+        fs::write(&input, svg).await.unwrap();
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_quality(80, PackKind::StaticEmoji);
+
+        MultiVideoGenContext::builder()
+            .input(input)
+            .pack_kind(PackKind::StaticEmoji)
+            .overwrite(false)
+            .ffmpeg(mock_ffmpeg.clone())
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+
+        assert!(dir.join("input-static-emoji.webp").exists());
+
+        let args = mock_ffmpeg.unwrap().args_log[0]
+            .iter()
+            .map(|arg| regex_replace!(r".*\.tmp\w*", arg, |_| "{temp_dir}").into_owned())
+            .join("\n");
+
+        // The rasterized image is used as the input instead of the SVG
+        testing::expect_file("ffmpeg_calls/smoke_svg.txt")
+            .await
+            .assert_eq(&args);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_lottie() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let lottie = serde_json::json!({
+            "w": 64,
+            "h": 64,
+            "fr": 60,
+            "ip": 0,
+            "op": 3,
+            "layers": [{ "ty": 1, "sw": 64, "sh": 64, "sc": "#ff0000", "ks": {} }]
+        });
+
+        let input = dir.join("input.json");
+        fs::write(&input, lottie.to_string()).await.unwrap();
+
+        let mock_ffmpeg = SharedMockFfmpeg::with_best_crf(25, PackKind::Sticker);
+
+        MultiVideoGenContext::builder()
+            .input(input)
+            .pack_kind(PackKind::Sticker)
+            .overwrite(false)
+            .ffmpeg(mock_ffmpeg.clone())
+            .build()
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+
+        assert!(dir.join("input-sticker.webm").exists());
+
+        let mock = mock_ffmpeg.unwrap();
+
+        let [render] = mock.analysis_args_log.as_slice() else {
+            panic!("Expected a single render, got {:?}", mock.analysis_args_log);
+        };
+
+        let sanitize = |args: &[String]| {
+            args.iter()
+                .map(|arg| regex_replace!(r".*\.tmp\w*", arg, |_| "{temp_dir}").into_owned())
+                .join("\n")
+        };
+
+        expect![[r#"
+            -y
+            -framerate
+            30
+            -i
+            {temp_dir}/frame_%05d.png
+            -vcodec
+            ffv1
+            -pix_fmt
+            bgra
+            {temp_dir}/lottie.mkv"#]]
+        .assert_eq(&sanitize(render));
+
+        let rendered = render.last().unwrap();
+        let sticker = &mock.args_log[0];
+
+        // The rendered video is used as the input instead of the Lottie file
+        assert!(sticker.contains(rendered));
+        assert!(!sticker.iter().any(|arg| arg.ends_with("input.json")));
+
+        // The intermediate files are removed after the run
+        let frame = std::path::Path::new(rendered).with_file_name("frame_00002.png");
+        assert!(!frame.exists());
+    }
+
     struct FfmpegCall;
 
     #[buildstructor]
     impl FfmpegCall {
         #[builder(exit = "assert")]
+        #[allow(clippy::new_ret_no_self, clippy::too_many_arguments)]
         async fn new(
             expected: String,
+            input_suffix: Option<String>,
+            pack_kind: Option<PackKind>,
 
             begin: Option<Duration>,
             end: Option<Duration>,
 
+            split: Option<SplitMode>,
+
+            loop_mode: Option<LoopMode>,
+
+            animation: Option<AnimationPreset>,
+            still_duration: Option<Duration>,
+
             filter: Option<String>,
             ffmpeg_args: Vec<String>,
 
+            caption: Option<String>,
+            caption_style: Option<CaptionStyle>,
+
+            watermark: Option<Watermark>,
+            repaint: Option<Repaint>,
+
+            playback: Option<Playback>,
+
             publisher: Option<String>,
         ) {
-            let input = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+            let input = tempfile::Builder::new()
+                .suffix(input_suffix.as_deref().unwrap_or_default())
+                .tempfile()
+                .unwrap()
+                .into_temp_path();
             fs::write(&input, "hello").await.unwrap();
 
-            let pack_kind = PackKind::Emoji;
+            let pack_kind = pack_kind.unwrap_or(PackKind::Emoji);
 
             let mock_ffmpeg = SharedMockFfmpeg::with_best_crf(25, pack_kind);
 
@@ -219,8 +859,17 @@ mod tests {
             let ctx = ctx
                 .and_begin(begin)
                 .and_end(end)
+                .and_split(split)
+                .and_loop_mode(loop_mode)
+                .and_animation(animation)
+                .and_still_duration(still_duration)
                 .and_filter(filter)
                 .ffmpeg_args(ffmpeg_args)
+                .and_caption(caption)
+                .and_caption_style(caption_style)
+                .and_watermark(watermark)
+                .and_repaint(repaint)
+                .and_playback(playback)
                 .and_publisher(publisher)
                 .build()
                 .unwrap();
@@ -237,6 +886,13 @@ mod tests {
                 .into_owned();
             }
 
+            // Sanitize the platform-specific null output path
+            if let Some(null_output) = ffmpeg_call.last_mut() {
+                if null_output == "NUL" || null_output == "/dev/null" {
+                    *null_output = "{null_output}".to_owned();
+                }
+            }
+
             let ffmpeg_call = ffmpeg_call.iter().join("\n");
 
             let expected = testing::expect_file(&format!("ffmpeg_calls/{expected}.txt")).await;
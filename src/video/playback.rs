@@ -0,0 +1,285 @@
+use super::MAX_FPS;
+use crate::prelude::*;
+use std::time::Duration;
+
+/// Clockwise rotation of the video
+#[derive(strum::Display, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Rotation {
+    #[value(name = "90")]
+    #[strum(serialize = "90")]
+    Deg90,
+
+    #[value(name = "180")]
+    #[strum(serialize = "180")]
+    Deg180,
+
+    #[value(name = "270")]
+    #[strum(serialize = "270")]
+    Deg270,
+}
+
+impl Rotation {
+    fn filter(self) -> &'static str {
+        match self {
+            Self::Deg90 => "transpose=clock",
+            Self::Deg180 => "hflip,vflip",
+            Self::Deg270 => "transpose=cclock",
+        }
+    }
+}
+
+/// Mirroring of the video
+#[derive(strum::Display, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum Flip {
+    /// Mirror horizontally (left to right)
+    H,
+
+    /// Mirror vertically (top to bottom)
+    V,
+}
+
+impl Flip {
+    fn filter(self) -> &'static str {
+        match self {
+            Self::H => "hflip",
+            Self::V => "vflip",
+        }
+    }
+}
+
+/// Durations of the fade from and to the full transparency at the
+/// beginning and the end of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Fade {
+    pub(crate) fade_in: Duration,
+    pub(crate) fade_out: Duration,
+}
+
+impl Fade {
+    /// Parses the value in format `{in}:{out}`. Any of the durations may be
+    /// zero to disable the fade on that side.
+    pub(crate) fn parse(arg: &str) -> Result<Self> {
+        let (fade_in, fade_out) = arg
+            .split_once(':')
+            .with_context(|| format!("Expected `in:out` fade durations, but got `{arg}`"))?;
+
+        let fade = Self {
+            fade_in: crate::util::duration::parse(fade_in)?,
+            fade_out: crate::util::duration::parse(fade_out)?,
+        };
+
+        if fade.fade_in.is_zero() && fade.fade_out.is_zero() {
+            bail!("At least one of the fade durations must be greater than zero");
+        }
+
+        Ok(fade)
+    }
+}
+
+/// Parses the speed factor making sure it's a positive number
+pub(crate) fn parse_speed(arg: &str) -> Result<f64> {
+    let speed: f64 = arg
+        .parse()
+        .with_context(|| format!("Invalid speed factor `{arg}`"))?;
+
+    if !speed.is_finite() || speed <= 0.0 {
+        bail!("Speed factor must be a positive number, but got {speed}");
+    }
+
+    Ok(speed)
+}
+
+/// Transformations of the way the video is played back
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Playback {
+    pub(crate) reverse: bool,
+
+    /// Factor by which the video is sped up. Values less than 1 slow it down.
+    pub(crate) speed: Option<f64>,
+
+    pub(crate) rotate: Option<Rotation>,
+    pub(crate) flip: Option<Flip>,
+    pub(crate) fade: Option<Fade>,
+}
+
+impl Playback {
+    pub(crate) fn speed(&self) -> f64 {
+        self.speed.unwrap_or(1.0)
+    }
+
+    /// Duration of the output for the given duration of the source segment
+    pub(crate) fn output_duration(&self, source: Duration) -> Duration {
+        source.div_f64(self.speed())
+    }
+
+    /// Duration of the source segment for the given duration of the output
+    pub(crate) fn source_duration(&self, output: Duration) -> Duration {
+        output.mul_f64(self.speed())
+    }
+
+    /// Whether the fade filter needs to know the exact duration of the output
+    pub(crate) fn needs_output_duration(&self) -> bool {
+        matches!(self.fade, Some(fade) if !fade.fade_out.is_zero())
+    }
+
+    /// Filter that changes the timeline of the trimmed source segment.
+    /// It goes before the loop filter, which sees the post-speed duration.
+    ///
+    /// The frames are passed through by the encoder, so speeding up raises
+    /// the frame rate. It's capped at [`MAX_FPS`] by dropping the extra frames.
+    pub(crate) fn timeline_filter(&self) -> Option<String> {
+        let speed = self.speed.filter(|&speed| speed != 1.0).map(|speed| {
+            if speed > 1.0 {
+                format!("setpts=PTS/{speed},fps={MAX_FPS}")
+            } else {
+                format!("setpts=PTS/{speed}")
+            }
+        });
+
+        let reverse = self.reverse.then(|| "reverse".to_owned());
+
+        let filters = speed.into_iter().chain(reverse).collect_vec();
+
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+
+    /// Filter that rotates and mirrors the frames. It goes before scaling,
+    /// so that the rotated frame fits into the bounding box.
+    pub(crate) fn orientation_filter(&self) -> Option<String> {
+        let filters = self
+            .rotate
+            .map(Rotation::filter)
+            .into_iter()
+            .chain(self.flip.map(Flip::filter))
+            .collect_vec();
+
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+
+    /// Filter that fades the output from and to the full transparency.
+    /// The `output` is the final duration of the output. It's required only
+    /// if [`Self::needs_output_duration`] returns `true`.
+    pub(crate) fn fade_filter(&self, output: Option<Duration>) -> Result<Option<String>> {
+        let Some(fade) = self.fade else {
+            return Ok(None);
+        };
+
+        let fade_in = (!fade.fade_in.is_zero())
+            .then(|| format!("fade=t=in:st=0:d={}:alpha=1", fade.fade_in.to_secs_f64()));
+
+        let fade_out = if fade.fade_out.is_zero() {
+            None
+        } else {
+            let output = output.context("BUG: output duration is required for fade out")?;
+
+            if output < fade.fade_in + fade.fade_out {
+                bail!(
+                    "The output ({output:.2?}) is shorter than the fade in and \
+                    fade out durations combined ({:?} + {:?})",
+                    fade.fade_in,
+                    fade.fade_out,
+                );
+            }
+
+            let start = (output - fade.fade_out).to_secs_f64();
+
+            Some(format!(
+                "fade=t=out:st={start}:d={}:alpha=1",
+                fade.fade_out.to_secs_f64()
+            ))
+        };
+
+        let filters = fade_in.into_iter().chain(fade_out).collect_vec();
+
+        Ok((!filters.is_empty()).then(|| filters.join(",")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_filters() {
+        let playback = Playback {
+            reverse: true,
+            speed: Some(2.0),
+            rotate: Some(Rotation::Deg90),
+            flip: Some(Flip::H),
+            fade: Some(Fade::parse("0.25:0.5").unwrap()),
+        };
+
+        let output = playback.output_duration(Duration::from_secs(4));
+
+        let filters = [
+            playback.timeline_filter(),
+            playback.orientation_filter(),
+            playback.fade_filter(Some(output)).unwrap(),
+        ];
+
+        expect![[r#"
+            [
+                Some(
+                    "setpts=PTS/2,fps=30,reverse",
+                ),
+                Some(
+                    "transpose=clock,hflip",
+                ),
+                Some(
+                    "fade=t=in:st=0:d=0.25:alpha=1,fade=t=out:st=1.5:d=0.5:alpha=1",
+                ),
+            ]
+        "#]]
+        .assert_debug_eq(&filters);
+
+        assert_eq!(Playback::default().timeline_filter(), None);
+        assert_eq!(Playback::default().orientation_filter(), None);
+    }
+
+    #[test]
+    fn speed_filters() {
+        let filters = [0.5, 1.0, 2.0].map(|speed| {
+            Playback {
+                speed: Some(speed),
+                ..Default::default()
+            }
+            .timeline_filter()
+        });
+
+        expect![[r#"
+            [
+                Some(
+                    "setpts=PTS/0.5",
+                ),
+                None,
+                Some(
+                    "setpts=PTS/2,fps=30",
+                ),
+            ]
+        "#]]
+        .assert_debug_eq(&filters);
+    }
+
+    #[test]
+    fn error_parse() {
+        let errors = [
+            Fade::parse("0.5").unwrap_err(),
+            Fade::parse("0:0").unwrap_err(),
+            parse_speed("-1").unwrap_err(),
+            parse_speed("fast").unwrap_err(),
+        ]
+        .map(|err| err.to_string());
+
+        expect![[r#"
+            [
+                "Expected `in:out` fade durations, but got `0.5`",
+                "At least one of the fade durations must be greater than zero",
+                "Speed factor must be a positive number, but got -1",
+                "Invalid speed factor `fast`",
+            ]
+        "#]]
+        .assert_debug_eq(&errors);
+    }
+}
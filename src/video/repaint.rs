@@ -0,0 +1,164 @@
+use super::PackKind;
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Where the opacity of the repaintable emoji is taken from
+#[derive(
+    Deserialize,
+    Serialize,
+    strum::Display,
+    clap::ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum MatteSource {
+    /// The pixels brighter than the threshold become opaque and the rest
+    /// become transparent
+    #[default]
+    Threshold,
+
+    /// The brightness of the pixels becomes their opacity
+    Luminance,
+
+    /// The existing alpha channel is used as is. It fits the inputs that
+    /// already are silhouettes on a transparent background.
+    Alpha,
+}
+
+/// Conversion of the emoji into a white silhouette on a transparent
+/// background. Telegram recolors such emoji into the color of the text
+/// when the set is created with `needs_repainting`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Repaint {
+    pub(crate) source: MatteSource,
+
+    /// Brightness in range `[0; 1]` that separates the opaque pixels from
+    /// the transparent ones for the `threshold` source
+    pub(crate) threshold: f64,
+
+    /// Swap the opaque and the transparent parts of the matte. Useful for
+    /// the dark art on a light background. Not allowed for the `alpha`
+    /// source, because it would make the padding opaque.
+    pub(crate) invert: bool,
+
+    /// Standard deviation of the Gaussian blur of the matte edges in pixels.
+    /// Zero keeps the edges sharp.
+    pub(crate) smoothing: f64,
+}
+
+impl Default for Repaint {
+    fn default() -> Self {
+        Self {
+            source: MatteSource::default(),
+            threshold: 0.5,
+            invert: false,
+            smoothing: 1.0,
+        }
+    }
+}
+
+impl Repaint {
+    pub(crate) fn validate(&self) -> Result {
+        if !(0.0..=1.0).contains(&self.threshold) {
+            bail!(
+                "Repaint threshold must be in range [0; 1], but got {}",
+                self.threshold
+            );
+        }
+        if !(self.smoothing >= 0.0 && self.smoothing.is_finite()) {
+            bail!(
+                "Repaint smoothing must be a non-negative number, but got {}",
+                self.smoothing
+            );
+        }
+        if self.invert && self.source == MatteSource::Alpha {
+            bail!(
+                "Repaint invert isn't supported for the `alpha` source, because it \
+                would turn the transparent padding and background opaque"
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the filter that turns the frames into the alpha matte. It's
+    /// meant to be applied after the video is scaled, so that the expressions
+    /// are evaluated only for the pixels of the output. Returns `None` for
+    /// the pack kinds other than emoji, because Telegram repaints only them.
+    pub(crate) fn filter(&self, pack_kind: PackKind) -> Option<String> {
+        if !matches!(pack_kind, PackKind::Emoji | PackKind::StaticEmoji) {
+            return None;
+        }
+
+        // Relative luminance as defined by ITU-R BT.709 in range `[0; 255]`
+        let luminance = "(0.2126*r(X,Y)+0.7152*g(X,Y)+0.0722*b(X,Y))";
+
+        let luminance = if self.invert {
+            format!("(255-{luminance})")
+        } else {
+            luminance.to_owned()
+        };
+
+        // The existing transparency is kept for all sources, so that
+        // the padding and the transparent background stay transparent
+        let alpha = match self.source {
+            MatteSource::Threshold => {
+                let threshold = self.threshold * 255.0;
+                format!("if(gte({luminance},{threshold}),alpha(X,Y),0)")
+            }
+            MatteSource::Luminance => format!("alpha(X,Y)*{luminance}/255"),
+            MatteSource::Alpha => "alpha(X,Y)".to_owned(),
+        };
+
+        // The commas of the expression must be escaped in the filtergraph
+        let alpha = alpha.replace(',', "\\,");
+        let matte = format!("format=rgba,geq=r=255:g=255:b=255:a={alpha}");
+
+        // Only the alpha plane is blurred, the color stays pure white
+        let smoothing = (self.smoothing > 0.0)
+            .then(|| format!("format=gbrap,gblur=sigma={}:planes=8", self.smoothing));
+
+        Some([Some(matte), smoothing].into_iter().flatten().join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_repaint_filter() {
+        let repaint = Repaint::default();
+
+        assert_eq!(repaint.filter(PackKind::Sticker), None);
+
+        expect![[r#"format=rgba,geq=r=255:g=255:b=255:a=if(gte((0.2126*r(X\,Y)+0.7152*g(X\,Y)+0.0722*b(X\,Y))\,127.5)\,alpha(X\,Y)\,0),format=gbrap,gblur=sigma=1:planes=8"#]]
+        .assert_eq(&repaint.filter(PackKind::Emoji).unwrap());
+
+        let repaint = Repaint {
+            source: MatteSource::Luminance,
+            invert: true,
+            smoothing: 0.0,
+            ..Default::default()
+        };
+
+        expect![[r#"format=rgba,geq=r=255:g=255:b=255:a=alpha(X\,Y)*(255-(0.2126*r(X\,Y)+0.7152*g(X\,Y)+0.0722*b(X\,Y)))/255"#]]
+        .assert_eq(&repaint.filter(PackKind::StaticEmoji).unwrap());
+
+        // Inverting the alpha would make the transparent padding opaque
+        let repaint = Repaint {
+            source: MatteSource::Alpha,
+            invert: true,
+            ..Default::default()
+        };
+
+        expect!["Repaint invert isn't supported for the `alpha` source, because it would turn the transparent padding and background opaque"].assert_eq(&repaint.validate().unwrap_err().to_string());
+    }
+}
@@ -1,5 +1,16 @@
+use super::animation::{self, StillOptions};
+use super::caption::CaptionStyle;
+use super::concat_gen::Concat;
+use super::grid_gen::GridTile;
+use super::lottie::RenderedLottie;
+use super::playback::Playback;
+use super::repaint::Repaint;
+use super::split::Segment;
+use super::static_image::{StaticFormat, StaticImageContext};
+use super::svg;
+use super::watermark::Watermark;
 use super::webm_vp9_two_pass::TwoPassContext;
-use super::{PackKind, MAX_CRF};
+use super::{LoopMode, PackKind, MAX_CRF, MAX_DURATION};
 use crate::display;
 use crate::ffmpeg::Ffmpeg;
 use crate::prelude::*;
@@ -12,13 +23,59 @@ pub(crate) struct SingleVideoGenOptions {
     pub(crate) begin: Option<Duration>,
     pub(crate) end: Option<Duration>,
 
+    pub(crate) loop_mode: Option<LoopMode>,
+
+    pub(crate) still: StillOptions,
+
     pub(crate) filter: Option<String>,
     pub(crate) ffmpeg_args: Vec<String>,
     pub(crate) ffmpeg: Arc<dyn Ffmpeg>,
 
+    pub(crate) caption_style: CaptionStyle,
+    pub(crate) watermark: Option<Watermark>,
+
+    /// Turns the emoji into a silhouette that Telegram can repaint
+    pub(crate) repaint: Option<Repaint>,
+
+    pub(crate) playback: Playback,
+
+    /// Image format of the static outputs
+    pub(crate) static_format: StaticFormat,
+
     pub(crate) publisher: Option<String>,
 }
 
+impl SingleVideoGenOptions {
+    /// Extension of the output file for the given pack kind
+    pub(crate) fn extension(&self, pack_kind: PackKind) -> &'static str {
+        if pack_kind.is_static() {
+            self.static_format.extension()
+        } else {
+            "webm"
+        }
+    }
+
+    /// Max duration of the source segment such that the output still fits
+    /// into [`MAX_DURATION`] after the speed change and looping
+    pub(crate) fn source_budget(&self) -> Duration {
+        let output = self
+            .loop_mode
+            .map(|loop_mode| loop_mode.source_budget())
+            .unwrap_or(MAX_DURATION);
+
+        self.playback.source_duration(output)
+    }
+}
+
+/// The bounds of the source segment and the filters that depend on them
+struct Trim {
+    begin: Option<Duration>,
+    end: Option<Duration>,
+    loop_filter: Option<String>,
+    fade_filter: Option<String>,
+}
+
+#[derive(Clone)]
 pub(crate) struct SingleVideoGenContext {
     // It's theoreically possible to replace this `Arc` with a bare shared reference
     // but there is a bug in rust compiler that prevents it from working.
@@ -32,6 +89,24 @@ pub(crate) struct SingleVideoGenContext {
     pub(crate) pack_kind: PackKind,
     pub(crate) input: Utf8StemmedPathBuf,
     pub(crate) output: Utf8PathBuf,
+
+    /// Text rendered on top of the video, which is specific to this output
+    pub(crate) caption: Option<String>,
+
+    /// The part of the input's frame that this output covers
+    pub(crate) tile: Option<GridTile>,
+
+    /// The time range of the input that this output covers. It overrides
+    /// the `begin` and `end` from the options.
+    pub(crate) segment: Option<Segment>,
+
+    /// Several inputs concatenated into this output. If set, the `input`
+    /// is used only for logging.
+    pub(crate) concat: Option<Arc<Concat>>,
+
+    /// Video rendered from the Lottie input, which is used as the source
+    /// instead of the `input`
+    pub(crate) lottie: Option<Arc<RenderedLottie>>,
 }
 
 impl SingleVideoGenContext {
@@ -45,18 +120,51 @@ impl SingleVideoGenContext {
     )]
     pub(crate) async fn generate_file(self) -> Result {
         let output = self.output.clone();
-        let bytes = self.generate_bytes().await?;
+        let input = self.input.clone();
+        let pack_kind = self.pack_kind;
 
-        fs::write(&output, bytes).await?;
+        let bytes = self.generate_bytes().await?;
 
-        let out_file = nu_ansi_term::Color::Magenta.bold().paint(output.as_str());
+        crate::fs::write_output(&output, &bytes).await?;
 
-        info!("🔥 Saved output at {out_file}");
+        // The thumbnail isn't a sticker, so it doesn't need the metadata
+        if pack_kind != PackKind::Thumbnail {
+            crate::meta::copy_sidecar(input.as_path(), &output).await?;
+        }
 
         Ok(())
     }
 
     pub(crate) async fn generate_bytes(self) -> Result<Arc<[u8]>> {
+        if self.pack_kind.is_static() {
+            return self.static_image_context().await?.search().await;
+        }
+
+        self.search_crf().await.map(|(_crf, output)| output)
+    }
+
+    /// Generates the output with the given CRF without searching for the best one.
+    /// Returns an error if the output doesn't fit into the limits.
+    pub(crate) async fn generate_bytes_with_crf(self, crf: usize) -> Result<Arc<[u8]>> {
+        let max_bytes = self.pack_kind.max_bytes();
+
+        let output = self.two_pass_context().await?.run(crf).await?;
+
+        if output.len() > max_bytes {
+            bail!(
+                "The output generated with CRF {} is {}, which exceeds the limit of {}",
+                display::bold(&crf),
+                display::bold_human_size(output.len()),
+                display::bold_human_size(max_bytes),
+            );
+        }
+
+        Ok(output)
+    }
+
+    /// Runs the binary search for the lowest CRF (best quality) that generates
+    /// the output fitting into the limits of the pack kind
+    pub(crate) async fn search_crf(self) -> Result<(usize, Arc<[u8]>)> {
         let start = std::time::Instant::now();
 
         let mut min = 0;
@@ -68,7 +176,7 @@ impl SingleVideoGenContext {
 
         info!("🚀 Trying to find best CRF to fit into {max_bytes_display}");
 
-        let mut two_pass = self.two_pass_context()?;
+        let mut two_pass = self.two_pass_context().await?;
         let mut i = 0u32;
 
         let (crf, output) = loop {
@@ -109,13 +217,13 @@ impl SingleVideoGenContext {
             }
         };
 
-        let crf = display::bold(&crf);
+        let crf_display = display::bold(&crf);
 
         if output.len() > max_bytes {
             let size_display = display::bold_human_size(output.len());
             let msg = format!(
                 "The output can't possibly fit into the limit of {max_bytes_display}. \
-                The minimum generated file size with CRF {crf} is {size_display}",
+                The minimum generated file size with CRF {crf_display} is {size_display}",
             );
             debug!("{msg}");
             bail!("{msg}");
@@ -125,25 +233,239 @@ impl SingleVideoGenContext {
 
         let elapsed = display::elpased(start);
 
-        info!("🎉 Found a fitting CRF {crf}, which generates {size_display} in {elapsed}");
+        info!("🎉 Found a fitting CRF {crf_display}, which generates {size_display} in {elapsed}");
+        Ok((crf, output))
+    }
+
+    /// Returns the `begin` and `end` bounds of the source segment along with
+    /// the filters that depend on its duration.
+    async fn trim(&self) -> Result<Trim> {
+        let (begin, end) = match self.segment {
+            Some(segment) => (Some(segment.begin), Some(segment.end)),
+            None => (self.options.begin, self.options.end),
+        };
+
+        let loop_mode = self.options.loop_mode;
+        let playback = &self.options.playback;
+
+        let needs_duration = matches!(loop_mode, Some(mode) if mode.needs_segment_duration())
+            || playback.needs_output_duration();
+
+        let start = begin.unwrap_or_default();
+
+        let end = if needs_duration {
+            let available = self.media_duration().await?;
+
+            Some(end.map_or(available, |end| end.min(available)))
+        } else {
+            end
+        };
+
+        if matches!(end, Some(end) if end <= start) {
+            bail!("The end of the video segment must be after its beginning");
+        }
+
+        let budget = self.options.source_budget();
+
+        let end = match (end, loop_mode) {
+            (Some(end), _) if end - start <= budget => Some(end),
+            (end, None) => {
+                if let Some(end) = end {
+                    let output = playback.output_duration(end - start);
+                    warn!(
+                        "The output duration {} exceeds the limit of {MAX_DURATION:?}. \
+                        Telegram may reject such an emoji/sticker.",
+                        display::bold(&format_args!("{output:.2?}")),
+                    );
+                }
+                end
+            }
+            (_, Some(loop_mode)) => {
+                info!(
+                    "✂️ Limiting the source segment to {} with {loop_mode:?} loop \
+                    to fit into the duration limit",
+                    display::bold(&format_args!("{budget:.2?}")),
+                );
+                Some(start + budget)
+            }
+        };
+
+        // The loop filter goes after the speed change, so it sees the
+        // post-speed duration of the segment
+        let segment = end.map(|end| playback.output_duration(end - start));
+
+        let loop_filter = loop_mode.map(|mode| mode.filter(segment)).transpose()?;
+
+        let output = match loop_mode {
+            Some(mode) => segment.map(|segment| mode.output_duration(segment)),
+            None => segment,
+        };
+
+        Ok(Trim {
+            begin,
+            end,
+            loop_filter,
+            fade_filter: playback.fade_filter(output)?,
+        })
+    }
+
+    /// SVG inputs are rasterized into still images
+    fn is_still_image(&self) -> bool {
+        animation::is_still_image(self.input.as_path()) || self.is_svg()
+    }
+
+    fn is_svg(&self) -> bool {
+        svg::is_svg(self.input.as_path())
+    }
+
+    /// Path to the media that ffmpeg reads
+    fn source(&self) -> &Utf8Path {
+        match &self.lottie {
+            Some(lottie) => &lottie.path,
+            None => self.input.as_path(),
+        }
+    }
+
+    async fn media_duration(&self) -> Result<Duration> {
+        if self.is_still_image() {
+            return Ok(self.options.still.duration);
+        }
+
+        self.options.ffmpeg.probe_duration(self.source()).await
+    }
+
+    /// Returns the bounds of the source segment. Static outputs take a single
+    /// frame at `begin`, so they don't depend on the duration of the segment.
+    async fn bounds(&self) -> Result<Trim> {
+        if !self.pack_kind.is_static() {
+            return self.trim().await;
+        }
+
+        let begin = match self.segment {
+            Some(segment) => Some(segment.begin),
+            None => self.options.begin,
+        };
+
+        Ok(Trim {
+            begin,
+            end: None,
+            loop_filter: None,
+            fade_filter: None,
+        })
+    }
+
+    /// Rasterizes the SVG input into the `temp_dir` at the exact size of the
+    /// output. Grid tiles are cut from the image rasterized for the whole grid.
+    async fn rasterize_svg(&self, temp_dir: &Utf8Path) -> Result<Utf8PathBuf> {
+        let max_side = self.pack_kind.bounding_box() as u32;
+
+        let (cols, rows) = self
+            .tile
+            .as_ref()
+            .map_or((1, 1), |tile| (tile.cols, tile.rows));
+
+        let output = temp_dir.join("svg.png");
+
+        svg::rasterize(
+            self.input.as_path(),
+            max_side * cols,
+            max_side * rows,
+            &output,
+        )
+        .await?;
+
         Ok(output)
     }
 
-    fn two_pass_context(self) -> Result<TwoPassContext> {
-        let temp_dir = tempfile::tempdir()?;
-        let pass_log_file = temp_dir
-            .path()
-            .join("ffmpeg2pass")
-            .to_string_lossy()
-            .into_owned();
+    /// Builds the input options and the filtergraph shared by the video
+    /// and the static outputs. The intermediate files are put into `temp_dir`.
+    async fn encoding(&self, temp_dir: &Utf8Path) -> Result<Encoding> {
+        let Trim {
+            begin,
+            end,
+            loop_filter,
+            fade_filter,
+        } = self.bounds().await?;
+
+        let is_static = self.pack_kind.is_static();
 
         let max_side = self.pack_kind.bounding_box();
 
+        // Static outputs consist of a single frame, so the timeline doesn't matter
+        let timeline = (!is_static)
+            .then(|| self.options.playback.timeline_filter())
+            .flatten();
+
+        // The loop, the timeline and the fade filters need the timestamps of
+        // the trimmed segment to start from zero, so the bounds are specified
+        // as input options for them. Otherwise they stay output options.
+        let seek_input = loop_filter.is_some() || fade_filter.is_some() || timeline.is_some();
+
+        let (input_args, source_filter, filter_option) = match &self.concat {
+            Some(concat) => {
+                let (input_args, graph) = concat
+                    .inputs(&*self.options.ffmpeg, &self.options.still, max_side)
+                    .await?;
+                (input_args, Some(graph), "-filter_complex")
+            }
+            None => {
+                let (still_input_args, animation_filter) = if self.is_still_image() && !is_static {
+                    (self.options.still.input_args(), self.options.still.filter())
+                } else {
+                    (vec![], None)
+                };
+
+                let source = if self.is_svg() {
+                    self.rasterize_svg(temp_dir).await?
+                } else {
+                    self.source().to_owned()
+                };
+
+                let bounds = optional_named_duration_arg("-ss", begin)
+                    .chain(optional_named_duration_arg("-to", end))
+                    .collect_vec();
+
+                let (input_bounds, output_bounds) = if seek_input {
+                    (bounds, vec![])
+                } else {
+                    (vec![], bounds)
+                };
+
+                let input_args = input_bounds
+                    .into_iter()
+                    .chain(still_input_args)
+                    .chain(iter::strs(["-i", source.as_str()]))
+                    .chain(output_bounds)
+                    .collect();
+
+                (input_args, animation_filter, "-filter:v")
+            }
+        };
+
         let ultimate_padding = self
             .pack_kind
             .must_be_square()
             .then(|| format!("pad={max_side}:{max_side}:-1:-1:color=0x00000000"));
 
+        // The caption is rendered after scaling to make its size relative to
+        // the final output, otherwise it could become unreadable
+        let caption = self
+            .caption
+            .as_deref()
+            .and_then(|text| self.options.caption_style.filter(text, max_side));
+
+        let watermark = self
+            .options
+            .watermark
+            .as_ref()
+            .and_then(|watermark| watermark.filter(self.pack_kind));
+
+        let repaint = self
+            .options
+            .repaint
+            .as_ref()
+            .and_then(|repaint| repaint.filter(self.pack_kind));
+
         // We need to make sure the image fits into the bounding box.
         // The scale filter expression is inspired by this answer:
         // https://superuser.com/a/547406
@@ -157,12 +479,26 @@ impl SingleVideoGenContext {
             flags=lanczos"
         );
 
-        let video_filter = self
-            .options
-            .filter
+        let tile = self.tile.as_ref().map(GridTile::filter);
+
+        let orientation = self.options.playback.orientation_filter();
+
+        // The matte goes after the caption and the watermark to make them
+        // a part of the silhouette. The fade goes last to make all of them
+        // fade too.
+        let video_filter = source_filter
             .iter()
+            .chain(&timeline)
+            .chain(&self.options.filter)
+            .chain(&loop_filter)
+            .chain(&orientation)
+            .chain(&tile)
             .chain([&ultimate_scale])
             .chain(&ultimate_padding)
+            .chain(&caption)
+            .chain(&watermark)
+            .chain(&repaint)
+            .chain(&fade_filter)
             .join(",");
 
         let publisher = optional_named_arg(
@@ -173,13 +509,41 @@ impl SingleVideoGenContext {
                 .map(|publisher| format!("publisher={publisher}")),
         );
 
-        let prefix_args = iter::strs(["-y", "-i", self.input.as_path().as_str()])
-            .chain(optional_named_duration_arg("-ss", self.options.begin))
-            .chain(optional_named_duration_arg("-to", self.options.end))
-            .chain(publisher)
+        let metadata_args = publisher
             .chain(iter::strs([
                 "-metadata",
                 "encoded_by=https://github.com/Veetaha/tstick",
+            ]))
+            .collect();
+
+        Ok(Encoding {
+            input_args,
+            filter_option,
+            video_filter,
+            metadata_args,
+        })
+    }
+
+    async fn two_pass_context(self) -> Result<TwoPassContext> {
+        let temp_dir = tempfile::tempdir()?;
+
+        let Encoding {
+            input_args,
+            filter_option,
+            video_filter,
+            metadata_args,
+        } = self.encoding(temp_dir.path().unwrap_utf8()).await?;
+
+        let pass_log_file = temp_dir
+            .path()
+            .join("ffmpeg2pass")
+            .to_string_lossy()
+            .into_owned();
+
+        let prefix_args = iter::strs(["-y"])
+            .chain(input_args)
+            .chain(metadata_args)
+            .chain(iter::strs([
                 "-fps_mode",
                 "passthrough",
                 "-vcodec",
@@ -190,7 +554,7 @@ impl SingleVideoGenContext {
                 "0",
                 // Audio streams must be removed from the output
                 "-an",
-                "-filter:v",
+                filter_option,
             ]))
             .chain([video_filter])
             .chain(iter::strs(["-passlogfile"]))
@@ -205,6 +569,44 @@ impl SingleVideoGenContext {
             .temp_dir(temp_dir)
             .build())
     }
+
+    async fn static_image_context(self) -> Result<StaticImageContext> {
+        let temp_dir = tempfile::tempdir()?;
+
+        let Encoding {
+            input_args,
+            filter_option,
+            video_filter,
+            metadata_args,
+        } = self.encoding(temp_dir.path().unwrap_utf8()).await?;
+
+        let input_args = iter::strs(["-y"]).chain(input_args).collect();
+
+        let output_args = metadata_args
+            .into_iter()
+            .chain(self.options.ffmpeg_args.iter().cloned())
+            .collect();
+
+        Ok(StaticImageContext::builder()
+            .input_args(input_args)
+            .filter_option(filter_option)
+            .video_filter(video_filter)
+            .output_args(output_args)
+            .format(self.options.static_format)
+            .ffmpeg(self.options.ffmpeg.clone())
+            .max_bytes(self.pack_kind.max_bytes())
+            .temp_dir(temp_dir)
+            .build())
+    }
+}
+
+/// Input options and filters of the ffmpeg command that are the same for
+/// all encoders
+struct Encoding {
+    input_args: Vec<String>,
+    filter_option: &'static str,
+    video_filter: String,
+    metadata_args: Vec<String>,
 }
 
 fn optional_named_duration_arg(
@@ -246,9 +648,16 @@ mod tests {
         let options = SingleVideoGenOptions {
             begin: None,
             end: None,
+            loop_mode: None,
+            still: Default::default(),
             filter: None,
             ffmpeg_args: vec![],
             ffmpeg: mock_ffmpeg.clone(),
+            caption_style: Default::default(),
+            watermark: None,
+            repaint: None,
+            playback: Default::default(),
+            static_format: Default::default(),
             publisher: None,
         };
 
@@ -257,6 +666,11 @@ mod tests {
             pack_kind,
             input: Utf8StemmedPathBuf::try_from(Utf8PathBuf::from("input")).unwrap(),
             output: Utf8PathBuf::from("output"),
+            caption: None,
+            tile: None,
+            segment: None,
+            concat: None,
+            lottie: None,
         };
 
         let output = ctx.generate_bytes().await.unwrap();
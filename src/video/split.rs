@@ -0,0 +1,258 @@
+use super::analysis;
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use std::fmt;
+use std::time::Duration;
+
+const DEFAULT_SCENE_THRESHOLD: f64 = 0.4;
+
+/// Segments shorter than this are too short to be meaningful stickers
+const MIN_SEGMENT_DURATION: Duration = Duration::from_millis(100);
+
+/// A time range of the input that produces a separate output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Segment {
+    pub(crate) begin: Duration,
+    pub(crate) end: Duration,
+}
+
+impl Segment {
+    fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.begin)
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2?}..{:.2?}", self.begin, self.end)
+    }
+}
+
+/// Defines how a long input is split into several outputs
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SplitMode {
+    /// Consecutive windows of the given duration
+    Windows(Duration),
+
+    /// Cut the input at the frames where the scene change score is greater
+    /// than the given threshold
+    Scene(f64),
+
+    /// Explicit list of time ranges
+    Ranges(Vec<Segment>),
+}
+
+impl SplitMode {
+    /// Parses the value in format `windows:{duration}`, `scene[:threshold]`
+    /// or `ranges:{begin}-{end},{begin}-{end},...`
+    pub(crate) fn parse(arg: &str) -> Result<Self> {
+        let (mode, param) = match arg.split_once(':') {
+            Some((mode, param)) => (mode, Some(param)),
+            None => (arg, None),
+        };
+
+        match (mode, param) {
+            ("windows", Some(duration)) => {
+                let duration = crate::util::duration::parse(duration)?;
+                if duration < MIN_SEGMENT_DURATION {
+                    bail!("The window duration must be at least {MIN_SEGMENT_DURATION:?}");
+                }
+                Ok(Self::Windows(duration))
+            }
+            ("scene", threshold) => {
+                let threshold = threshold
+                    .map(str::parse)
+                    .transpose()
+                    .context("Invalid scene change threshold")?
+                    .unwrap_or(DEFAULT_SCENE_THRESHOLD);
+
+                if !(0.0..=1.0).contains(&threshold) {
+                    bail!("Scene change threshold must be in range [0; 1], but got {threshold}");
+                }
+
+                Ok(Self::Scene(threshold))
+            }
+            ("ranges", Some(ranges)) => ranges
+                .split(',')
+                .map(|range| {
+                    let (begin, end) = range.split_once('-').with_context(|| {
+                        format!("Expected `begin-end` range, but got `{range}`")
+                    })?;
+
+                    let segment = Segment {
+                        begin: crate::util::duration::parse(begin)?,
+                        end: crate::util::duration::parse(end)?,
+                    };
+
+                    if segment.end <= segment.begin {
+                        bail!("The end of the range `{range}` must be after its beginning");
+                    }
+
+                    Ok(segment)
+                })
+                .try_collect()
+                .map(Self::Ranges),
+            _ => bail!(
+                "Unknown split mode `{arg}`, expected `windows:{{duration}}`, \
+                `scene[:threshold]` or `ranges:{{begin}}-{{end}},...`"
+            ),
+        }
+    }
+
+    /// Computes the segments of the input limited by the `begin` and `end`
+    /// bounds. Every segment fits into the `budget`.
+    pub(crate) async fn segments(
+        &self,
+        ffmpeg: &dyn Ffmpeg,
+        input: &Utf8Path,
+        begin: Option<Duration>,
+        end: Option<Duration>,
+        budget: Duration,
+    ) -> Result<Vec<Segment>> {
+        let start = begin.unwrap_or_default();
+
+        let segments = match self {
+            Self::Ranges(ranges) => ranges.clone(),
+            Self::Windows(window) => {
+                let end = Self::end(ffmpeg, input, end).await?;
+                std::iter::successors(Some(start), |begin| Some(*begin + *window))
+                    .take_while(|begin| *begin < end)
+                    .map(|begin| Segment {
+                        begin,
+                        end: (begin + *window).min(end),
+                    })
+                    .collect()
+            }
+            Self::Scene(threshold) => {
+                let end = Self::end(ffmpeg, input, end).await?;
+
+                let filter = format!("select=gt(scene\\,{threshold})");
+
+                let cuts = analysis::frame_metadata(ffmpeg, input, begin, Some(end), &filter)
+                    .await?
+                    .into_iter()
+                    .map(|frame| start + Duration::from_secs_f64(frame.pts_time));
+
+                std::iter::once(start)
+                    .chain(cuts)
+                    .chain([end])
+                    .tuple_windows()
+                    .map(|(begin, end)| Segment { begin, end })
+                    .collect()
+            }
+        };
+
+        let segments = segments
+            .into_iter()
+            .filter(|segment| segment.duration() >= MIN_SEGMENT_DURATION)
+            .map(|segment| {
+                if segment.duration() <= budget {
+                    return segment;
+                }
+                let limited = Segment {
+                    begin: segment.begin,
+                    end: segment.begin + budget,
+                };
+                warn!("Segment {segment} is longer than {budget:.2?}, so it's cut to {limited}");
+                limited
+            })
+            .collect_vec();
+
+        if segments.is_empty() {
+            bail!("No segments were found in the input {input}");
+        }
+
+        info!(
+            "✂️ Split the input into {} segments: {}",
+            display::bold(&segments.len()),
+            segments.iter().format(", ")
+        );
+
+        Ok(segments)
+    }
+
+    async fn end(ffmpeg: &dyn Ffmpeg, input: &Utf8Path, end: Option<Duration>) -> Result<Duration> {
+        let available = ffmpeg.probe_duration(input).await?;
+        Ok(end.map_or(available, |end| end.min(available)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::testing::SharedMockFfmpeg;
+    use crate::video::{PackKind, MAX_DURATION};
+    use expect_test::{expect, Expect};
+
+    fn assert_parse(arg: &str, expected: Expect) {
+        let actual = SplitMode::parse(arg)
+            .map(|mode| format!("{mode:?}"))
+            .unwrap_or_else(|err| format!("Error: {err:?}"));
+
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn smoke_parse() {
+        assert_parse("windows:2.5", expect!["Windows(2.5s)"]);
+        assert_parse("scene", expect!["Scene(0.4)"]);
+        assert_parse("scene:0.25", expect!["Scene(0.25)"]);
+        assert_parse(
+            "ranges:0-1.5,00:02-00:04.5",
+            expect![
+                "Ranges([Segment { begin: 0ns, end: 1.5s }, Segment { begin: 2s, end: 4.5s }])"
+            ],
+        );
+        assert_parse(
+            "ranges:2-1",
+            expect!["Error: The end of the range `2-1` must be after its beginning"],
+        );
+        assert_parse(
+            "windows",
+            expect!["Error: Unknown split mode `windows`, expected `windows:{duration}`, `scene[:threshold]` or `ranges:{begin}-{end},...`"],
+        );
+    }
+
+    async fn assert_segments(mode: &str, ffmpeg: &dyn Ffmpeg, expected: Expect) {
+        let segments = SplitMode::parse(mode)
+            .unwrap()
+            .segments(
+                ffmpeg,
+                "input".into(),
+                Some(Duration::from_secs(1)),
+                None,
+                MAX_DURATION,
+            )
+            .await
+            .unwrap();
+
+        expected.assert_eq(&segments.iter().join(", "));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_segments() {
+        let ffmpeg = SharedMockFfmpeg::with_best_crf(0, PackKind::Emoji);
+
+        assert_segments(
+            "windows:4",
+            &*ffmpeg,
+            expect!["1.00s..4.00s, 5.00s..8.00s, 9.00s..10.00s"],
+        )
+        .await;
+
+        let ffmpeg = ffmpeg.with_analysis_output(
+            "frame:10  pts:10  pts_time:0.5\n\
+            lavfi.scene_score=0.9\n\
+            frame:50  pts:50  pts_time:2.5\n\
+            lavfi.scene_score=0.8\n",
+        );
+
+        assert_segments(
+            "scene",
+            &*ffmpeg,
+            expect!["1.00s..1.50s, 1.50s..3.50s, 3.50s..6.50s"],
+        )
+        .await;
+    }
+}
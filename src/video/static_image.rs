@@ -0,0 +1,218 @@
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use crate::util::iter;
+use buildstructor::buildstructor;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Image format of the static emoji and stickers
+#[derive(
+    serde::Deserialize,
+    serde::Serialize,
+    strum::Display,
+    clap::ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum StaticFormat {
+    /// Lossy WEBP. The `-quality` of the encoder is adjusted to fit into
+    /// the size limit.
+    #[default]
+    Webp,
+
+    /// PNG with a palette. The number of colors in the palette is adjusted
+    /// to fit into the size limit.
+    Png,
+}
+
+impl StaticFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Png => "png",
+        }
+    }
+
+    /// The range of the parameter that controls the output size. The greater
+    /// the value, the better the quality and the bigger the output.
+    fn levels(self) -> RangeInclusive<usize> {
+        match self {
+            Self::Webp => 0..=100,
+            Self::Png => 2..=256,
+        }
+    }
+
+    fn level_name(self) -> &'static str {
+        match self {
+            Self::Webp => "quality",
+            Self::Png => "colors",
+        }
+    }
+
+    /// Returns the video filter and the output options for the given level
+    fn encode(self, video_filter: &str, level: usize) -> (String, Vec<String>) {
+        match self {
+            Self::Webp => (
+                video_filter.to_owned(),
+                iter::strs(["-vcodec", "libwebp", "-quality"])
+                    .chain([level.to_string()])
+                    .chain(iter::strs(["-f", "webp"]))
+                    .collect(),
+            ),
+            Self::Png => (
+                format!(
+                    "{video_filter},split[frame][stats];\
+                    [stats]palettegen=max_colors={level}:reserve_transparent=1[palette];\
+                    [frame][palette]paletteuse=alpha_threshold=128"
+                ),
+                iter::strs(["-vcodec", "png", "-f", "image2"]).collect(),
+            ),
+        }
+    }
+}
+
+/// Context for encoding a single frame into a static image that fits into
+/// the size limit
+pub(crate) struct StaticImageContext {
+    input_args: Vec<String>,
+    filter_option: &'static str,
+    video_filter: String,
+    output_args: Vec<String>,
+    format: StaticFormat,
+    ffmpeg: Arc<dyn Ffmpeg>,
+    max_bytes: usize,
+
+    /// Temp dir where the output file is written
+    temp_dir: tempfile::TempDir,
+}
+
+#[buildstructor]
+impl StaticImageContext {
+    #[builder]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        input_args: Vec<String>,
+        filter_option: &'static str,
+        video_filter: String,
+        output_args: Vec<String>,
+        format: StaticFormat,
+        ffmpeg: Arc<dyn Ffmpeg>,
+        max_bytes: usize,
+        temp_dir: tempfile::TempDir,
+    ) -> Self {
+        Self {
+            input_args,
+            filter_option,
+            video_filter,
+            output_args,
+            format,
+            ffmpeg,
+            max_bytes,
+            temp_dir,
+        }
+    }
+}
+
+impl StaticImageContext {
+    async fn run(&self, level: usize) -> Result<Arc<[u8]>> {
+        let (video_filter, codec_args) = self.format.encode(&self.video_filter, level);
+
+        let args = self
+            .input_args
+            .iter()
+            .cloned()
+            .chain([self.filter_option.to_owned(), video_filter])
+            .chain(iter::strs(["-frames:v", "1"]))
+            .chain(codec_args)
+            .chain(self.output_args.iter().cloned())
+            .collect();
+
+        let output = self
+            .temp_dir
+            .path()
+            .unwrap_utf8()
+            .join(format!("output.{}", self.format.extension()));
+
+        let start = std::time::Instant::now();
+
+        let output = Arc::<[_]>::from(self.ffmpeg.run_with_output_file(args, &output).await?);
+
+        let checkbox = if output.len() > self.max_bytes {
+            '❌'
+        } else {
+            '✅'
+        };
+
+        info!(
+            "{checkbox} {} {} generated {} in {}",
+            self.format.level_name(),
+            display::bold(&level),
+            display::bold_human_size(output.len()),
+            display::elpased(start),
+        );
+
+        Ok(output)
+    }
+
+    /// Runs the binary search for the highest level of quality that generates
+    /// the output fitting into the size limit
+    pub(crate) async fn search(self) -> Result<Arc<[u8]>> {
+        let start = std::time::Instant::now();
+
+        let level_name = self.format.level_name();
+        let max_bytes_display = &display::bold_human_size(self.max_bytes);
+
+        info!("🚀 Trying to find best {level_name} to fit into {max_bytes_display}");
+
+        let mut outputs = HashMap::new();
+
+        let (mut min, mut max) = self.format.levels().into_inner();
+
+        while min < max {
+            // Round up to make sure the range shrinks when `max == min + 1`
+            let mid = min + (max - min).div_ceil(2);
+
+            let output = self.run(mid).await?;
+
+            if output.len() <= self.max_bytes {
+                min = mid;
+            } else {
+                max = mid - 1;
+            }
+
+            outputs.insert(mid, output);
+        }
+
+        let output = match outputs.remove(&min) {
+            Some(output) => output,
+            None => self.run(min).await?,
+        };
+
+        let level_display = display::bold(&min);
+        let size_display = display::bold_human_size(output.len());
+
+        if output.len() > self.max_bytes {
+            bail!(
+                "The output can't possibly fit into the limit of {max_bytes_display}. \
+                The minimum generated file size with {level_name} {level_display} is {size_display}",
+            );
+        }
+
+        let elapsed = display::elpased(start);
+
+        info!(
+            "🎉 Found a fitting {level_name} {level_display}, which generates \
+            {size_display} in {elapsed}"
+        );
+
+        Ok(output)
+    }
+}
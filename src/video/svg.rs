@@ -0,0 +1,97 @@
+//! SVG inputs are rasterized directly at the size of the output instead of
+//! going through the image decoders of ffmpeg, which would lose the crisp
+//! edges of the vector art when upscaling it
+
+use crate::prelude::*;
+use resvg::{tiny_skia, usvg};
+
+const SVG_EXTENSIONS: &[&str] = &["svg"];
+
+/// Checks if the input is an SVG image judging by its extension
+pub(crate) fn is_svg(path: &Utf8Path) -> bool {
+    let Some(ext) = path.extension() else {
+        return false;
+    };
+
+    SVG_EXTENSIONS
+        .iter()
+        .any(|svg| ext.eq_ignore_ascii_case(svg))
+}
+
+/// Renders the SVG scaled to fit into the `width`x`height` box preserving
+/// its aspect ratio and saves it as a PNG at `output`
+pub(crate) async fn rasterize(
+    input: &Utf8Path,
+    width: u32,
+    height: u32,
+    output: &Utf8Path,
+) -> Result {
+    let data = fs::read(input).await?;
+
+    let resources_dir = input.parent().map(|dir| dir.as_std_path().to_owned());
+    let output = output.to_owned();
+
+    tokio::task::spawn_blocking(move || {
+        let mut options = usvg::Options {
+            resources_dir,
+            ..Default::default()
+        };
+
+        options.fontdb_mut().load_system_fonts();
+
+        let tree = usvg::Tree::from_data(&data, &options)?;
+        let size = tree.size();
+
+        let scale = (width as f32 / size.width()).min(height as f32 / size.height());
+
+        let side = |side: f32| ((side * scale).round() as u32).max(1);
+
+        let mut pixmap = tiny_skia::Pixmap::new(side(size.width()), side(size.height()))
+            .context("Failed to allocate the image buffer")?;
+
+        resvg::render(
+            &tree,
+            tiny_skia::Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        pixmap
+            .save_png(&output)
+            .with_context(|| format!("Failed to save the rasterized image {output}"))
+    })
+    .await?
+    .with_context(|| format!("Failed to rasterize the SVG `{input}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn smoke_rasterize() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().unwrap_utf8();
+
+        let input = dir.join("input.svg");
+        let output = dir.join("output.png");
+
+        fs::write(
+            &input,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20">
+                <rect width="10" height="20" fill="red"/>
+            </svg>"#,
+        )
+        .await
+        .unwrap();
+
+        rasterize(&input, 100, 100, &output).await.unwrap();
+
+        let pixmap = tiny_skia::Pixmap::load_png(&output).unwrap();
+
+        assert_eq!((pixmap.width(), pixmap.height()), (50, 100));
+
+        // The edges are crisp, because the image isn't upscaled
+        let corner = pixmap.pixel(49, 99).unwrap();
+        assert_eq!((corner.red(), corner.alpha()), (255, 255));
+    }
+}
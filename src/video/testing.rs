@@ -2,15 +2,25 @@ use super::{PackKind, MAX_CRF};
 use crate::prelude::*;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Duration of the media reported by the mock ffprobe
+const MOCK_MEDIA_DURATION: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub(crate) struct SharedMockFfmpeg(Mutex<MockFfmpeg>);
 
 #[derive(Debug)]
 pub(crate) struct MockFfmpeg {
+    /// Sizes of the outputs generated with the given CRF, quality or
+    /// number of colors
     pub(crate) crfs_ret_lens: Vec<(usize, usize)>,
     pub(crate) args_log: Vec<Vec<String>>,
     pub(crate) crfs_log: Vec<usize>,
+
+    /// Stdout returned for the ffmpeg calls that analyse the input
+    pub(crate) analysis_output: String,
+    pub(crate) analysis_args_log: Vec<Vec<String>>,
 }
 
 impl SharedMockFfmpeg {
@@ -19,13 +29,30 @@ impl SharedMockFfmpeg {
             crfs_ret_lens: Vec::from_iter(crfs_ret_lens),
             args_log: Default::default(),
             crfs_log: Default::default(),
+            analysis_output: Default::default(),
+            analysis_args_log: Default::default(),
         })))
     }
 
+    pub(crate) fn with_analysis_output(self: Arc<Self>, output: &str) -> Arc<Self> {
+        self.0.lock().unwrap().analysis_output = output.to_owned();
+        self
+    }
+
     pub(crate) fn with_best_crf(best_crf: usize, kind: PackKind) -> Arc<Self> {
         Self::new((0..=MAX_CRF).map(|crf| (crf, kind.max_bytes() + best_crf - crf)))
     }
 
+    /// The output size grows with the `-quality` of the static image encoder
+    pub(crate) fn with_best_quality(best_quality: usize, kind: PackKind) -> Arc<Self> {
+        Self::new((0..=100).map(|quality| (quality, kind.max_bytes() + quality - best_quality)))
+    }
+
+    /// The output size grows with the `max_colors` of the PNG palette
+    pub(crate) fn with_best_colors(best_colors: usize, kind: PackKind) -> Arc<Self> {
+        Self::new((2..=256).map(|colors| (colors, kind.max_bytes() + colors - best_colors)))
+    }
+
     pub(crate) fn unwrap(self: Arc<Self>) -> MockFfmpeg {
         Arc::try_unwrap(self).unwrap().0.into_inner().unwrap()
     }
@@ -34,11 +61,28 @@ impl SharedMockFfmpeg {
 #[async_trait]
 impl crate::ffmpeg::Ffmpeg for SharedMockFfmpeg {
     async fn run(&self, args: Vec<String>) -> Result<Vec<u8>> {
-        let crf_pos = args.iter().position(|arg| arg == "-crf").unwrap();
-        let crf = args[crf_pos + 1].parse().unwrap();
-
         let mut me = self.0.lock().unwrap();
 
+        // The static image encoder uses `-quality` instead of `-crf` for WEBP
+        // and the `max_colors` of the palette in the video filter for PNG
+        let crf = args
+            .iter()
+            .position(|arg| arg == "-crf" || arg == "-quality")
+            .map(|pos| args[pos + 1].as_str())
+            .or_else(|| {
+                args.iter().find_map(|arg| {
+                    let (_, colors) = arg.split_once("max_colors=")?;
+                    colors.split(|c: char| !c.is_ascii_digit()).next()
+                })
+            });
+
+        let Some(crf) = crf else {
+            me.analysis_args_log.push(args);
+            return Ok(me.analysis_output.clone().into_bytes());
+        };
+
+        let crf = crf.parse().unwrap();
+
         me.crfs_log.push(crf);
         me.args_log.push(args.clone());
 
@@ -49,7 +93,11 @@ impl crate::ffmpeg::Ffmpeg for SharedMockFfmpeg {
             .unwrap()
             .1;
 
-        Ok(vec![0; len as usize])
+        Ok(vec![0; len])
+    }
+
+    async fn probe_duration(&self, _input: &Utf8Path) -> Result<Duration> {
+        Ok(MOCK_MEDIA_DURATION)
     }
 
     async fn run_with_output_file(
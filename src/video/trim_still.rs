@@ -0,0 +1,145 @@
+use super::analysis::{self, FrameMetadata};
+use super::split::Segment;
+use crate::display;
+use crate::ffmpeg::Ffmpeg;
+use crate::prelude::*;
+use std::time::Duration;
+
+/// Minimum duration of the frozen part of the video to be trimmed
+const MIN_FREEZE_DURATION: f64 = 0.1;
+
+/// Max difference between the frames to consider them frozen
+const FREEZE_NOISE: f64 = 0.003;
+
+/// Max distance between the edge of the video and the frozen interval for
+/// the interval to be considered touching the edge. Needed because the
+/// timestamps reported by ffmpeg aren't exact.
+const EDGE_TOLERANCE: f64 = 0.05;
+
+const FREEZE_START: &str = "lavfi.freezedetect.freeze_start";
+const FREEZE_END: &str = "lavfi.freezedetect.freeze_end";
+
+/// Moves the `begin` and `end` bounds inward past the frozen frames at the
+/// head and the tail of the input
+pub(crate) async fn trim_still(
+    ffmpeg: &dyn Ffmpeg,
+    input: &Utf8Path,
+    begin: Option<Duration>,
+    end: Option<Duration>,
+) -> Result<Segment> {
+    let available = ffmpeg.probe_duration(input).await?;
+
+    let original = Segment {
+        begin: begin.unwrap_or_default(),
+        end: end.map_or(available, |end| end.min(available)),
+    };
+
+    let filter = format!("freezedetect=n={FREEZE_NOISE}:d={MIN_FREEZE_DURATION}");
+
+    let frames = analysis::frame_metadata(ffmpeg, input, begin, end, &filter).await?;
+
+    // The timestamps are relative to the beginning of the segment
+    let duration = (original.end - original.begin).as_secs_f64();
+    let freezes = freeze_intervals(&frames, duration)?;
+
+    let head = freezes
+        .first()
+        .filter(|(start, _)| *start <= EDGE_TOLERANCE)
+        .map_or(0.0, |(_, end)| *end);
+
+    let tail = freezes
+        .last()
+        .filter(|(_, end)| *end >= duration - EDGE_TOLERANCE)
+        .map_or(duration, |(start, _)| *start);
+
+    if head >= tail {
+        bail!(
+            "The whole segment {original} of the input {input} consists of \
+            still frames, there is nothing left after trimming them"
+        );
+    }
+
+    let trimmed = Segment {
+        begin: original.begin + Duration::from_secs_f64(head),
+        end: original.begin + Duration::from_secs_f64(tail),
+    };
+
+    info!(
+        "✂️ Trimmed the still frames at the head and the tail: {} → {}",
+        display::bold(&original),
+        display::bold(&trimmed),
+    );
+
+    Ok(trimmed)
+}
+
+/// Returns the pairs of start and end timestamps of the frozen intervals.
+/// If the video ends while frozen, the interval ends at `duration`.
+fn freeze_intervals(frames: &[FrameMetadata], duration: f64) -> Result<Vec<(f64, f64)>> {
+    let parse = |frame: &FrameMetadata, key: &str| {
+        frame
+            .entries
+            .get(key)
+            .map(|value| {
+                value
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid `{key}` in the frame metadata: `{value}`"))
+            })
+            .transpose()
+    };
+
+    let mut intervals = vec![];
+    let mut start = None;
+
+    for frame in frames {
+        if let Some(end) = parse(frame, FREEZE_END)? {
+            if let Some(start) = start.take() {
+                intervals.push((start, end));
+            }
+        }
+        if let Some(freeze_start) = parse(frame, FREEZE_START)? {
+            start = Some(freeze_start);
+        }
+    }
+
+    if let Some(start) = start {
+        intervals.push((start, duration));
+    }
+
+    Ok(intervals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::testing::SharedMockFfmpeg;
+    use crate::video::PackKind;
+    use expect_test::expect;
+
+    #[test_log::test(tokio::test)]
+    async fn smoke_trim_still() {
+        // The mock media is 10 seconds long, and we analyse it from 1 second
+        let output = "\
+            frame:12  pts:12  pts_time:0.5\n\
+            lavfi.freezedetect.freeze_start=0\n\
+            frame:30  pts:30  pts_time:1.2\n\
+            lavfi.freezedetect.freeze_duration=1.2\n\
+            lavfi.freezedetect.freeze_end=1.2\n\
+            frame:50  pts:50  pts_time:3\n\
+            lavfi.freezedetect.freeze_start=2.5\n\
+            frame:80  pts:80  pts_time:3.2\n\
+            lavfi.freezedetect.freeze_duration=0.7\n\
+            lavfi.freezedetect.freeze_end=3.2\n\
+            frame:200 pts:200 pts_time:8\n\
+            lavfi.freezedetect.freeze_start=7.5\n";
+
+        let ffmpeg =
+            SharedMockFfmpeg::with_best_crf(0, PackKind::Emoji).with_analysis_output(output);
+
+        let segment = trim_still(&*ffmpeg, "input".into(), Some(Duration::from_secs(1)), None)
+            .await
+            .unwrap();
+
+        expect!["2.20s..8.50s"].assert_eq(&segment.to_string());
+    }
+}
@@ -0,0 +1,131 @@
+use super::PackKind;
+use crate::ffmpeg::escape_filter_value;
+use crate::prelude::*;
+
+/// Distance between the watermark and the edges of the frame relative to
+/// the bounding box
+const MARGIN_RATIO: f64 = 0.03;
+
+#[derive(strum::Display, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum WatermarkCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum WatermarkContent {
+    /// Path to the logo image
+    Image(Utf8PathBuf),
+    Text(String),
+}
+
+/// Visible mark of the publisher overlaid on top of the video
+#[derive(Debug, Clone)]
+pub(crate) struct Watermark {
+    pub(crate) content: WatermarkContent,
+    pub(crate) corner: WatermarkCorner,
+
+    /// Value in range `[0; 1]` where `0` is fully transparent
+    pub(crate) opacity: f64,
+
+    /// Height of the watermark relative to the bounding box of the pack kind
+    pub(crate) scale: f64,
+
+    /// Pack kinds that must not have the watermark
+    pub(crate) skip: Vec<PackKind>,
+}
+
+impl Watermark {
+    pub(crate) fn validate(&self) -> Result {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            bail!(
+                "Watermark opacity must be in range [0; 1], but got {}",
+                self.opacity
+            );
+        }
+        if !(self.scale > 0.0 && self.scale <= 1.0) {
+            bail!(
+                "Watermark scale must be in range (0; 1], but got {}",
+                self.scale
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the filters that render the watermark. It is meant to be
+    /// applied after the video is scaled to fit into the bounding box of the
+    /// `pack_kind`. Returns `None` if the watermark is skipped for the kind.
+    pub(crate) fn filter(&self, pack_kind: PackKind) -> Option<String> {
+        if self.skip.contains(&pack_kind) {
+            return None;
+        }
+
+        let bounding_box = pack_kind.bounding_box() as f64;
+        let height = (bounding_box * self.scale).round().max(1.0);
+        let margin = (bounding_box * MARGIN_RATIO).round();
+
+        // `overlay` and `drawtext` use different names for the dimensions of
+        // the frame and the watermark
+        let position = |frame_w, frame_h, mark_w, mark_h| {
+            let (x, y) = match self.corner {
+                WatermarkCorner::TopLeft => (format!("{margin}"), format!("{margin}")),
+                WatermarkCorner::TopRight => {
+                    (format!("{frame_w}-{mark_w}-{margin}"), format!("{margin}"))
+                }
+                WatermarkCorner::BottomLeft => {
+                    (format!("{margin}"), format!("{frame_h}-{mark_h}-{margin}"))
+                }
+                WatermarkCorner::BottomRight => (
+                    format!("{frame_w}-{mark_w}-{margin}"),
+                    format!("{frame_h}-{mark_h}-{margin}"),
+                ),
+            };
+            format!("x={x}:y={y}")
+        };
+
+        let filter = match &self.content {
+            WatermarkContent::Image(path) => format!(
+                "null[main];\
+                movie={},format=rgba,scale=-1:{height},colorchannelmixer=aa={}[watermark];\
+                [main][watermark]overlay={}",
+                escape_filter_value(path.as_str()),
+                self.opacity,
+                position("W", "H", "w", "h"),
+            ),
+            WatermarkContent::Text(text) => format!(
+                "drawtext=text={}:fontsize={height}:fontcolor=white@{}:expansion=none:{}",
+                escape_filter_value(text),
+                self.opacity,
+                position("w", "h", "text_w", "text_h"),
+            ),
+        };
+
+        Some(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn smoke_text_watermark() {
+        let watermark = Watermark {
+            content: WatermarkContent::Text("t.me/channel".to_owned()),
+            corner: WatermarkCorner::TopRight,
+            opacity: 0.5,
+            scale: 0.1,
+            skip: vec![PackKind::Emoji],
+        };
+
+        assert_eq!(watermark.filter(PackKind::Emoji), None);
+
+        expect![[r#"drawtext=text=t.me/channel:fontsize=51:fontcolor=white@0.5:expansion=none:x=w-text_w-15:y=15"#]]
+            .assert_eq(&watermark.filter(PackKind::Sticker).unwrap());
+    }
+}